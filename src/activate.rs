@@ -0,0 +1,131 @@
+//! Activation of an installed toolchain for a single command, without changing the defaults.
+
+use crate::{
+    env::{export_variable as export, Environment},
+    error::Error,
+    state::ToolchainState,
+};
+use log::debug;
+use std::{
+    env,
+    ffi::OsString,
+    process::{Command, ExitStatus},
+};
+
+/// Returns the exports activating the toolchain: the ones recorded by its installation, and the
+/// selection of the toolchain by rustup.
+///
+/// The ESP-IDF version recorded by the installation is replaced by `esp_idf_version`, if any.
+pub fn activation_exports(
+    name: &str,
+    toolchain: &ToolchainState,
+    esp_idf_version: Option<&str>,
+) -> Vec<String> {
+    let mut exports: Vec<String> = toolchain
+        .exports
+        .iter()
+        .filter(|line| {
+            esp_idf_version.is_none()
+                || !Environment::from_exports(&[line.to_string()])
+                    .variables
+                    .iter()
+                    .any(|(variable, _)| variable == "ESP_IDF_VERSION")
+        })
+        .cloned()
+        .collect();
+    exports.push(export("RUSTUP_TOOLCHAIN", name));
+    if let Some(esp_idf_version) = esp_idf_version {
+        exports.push(export("ESP_IDF_VERSION", esp_idf_version));
+    }
+    exports
+}
+
+/// Returns the variables the exports set, the `PATH` being prepended to the current one.
+pub fn activation_variables(exports: &[String]) -> Result<Vec<(String, OsString)>, Error> {
+    let environment = Environment::from_exports(exports);
+    let mut variables: Vec<(String, OsString)> = environment
+        .variables
+        .into_iter()
+        .map(|(name, value)| (name, OsString::from(value)))
+        .collect();
+    if !environment.path.is_empty() {
+        let current = env::var_os("PATH").unwrap_or_default();
+        let path = env::join_paths(
+            environment
+                .path
+                .into_iter()
+                .chain(env::split_paths(&current)),
+        )
+        .map_err(|_| Error::RunCommand("PATH".to_string()))?;
+        variables.push(("PATH".to_string(), path));
+    }
+    Ok(variables)
+}
+
+/// Runs the command, its first element being the program, with the variables set.
+pub fn run_activated(
+    variables: &[(String, OsString)],
+    command: &[String],
+) -> Result<ExitStatus, Error> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| Error::RunCommand(String::new()))?;
+    debug!(
+        "Running '{}' with the activated environment",
+        command.join(" ")
+    );
+    Command::new(program)
+        .args(args)
+        .envs(variables.iter().map(|(name, value)| (name, value)))
+        .status()
+        .map_err(|_| Error::RunCommand(program.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        activate::{activation_exports, activation_variables},
+        state::ToolchainState,
+    };
+    use std::ffi::OsString;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_activation() {
+        let toolchain = ToolchainState {
+            exports: vec![
+                "export PATH=\"/home/user/.rustup/toolchains/esp-1.74/xtensa-esp-elf/bin:$PATH\""
+                    .to_string(),
+                "export LIBCLANG_PATH=\"/home/user/.espup/esp-clang\"".to_string(),
+                "export ESP_IDF_VERSION=\"v5.1.2\"".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let exports = activation_exports("esp-1.74", &toolchain, None);
+        assert_eq!(exports.len(), 4);
+        assert_eq!(exports[3], "export RUSTUP_TOOLCHAIN=\"esp-1.74\"");
+
+        let exports = activation_exports("esp-1.74", &toolchain, Some("v5.2"));
+        let variables = activation_variables(&exports).unwrap();
+        let variable = |name: &str| {
+            variables
+                .iter()
+                .find(|(variable, _)| variable == name)
+                .map(|(_, value)| value.clone())
+        };
+        assert_eq!(
+            variable("LIBCLANG_PATH"),
+            Some(OsString::from("/home/user/.espup/esp-clang"))
+        );
+        assert_eq!(variable("ESP_IDF_VERSION"), Some(OsString::from("v5.2")));
+        assert_eq!(
+            variable("RUSTUP_TOOLCHAIN"),
+            Some(OsString::from("esp-1.74"))
+        );
+        assert!(variable("PATH")
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("/home/user/.rustup/toolchains/esp-1.74/xtensa-esp-elf/bin"));
+    }
+}
@@ -0,0 +1,986 @@
+//! Command line application, shared by the espup and cargo-espup binaries.
+
+use crate::{
+    activate::{activation_exports, activation_variables, run_activated},
+    backup::{create_backup, list_backups, restore_backup as restore},
+    clean::{
+        confirm, find_build_artifacts, format_size, get_idf_tools_dist, remove_artifact, Artifact,
+    },
+    cli::{
+        ActivateOpts, ApplyOpts, BundleOpts, CheckOpts, CleanOpts, CompletionsOpts, ComponentOpts,
+        ComponentSubCommand, DiffOpts, DoctorOpts, EnvOpts, ExplainOpts, FreezeOpts, GcOpts,
+        GenerateOpts, GenerateSubCommand, HostInfoOpts, InstallOpts, KeysOpts, KeysSubCommand,
+        ListRemoteOpts, RestoreBackupOpts, SelfUpdateOpts, StartOpts, SyncOpts, UninstallOpts,
+        VersionsOpts, WhichOpts,
+    },
+    completions::generate_completions,
+    config::Config,
+    diff::{diff_environments, parse_environment},
+    doctor::{run_checks, Status},
+    env::{export_file_contents, get_installed_export_file},
+    error::Error,
+    explain::{explain as explain_components, recipe},
+    gc::{format_elapsed, now, unused_toolchains},
+    guide::next_steps,
+    host_info::{check_libc_compatibility, HostInfo},
+    host_triple::get_host_triple,
+    keys::{add_key, get_keys_dir, list_keys, remove_key, TrustedKey},
+    logging::{initialize_logger, initialize_logger_with_events, take_warnings},
+    paths::log_paths,
+    project::ProjectManifest,
+    shell::Shell,
+    state::{record_usage, State, ToolchainState},
+    tasks::generate_tasks,
+    toolchain::{
+        bundle::create_bundle,
+        components::{
+            add_components, recorded_components, remove_components, update_export_file, Component,
+            Tool,
+        },
+        fetch::default_fetcher,
+        find_tool, install as toolchain_install,
+        options::{ExistingToolchain, InstallOptions, OutputFormat},
+        plan_uninstall, plan_uninstall_parts,
+        rust::{get_toolchain_dir, is_corrupted_toolchain},
+        uninstall as toolchain_uninstall, uninstall_parts, Change, InstallMode, UninstallPart,
+    },
+    update::{check_for_update, self_update},
+    versions::{get_versions, installed_toolchains, remote_versions, RemoteComponent},
+};
+use clap::{error::ErrorKind, ArgMatches, CommandFactory, FromArgMatches, Parser};
+use log::{info, warn};
+use miette::Result;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    env,
+    fs::{read_to_string, write},
+    path::Path,
+};
+use strum::IntoEnumIterator;
+use tempfile::TempDir;
+use tokio::task::spawn_blocking;
+
+#[derive(Parser)]
+#[command(about, version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub subcommand: SubCommand,
+    /// Format of the result of the apply, install, sync, uninstall, update and versions subcommands.
+    /// Logs are always written to stderr.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+}
+
+impl Cli {
+    /// Parses the command line matches, recording the install options set in them so the
+    /// configuration file does not replace them.
+    ///
+    /// Fails if '--output json' is given to a subcommand without a JSON result.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let mut cli = Self::from_arg_matches(matches)?;
+        if cli.output == OutputFormat::Json && !cli.subcommand.has_json_output() {
+            let name = matches.subcommand_name().unwrap_or_default();
+            return Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                format!("'espup {name}' does not support '--output json'"),
+            ));
+        }
+        if let (SubCommand::Install(args) | SubCommand::Update(args), Some((_, matches))) =
+            (&mut cli.subcommand, matches.subcommand())
+        {
+            args.record_explicit_args(matches);
+        }
+        Ok(cli)
+    }
+}
+
+#[derive(Parser)]
+pub enum SubCommand {
+    /// Runs a command, or prints the environment, with an installed toolchain selected for it only.
+    Activate(ActivateOpts),
+    /// Installs the environment described by a file written by 'espup freeze'.
+    Apply(ApplyOpts),
+    /// Downloads the artifacts of an installation into a bundle, for 'espup install --offline'.
+    Bundle(BundleOpts),
+    /// Reports the components of the toolchain with available updates, without installing anything.
+    Check(CheckOpts),
+    /// Removes build outputs and cached downloads to reclaim disk space.
+    Clean(CleanOpts),
+    /// Generate completions for the given shell.
+    Completions(CompletionsOpts),
+    /// Manages the optional components of an installation: openocd, qemu, gdb, sccache and probe-rs.
+    Component(ComponentOpts),
+    /// Compares two export files or downloads manifests.
+    Diff(DiffOpts),
+    /// Diagnoses issues with the installed toolchains.
+    Doctor(DoctorOpts),
+    /// Prints the environment set up by the installation, e.g. 'eval "$(espup env)"'.
+    Env(EnvOpts),
+    /// Explains which components are installed for a chip, and why.
+    Explain(ExplainOpts),
+    /// Writes the versions, targets and options of an installed toolchain into a file, to apply it
+    /// on another machine with 'espup apply'.
+    Freeze(FreezeOpts),
+    /// Suggests removing the toolchains that have not been used for a while.
+    Gc(GcOpts),
+    /// Generates files for working with the installed toolchains.
+    Generate(GenerateOpts),
+    /// Prints the detected host environment, useful for support requests.
+    HostInfo(HostInfoOpts),
+    /// Installs Espressif Rust ecosystem.
+    #[command(visible_alias = "i")]
+    // We use a Box here to make clippy happy (see https://rust-lang.github.io/rust-clippy/master/index.html#large_enum_variant)
+    Install(Box<InstallOpts>),
+    /// Manages the keys the signatures of the artifacts are verified against.
+    Keys(KeysOpts),
+    /// Lists the published versions that can be installed, and the installed ones.
+    #[command(visible_alias = "list")]
+    ListRemote(ListRemoteOpts),
+    /// Restores a toolchain backed up by '--backup'.
+    RestoreBackup(RestoreBackupOpts),
+    /// Updates espup itself.
+    SelfUpdate(SelfUpdateOpts),
+    /// Shows the next steps to start developing with the installed toolchains.
+    Start(StartOpts),
+    /// Installs the environment declared by the 'espup.toml' file of the project.
+    Sync(SyncOpts),
+    /// Uninstalls Espressif Rust ecosystem.
+    #[command(visible_alias = "rm")]
+    Uninstall(UninstallOpts),
+    /// Updates Xtensa Rust toolchain.
+    #[command(visible_alias = "up")]
+    Update(Box<InstallOpts>),
+    /// Prints the installed and latest available versions of the components.
+    Versions(VersionsOpts),
+    /// Prints the full path of a tool installed by espup.
+    Which(WhichOpts),
+}
+
+impl SubCommand {
+    /// Returns true if the subcommand prints its result as JSON with '--output json'.
+    fn has_json_output(&self) -> bool {
+        matches!(
+            self,
+            Self::Apply(_)
+                | Self::Install(_)
+                | Self::Sync(_)
+                | Self::Uninstall(_)
+                | Self::Update(_)
+                | Self::Versions(_)
+        )
+    }
+}
+
+/// Runs a command, or prints the environment, with an installed toolchain selected
+async fn activate(args: ActivateOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    let state = State::load()?;
+    let toolchain = state
+        .toolchains
+        .get(&args.name)
+        .ok_or_else(|| Error::MissingToolchain(args.name.clone()))?;
+    let exports = activation_exports(&args.name, toolchain, args.esp_idf_version.as_deref());
+    record_usage(&args.name);
+    if args.temporary {
+        let shell = args.shell.unwrap_or_else(Shell::detect);
+        print!("{}", export_file_contents(&exports, shell));
+        return Ok(());
+    }
+    let status = run_activated(&activation_variables(&exports)?, &args.command)?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// Installs the environment described by a file written by 'espup freeze'
+async fn apply(args: ApplyOpts, output: OutputFormat) -> Result<()> {
+    let manifest = ProjectManifest::load(&args.file)?;
+    let mut install_args = InstallOptions {
+        name: args.name,
+        output,
+        ..Default::default()
+    };
+    manifest.apply(&mut install_args)?;
+    install(install_args, &args.log_level, None, InstallMode::Install).await?;
+    info!("Environment of '{}' is applied", args.file.display());
+    Ok(())
+}
+
+/// Downloads the artifacts of an installation into a bundle
+async fn bundle(args: BundleOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+    check_for_update(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    // The installation is planned into an empty directory, so that every artifact is downloaded
+    let destination = TempDir::new().map_err(Error::IoError)?;
+    let host_triple = get_host_triple(args.default_host.clone())?;
+    let plan = toolchain_install(
+        InstallOptions {
+            default_host: args.default_host,
+            dry_run: true,
+            existing_toolchain: ExistingToolchain::Overwrite,
+            extended_llvm: args.extended_llvm,
+            output: OutputFormat::Json,
+            std: args.std,
+            targets: args.targets,
+            toolchain_destination: Some(destination.path().to_path_buf()),
+            toolchain_version: args.toolchain_version,
+            ..Default::default()
+        },
+        InstallMode::Install,
+    )
+    .await?;
+
+    let mut urls = Vec::new();
+    for change in plan
+        .components
+        .iter()
+        .flat_map(|component| &component.changes)
+    {
+        match change {
+            Change::Download { url, .. } => urls.push(url.clone()),
+            Change::Run { command } => {
+                warn!("'{command}' requires network access, it cannot be bundled")
+            }
+            _ => {}
+        }
+    }
+    for component in &args.components {
+        let component_urls = Tool::new(*component, &host_triple, destination.path()).urls();
+        if component_urls.is_empty() {
+            warn!("'{component}' is installed with cargo, it cannot be bundled");
+        }
+        urls.extend(component_urls);
+    }
+    let mut bundled = HashSet::new();
+    urls.retain(|url| bundled.insert(url.clone()));
+
+    let size = create_bundle(&urls, &args.path, default_fetcher().as_ref()).await?;
+    info!(
+        "Bundle '{}' holds {} artifacts ({}), install it with 'espup install --offline --bundle {}'",
+        args.path.display(),
+        urls.len(),
+        format_size(size),
+        args.path.display()
+    );
+    Ok(())
+}
+
+/// Reports the installed components with available updates
+async fn check(args: CheckOpts) -> Result<()> {
+    initialize_logger(if args.porcelain {
+        "error"
+    } else {
+        &args.log_level
+    });
+
+    let toolchain_dir = get_toolchain_dir(&args.name);
+    let versions: Vec<_> = get_versions(&args.name, &toolchain_dir)
+        .await
+        .into_iter()
+        .filter(|component| !component.installed.is_empty())
+        .collect();
+    for component in &versions {
+        if args.porcelain {
+            println!("{}\t{}", component.status(), component.porcelain());
+        } else {
+            println!(
+                "[{}] {}: {} (latest: {})",
+                component.status(),
+                component.name,
+                component.installed.join(", "),
+                component.latest.as_deref().unwrap_or("unknown")
+            );
+        }
+    }
+
+    let outdated = versions
+        .iter()
+        .filter(|component| component.is_outdated())
+        .count();
+    if outdated > 0 {
+        return Err(Error::Outdated(outdated).into());
+    }
+    Ok(())
+}
+
+/// Removes build outputs and cached downloads to reclaim disk space
+async fn clean(args: CleanOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    let mut paths = Vec::new();
+    if args.build_artifacts {
+        paths.extend(find_build_artifacts(&args.path));
+        paths.extend(get_idf_tools_dist());
+    }
+    paths.extend(args.cache_dir.filter(|cache_dir| cache_dir.is_dir()));
+    let artifacts: Vec<Artifact> = paths.into_iter().map(Artifact::new).collect();
+    if artifacts.is_empty() {
+        info!("Nothing to clean");
+        return Ok(());
+    }
+
+    for artifact in &artifacts {
+        println!(
+            "{:>10}  {}",
+            format_size(artifact.size),
+            artifact.path.display()
+        );
+    }
+    let total = format_size(artifacts.iter().map(|artifact| artifact.size).sum());
+    println!("{total:>10}  total");
+    if !args.yes && !confirm("Remove them?").map_err(Error::IoError)? {
+        info!("Nothing was removed");
+        return Ok(());
+    }
+    for artifact in &artifacts {
+        remove_artifact(artifact)?;
+    }
+
+    info!("Reclaimed {total}");
+    Ok(())
+}
+
+/// Updates Xtensa Rust toolchain.
+async fn completions(args: CompletionsOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+    check_for_update(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    info!("Generating completions for {} shell", args.shell);
+
+    print!("{}", generate_completions(args.shell));
+
+    info!("Completions successfully generated!");
+
+    Ok(())
+}
+
+/// Manages the optional components of an installation
+async fn component(args: ComponentOpts) -> Result<()> {
+    match args.subcommand {
+        ComponentSubCommand::Add(args) => {
+            initialize_logger(&args.log_level);
+
+            add_components(&args.name, &args.components).await?;
+            info!("Source the export file to use the added components");
+        }
+        ComponentSubCommand::List(args) => {
+            initialize_logger(&args.log_level);
+
+            let installed = State::load()?
+                .toolchains
+                .get(&args.name)
+                .map(recorded_components)
+                .unwrap_or_default();
+            for component in Component::iter() {
+                let status = if installed.contains(&component) {
+                    " (installed)"
+                } else {
+                    ""
+                };
+                println!("{component}{status}: {}", component.description());
+            }
+        }
+        ComponentSubCommand::Remove(args) => {
+            initialize_logger(&args.log_level);
+
+            remove_components(&args.name, &args.components).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares two export files or downloads manifests
+async fn diff(args: DiffOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    let first = parse_environment(&read_to_string(&args.first).map_err(Error::IoError)?)?;
+    let second = parse_environment(&read_to_string(&args.second).map_err(Error::IoError)?)?;
+    let differences = diff_environments(&first, &second);
+    if differences.is_empty() {
+        info!(
+            "No differences between '{}' and '{}'",
+            args.first.display(),
+            args.second.display()
+        );
+    }
+    for difference in differences {
+        println!("{difference}");
+    }
+    Ok(())
+}
+
+/// Diagnoses issues with the installed toolchains
+async fn doctor(args: DoctorOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+    log_paths();
+
+    if args.regenerate_exports {
+        let state = State::load()?;
+        match state.toolchains.get(&args.name) {
+            Some(
+                toolchain @ ToolchainState {
+                    export_file: Some(export_file),
+                    ..
+                },
+            ) => {
+                info!("Regenerating the export file '{}'", export_file.display());
+                update_export_file(toolchain)?;
+            }
+            _ => warn!("No export file has been recorded for '{}'", args.name),
+        }
+    }
+
+    let toolchain_dir = get_toolchain_dir(&args.name);
+    // Toolchains espup did not install are only reported, it does not know how to reinstall them
+    if is_corrupted_toolchain(&toolchain_dir) {
+        if let Some(toolchain) = State::load()?.toolchains.get(&args.name).cloned() {
+            warn!(
+                "Toolchain '{}' is corrupted, it does not hold 'bin/rustc'. Reinstalling it",
+                toolchain_dir.display()
+            );
+            let mut install_args = InstallOptions {
+                export_file: toolchain.export_file.clone(),
+                llvm_path: toolchain.llvm_path.clone(),
+                name: args.name.clone(),
+                shell: Some(Shell::from_installation(&toolchain)),
+                toolchain_destination: toolchain.toolchain_destination.clone(),
+                ..Default::default()
+            };
+            // The toolchain is reinstalled as its frozen environment declares it
+            ProjectManifest::freeze(&toolchain).apply(&mut install_args)?;
+            toolchain_install(install_args, InstallMode::Install).await?;
+        }
+    }
+
+    info!("Checking the Espressif Rust ecosystem");
+    let checks = run_checks(&args.name, &toolchain_dir);
+    for check in &checks {
+        println!("[{}] {}: {}", check.status, check.name, check.message);
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == Status::Error)
+        .count();
+    if failed > 0 {
+        return Err(Error::FailedChecks(failed).into());
+    }
+    Ok(())
+}
+
+/// Prints the export file of the installation
+async fn print_env(args: EnvOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    let recorded_exports = State::load()?
+        .toolchains
+        .get(&args.name)
+        .map(|toolchain| toolchain.exports.clone())
+        .filter(|exports| !exports.is_empty());
+    if let Some(shell) = args.shell {
+        match recorded_exports {
+            Some(exports) => {
+                print!("{}", export_file_contents(&exports, shell));
+                record_usage(&args.name);
+                return Ok(());
+            }
+            None => warn!(
+                "No exports have been recorded for '{}', printing the export file instead",
+                args.name
+            ),
+        }
+    }
+    let export_file = get_installed_export_file(&args.name, args.export_file)?;
+    let contents = read_to_string(&export_file)
+        .map_err(|_| Error::MissingExportFile(export_file.display().to_string()))?;
+    print!("{contents}");
+    record_usage(&args.name);
+    Ok(())
+}
+
+/// Explains the components installed for a chip, and why they are required
+async fn explain(args: ExplainOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    let kind = if args.std { "std" } else { "no_std" };
+    println!(
+        "Minimal installation for {} {kind} applications:\n",
+        args.chip
+    );
+    println!("    {}\n", recipe(args.chip, args.std));
+    for component in explain_components(args.chip, args.std) {
+        println!("{component}");
+    }
+    Ok(())
+}
+
+/// Writes the environment of an installed toolchain into a file
+async fn freeze(args: FreezeOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    let state = State::load()?;
+    let toolchain_state = state
+        .toolchains
+        .get(&args.name)
+        .ok_or_else(|| Error::MissingToolchain(args.name.clone()))?;
+    if toolchain_state.targets.is_empty() {
+        warn!(
+            "The targets of '{}' were not recorded, the environment installs all of them. Run 'espup update' to record them",
+            args.name
+        );
+    }
+    if args.file.exists() && !args.force {
+        return Err(Error::FileExists(args.file.display().to_string()).into());
+    }
+    let contents = format!(
+        "# Environment of the '{}' toolchain, written by espup {}\n{}",
+        args.name,
+        env!("CARGO_PKG_VERSION"),
+        ProjectManifest::freeze(toolchain_state).to_toml()
+    );
+    write(&args.file, contents).map_err(Error::IoError)?;
+    info!("Environment written to '{}'", args.file.display());
+    Ok(())
+}
+
+/// Suggests removing the toolchains that have not been used for a while
+async fn gc(args: GcOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    let state = State::load()?;
+    let unused = unused_toolchains(&state, now(), args.unused_for);
+    if unused.is_empty() {
+        info!(
+            "Every toolchain has been used in the last {}",
+            format_elapsed(args.unused_for)
+        );
+        return Ok(());
+    }
+    for usage in unused {
+        match usage.unused_for {
+            Some(elapsed) => println!(
+                "'{}' has not been used for {}, remove it with 'espup uninstall --name {}'",
+                usage.name,
+                format_elapsed(elapsed),
+                usage.name
+            ),
+            None => println!(
+                "'{}' has no recorded usage, it was installed by an older espup. Remove it with 'espup uninstall --name {}' if it is no longer needed",
+                usage.name, usage.name
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Generates files for working with the installed toolchains
+async fn generate(args: GenerateOpts) -> Result<()> {
+    match args.subcommand {
+        GenerateSubCommand::Tasks(args) => {
+            initialize_logger(&args.log_level);
+
+            let toolchain = if args.target.is_xtensa() {
+                &args.name
+            } else {
+                &args.nightly_version
+            };
+            let export_file = if cfg!(windows) {
+                None
+            } else {
+                Some(get_installed_export_file(&args.name, args.export_file)?)
+            };
+            let export_file = export_file.map(|file| file.display().to_string());
+            let contents = generate_tasks(
+                &args.target,
+                args.std,
+                toolchain,
+                export_file.as_deref(),
+                &args.format,
+            );
+            let output = args.output.unwrap_or_else(|| match args.format.as_str() {
+                "make" => "Makefile".into(),
+                _ => "justfile".into(),
+            });
+            if output.exists() {
+                return Err(Error::FileExists(output.display().to_string()).into());
+            }
+            write(&output, contents).map_err(Error::IoError)?;
+            info!("Task file generated at '{}'", output.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the detected host environment
+async fn host_info(args: HostInfoOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    println!("{}", HostInfo::detect());
+    if let Some(report) = check_libc_compatibility() {
+        warn!("{report}");
+    }
+    Ok(())
+}
+
+/// Installs or updates the Rust for ESP chips environment
+async fn install(
+    mut args: InstallOptions,
+    log_level: &str,
+    events_file: Option<&Path>,
+    install_mode: InstallMode,
+) -> Result<()> {
+    let log_level = if args.quiet { "error" } else { log_level };
+    initialize_logger_with_events(log_level, events_file).map_err(Error::IoError)?;
+    Config::load()?.apply(&mut args)?;
+
+    let command = match install_mode {
+        InstallMode::Install => "install",
+        InstallMode::Update => "update",
+    };
+    let output = args.output;
+    let offline = args.offline;
+    let installation = toolchain_install(args, install_mode).await;
+    // The update check goes through the proxy the installation set
+    if !offline {
+        check_for_update(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    }
+    if output == OutputFormat::Json {
+        print_json(command, &installation)?;
+    }
+    installation?;
+    Ok(())
+}
+
+/// Manages the trust store of the signature verification
+async fn keys(args: KeysOpts) -> Result<()> {
+    match args.subcommand {
+        KeysSubCommand::Add(args) => {
+            initialize_logger(&args.log_level);
+
+            let name = match args.name {
+                Some(name) => name,
+                None => args
+                    .file
+                    .file_stem()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| Error::InvalidKey(args.file.display().to_string()))?
+                    .to_string(),
+            };
+            let contents = read_to_string(&args.file).map_err(Error::IoError)?;
+            add_key(&TrustedKey::parse(&name, &contents)?)?;
+            info!("Key '{}' added to '{}'", name, get_keys_dir().display());
+        }
+        KeysSubCommand::List(args) => {
+            initialize_logger(&args.log_level);
+
+            let keys = list_keys()?;
+            if keys.is_empty() {
+                info!("No trusted keys, add one with 'espup keys add'");
+            }
+            for key in keys {
+                println!("{}\t{}", key.name, key.encoded());
+            }
+        }
+        KeysSubCommand::Remove(args) => {
+            initialize_logger(&args.log_level);
+
+            remove_key(&args.name)?;
+            info!("Key '{}' removed", args.name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the published versions that can be installed, and the installed ones
+async fn list_remote(args: ListRemoteOpts) -> Result<()> {
+    // The output of '--porcelain' is read by the shell completions, which do not show the logs
+    initialize_logger(if args.porcelain {
+        "error"
+    } else {
+        &args.log_level
+    });
+
+    let host_triple = get_host_triple(args.default_host)?;
+    let component = args.component;
+    let versions = spawn_blocking(move || remote_versions(component, &host_triple))
+        .await
+        .unwrap()?;
+    let state = State::load().unwrap_or_default();
+    for remote in versions {
+        if args.porcelain {
+            println!("{}", remote.version);
+        } else if component == RemoteComponent::XtensaRust {
+            println!(
+                "{}",
+                remote.describe(&installed_toolchains(&state, &remote.version))
+            );
+        } else {
+            println!("{}", remote.describe(&[]));
+        }
+    }
+    Ok(())
+}
+
+/// Restores a backup of a toolchain, or lists the backups
+async fn restore_backup(args: RestoreBackupOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    if args.list {
+        let backups = list_backups(None)?;
+        if backups.is_empty() {
+            info!("There are no backups");
+        }
+        for backup in backups {
+            println!("{:<32} {}", backup.id(), backup.path.display());
+        }
+        return Ok(());
+    }
+    let backups = list_backups(Some(&args.name))?;
+    let backup = match &args.backup {
+        Some(id) => backups.iter().find(|backup| &backup.id() == id),
+        None => backups.first(),
+    }
+    .ok_or_else(|| Error::MissingBackup(args.backup.unwrap_or(args.name)))?;
+    restore(backup)?;
+    Ok(())
+}
+
+/// Updates espup to the latest release of the selected channel
+async fn self_update_espup(args: SelfUpdateOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    self_update(&args.channel).await?;
+    Ok(())
+}
+
+/// Shows the next steps to start developing with the installed toolchains
+async fn start(args: StartOpts) -> Result<()> {
+    initialize_logger(&args.log_level);
+
+    let toolchain_dir = get_toolchain_dir(&args.name);
+    let mut targets = args.targets;
+    if !toolchain_dir.exists() {
+        warn!(
+            "Xtensa Rust toolchain '{}' is not installed, skipping Xtensa targets",
+            args.name
+        );
+        targets.retain(|target| !target.is_xtensa());
+    }
+    let export_file = get_installed_export_file(&args.name, args.export_file)?;
+    record_usage(&args.name);
+    println!(
+        "{}",
+        next_steps(
+            &targets,
+            args.std,
+            &args.name,
+            &args.nightly_version,
+            &export_file
+        )
+    );
+    Ok(())
+}
+
+/// Installs the environment declared by the manifest of the project
+async fn sync(args: SyncOpts, output: OutputFormat) -> Result<()> {
+    let (manifest, path) = ProjectManifest::find(&args.path)?;
+    let mut install_args = InstallOptions {
+        name: args.name,
+        output,
+        ..Default::default()
+    };
+    manifest.apply(&mut install_args)?;
+    install(install_args, &args.log_level, None, InstallMode::Install).await?;
+    info!("Environment of '{}' is in sync", path.display());
+    Ok(())
+}
+
+/// Uninstalls the Rust for ESP chips environment
+async fn uninstall(args: UninstallOpts, output: OutputFormat) -> Result<()> {
+    initialize_logger(&args.log_level);
+    check_for_update(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let mut parts = Vec::new();
+    if let Some(version) = args.remove_espidf {
+        parts.push(UninstallPart::EspIdf(version));
+    }
+    for (remove, part) in [
+        (args.remove_gcc, UninstallPart::Gcc),
+        (args.remove_llvm, UninstallPart::Llvm),
+        (args.remove_xtensa_rust, UninstallPart::XtensaRust),
+    ] {
+        if remove {
+            parts.push(part);
+        }
+    }
+
+    let uninstallation = if !parts.is_empty() && args.dry_run {
+        plan_uninstall_parts(&args.name, &parts)
+    } else if !parts.is_empty() {
+        uninstall_parts(&args.name, &parts).await
+    } else if args.dry_run {
+        plan_uninstall(&args.name, args.backup)
+    } else {
+        if args.backup {
+            create_backup(&args.name)?;
+        }
+        toolchain_uninstall(&args.name).await
+    };
+    if output == OutputFormat::Json {
+        print_json("uninstall", &uninstallation)?;
+    }
+    let uninstallation = uninstallation?;
+    if uninstallation.dry_run && output == OutputFormat::Text {
+        uninstallation.print_plan();
+    }
+    Ok(())
+}
+
+/// Result of a subcommand, as printed with `--output json`
+#[derive(Serialize)]
+struct JsonOutput<'a, T> {
+    command: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'a T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    warnings: Vec<String>,
+}
+
+/// Prints the result of a subcommand, and the warnings logged while running it, as JSON
+fn print_json<T: Serialize, E: std::fmt::Display>(
+    command: &str,
+    result: &std::result::Result<T, E>,
+) -> Result<(), Error> {
+    let output = JsonOutput {
+        command,
+        result: result.as_ref().ok(),
+        error: result.as_ref().err().map(ToString::to_string),
+        warnings: take_warnings(),
+    };
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&output).map_err(|_| Error::SerializeJson)?
+    );
+    Ok(())
+}
+
+/// Prints the installed and latest available versions of the components
+async fn versions(args: VersionsOpts, output: OutputFormat) -> Result<()> {
+    initialize_logger(if args.porcelain {
+        "error"
+    } else {
+        &args.log_level
+    });
+
+    let toolchain_dir = get_toolchain_dir(&args.name);
+    let versions = get_versions(&args.name, &toolchain_dir).await;
+    if args.porcelain {
+        for component in versions {
+            println!("{}", component.porcelain());
+        }
+    } else if args.json || output == OutputFormat::Json {
+        let json = serde_json::json!({ "toolchain": args.name, "components": versions });
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json).map_err(|_| Error::SerializeJson)?
+        );
+    } else {
+        for component in versions {
+            let installed = if component.installed.is_empty() {
+                "not installed".to_string()
+            } else {
+                component.installed.join(", ")
+            };
+            println!(
+                "{:<24} {:<32} latest: {}",
+                component.name,
+                installed,
+                component.latest.as_deref().unwrap_or("unknown")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Prints the full path of a tool installed by espup
+async fn which(args: WhichOpts) -> Result<()> {
+    initialize_logger(if args.porcelain {
+        "error"
+    } else {
+        &args.log_level
+    });
+
+    let toolchain_dir = get_toolchain_dir(&args.name);
+    println!(
+        "{}",
+        find_tool(&args.name, &toolchain_dir, &args.tool)?.display()
+    );
+    record_usage(&args.name);
+    Ok(())
+}
+
+/// Runs the subcommand of the parsed command line.
+pub async fn run(cli: Cli) -> Result<()> {
+    match cli.subcommand {
+        SubCommand::Activate(args) => activate(args).await,
+        SubCommand::Apply(args) => apply(args, cli.output).await,
+        SubCommand::Bundle(args) => bundle(args).await,
+        SubCommand::Check(args) => check(args).await,
+        SubCommand::Clean(args) => clean(args).await,
+        SubCommand::Completions(args) => completions(args).await,
+        SubCommand::Component(args) => component(args).await,
+        SubCommand::Diff(args) => diff(args).await,
+        SubCommand::Doctor(args) => doctor(args).await,
+        SubCommand::Env(args) => print_env(args).await,
+        SubCommand::Explain(args) => explain(args).await,
+        SubCommand::Freeze(args) => freeze(args).await,
+        SubCommand::Gc(args) => gc(args).await,
+        SubCommand::Generate(args) => generate(args).await,
+        SubCommand::HostInfo(args) => host_info(args).await,
+        SubCommand::Install(args) => {
+            let (log_level, events_file) = (args.log_level.clone(), args.events_file.clone());
+            let args = InstallOptions {
+                output: cli.output,
+                ..(*args).into()
+            };
+            install(
+                args,
+                &log_level,
+                events_file.as_deref(),
+                InstallMode::Install,
+            )
+            .await
+        }
+        SubCommand::Keys(args) => keys(args).await,
+        SubCommand::ListRemote(args) => list_remote(args).await,
+        SubCommand::RestoreBackup(args) => restore_backup(args).await,
+        SubCommand::SelfUpdate(args) => self_update_espup(args).await,
+        SubCommand::Start(args) => start(args).await,
+        SubCommand::Sync(args) => sync(args, cli.output).await,
+        SubCommand::Update(args) => {
+            let (log_level, events_file) = (args.log_level.clone(), args.events_file.clone());
+            let args = InstallOptions {
+                output: cli.output,
+                ..(*args).into()
+            };
+            install(
+                args,
+                &log_level,
+                events_file.as_deref(),
+                InstallMode::Update,
+            )
+            .await
+        }
+        SubCommand::Uninstall(args) => uninstall(args, cli.output).await,
+        SubCommand::Versions(args) => versions(args, cli.output).await,
+        SubCommand::Which(args) => which(args).await,
+    }
+}
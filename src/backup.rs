@@ -0,0 +1,209 @@
+//! Backups of the toolchains replaced by updates, or removed by uninstallations.
+
+use crate::{
+    env::create_export_file,
+    error::Error,
+    gc::now,
+    paths::get_espup_home,
+    shell::Shell,
+    state::{State, ToolchainState},
+    toolchain::{
+        fetch::proxy_env,
+        rust::{get_default_toolchain_dir, get_toolchain_dir, link_toolchain},
+    },
+};
+use log::{debug, info, warn};
+use std::{
+    fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, rename, write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Name of the directory, under the espup home, storing the backups.
+const BACKUPS_DIR: &str = "backups";
+/// Name of the backed up toolchain directory, in a backup.
+const BACKUP_TOOLCHAIN: &str = "toolchain";
+/// Name of the file storing the state of the backed up toolchain, in a backup.
+const BACKUP_STATE: &str = "state.json";
+
+/// Backup of a toolchain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    /// Xtensa Rust toolchain name.
+    pub name: String,
+    /// Time of the backup, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Directory of the backup.
+    pub path: PathBuf,
+}
+
+impl Backup {
+    /// Returns the backed up toolchain directory.
+    pub fn toolchain_dir(&self) -> PathBuf {
+        self.path.join(BACKUP_TOOLCHAIN)
+    }
+
+    /// Returns the identifier of the backup, '<name>-<timestamp>'.
+    pub fn id(&self) -> String {
+        format!("{}-{}", self.name, self.timestamp)
+    }
+
+    /// Parses the backup from its directory, whose name is its identifier.
+    fn from_path(path: &Path) -> Option<Self> {
+        let (name, timestamp) = path.file_name()?.to_str()?.rsplit_once('-')?;
+        Some(Self {
+            name: name.to_string(),
+            timestamp: timestamp.parse().ok()?,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+/// Gets the directory storing the backups.
+pub fn get_backups_dir() -> PathBuf {
+    get_espup_home().join(BACKUPS_DIR)
+}
+
+/// Moves a directory, failing instead of copying it when the destination is on another file system.
+fn move_dir(from: &Path, to: &Path) -> Result<(), Error> {
+    debug!("Moving '{}' to '{}'", from.display(), to.display());
+    rename(from, to)
+        .map_err(|_| Error::MoveDirectory(from.display().to_string(), to.display().to_string()))
+}
+
+/// Returns the directory a new backup of the toolchain is stored in.
+pub fn get_backup_path(name: &str) -> PathBuf {
+    get_backups_dir().join(format!("{name}-{}", now()))
+}
+
+/// Backs the toolchain up, moving its directory and storing its state into a new backup.
+///
+/// Returns `None` when the toolchain is not installed.
+pub fn create_backup(name: &str) -> Result<Option<Backup>, Error> {
+    backup_toolchain(name, &get_toolchain_dir(name))
+}
+
+/// Backs the toolchain directory up, like [`create_backup`], e.g. a toolchain of the same name
+/// that was not installed by espup.
+pub fn backup_toolchain(name: &str, toolchain_dir: &Path) -> Result<Option<Backup>, Error> {
+    if !toolchain_dir.exists() {
+        return Ok(None);
+    }
+    let path = get_backup_path(name);
+    create_dir_all(&path).map_err(|_| Error::CreateDirectory(path.display().to_string()))?;
+    if let Some(toolchain_state) = State::load()?.toolchains.get(name) {
+        let contents =
+            serde_json::to_string_pretty(toolchain_state).map_err(|_| Error::SerializeJson)?;
+        write(path.join(BACKUP_STATE), contents)?;
+    }
+    move_dir(toolchain_dir, &path.join(BACKUP_TOOLCHAIN))?;
+    info!(
+        "Toolchain '{}' has been backed up to '{}'",
+        toolchain_dir.display(),
+        path.display()
+    );
+    Ok(Backup::from_path(&path))
+}
+
+/// Returns the backups, most recent first, of every toolchain or only of the named one.
+pub fn list_backups(name: Option<&str>) -> Result<Vec<Backup>, Error> {
+    let backups_dir = get_backups_dir();
+    if !backups_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups: Vec<Backup> = read_dir(&backups_dir)?
+        .filter_map(|entry| Backup::from_path(&entry.ok()?.path()))
+        .filter(|backup| name.map_or(true, |name| backup.name == name))
+        .collect();
+    backups.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(a.name.cmp(&b.name)));
+    Ok(backups)
+}
+
+/// Restores the backup, backing up the currently installed toolchain of the same name first.
+pub fn restore_backup(backup: &Backup) -> Result<(), Error> {
+    if get_toolchain_dir(&backup.name).exists() {
+        create_backup(&backup.name)?;
+    }
+    let state_file = backup.path.join(BACKUP_STATE);
+    let toolchain_state: Option<ToolchainState> = if state_file.exists() {
+        Some(
+            serde_json::from_str(&read_to_string(&state_file)?)
+                .map_err(|_| Error::SerializeJson)?,
+        )
+    } else {
+        None
+    };
+    // The toolchain goes back where it was installed
+    let toolchain_dir = toolchain_state
+        .as_ref()
+        .and_then(|toolchain_state| toolchain_state.toolchain_destination.clone())
+        .unwrap_or_else(|| get_default_toolchain_dir(&backup.name));
+    move_dir(&backup.path.join(BACKUP_TOOLCHAIN), &toolchain_dir)?;
+
+    let mut state = State::load()?;
+    if let Some(toolchain_state) = toolchain_state {
+        if toolchain_state.toolchain_destination.is_some() {
+            link_toolchain(&backup.name, &toolchain_dir)?;
+        }
+        restore_rustup_targets(&toolchain_state);
+        if let Some(export_file) = &toolchain_state.export_file {
+            create_export_file(
+                export_file,
+                &toolchain_state.exports,
+                Shell::from_installation(&toolchain_state),
+            )?;
+        }
+        state
+            .toolchains
+            .insert(backup.name.clone(), toolchain_state);
+    } else {
+        state.toolchains.remove(&backup.name);
+    }
+    state.save()?;
+
+    remove_dir_all(&backup.path)
+        .map_err(|_| Error::RemoveDirectory(backup.path.display().to_string()))?;
+    info!(
+        "Toolchain '{}' has been restored from the backup '{}'",
+        toolchain_dir.display(),
+        backup.id()
+    );
+    Ok(())
+}
+
+/// Adds the rustup targets the toolchain was installed with, which an uninstallation removes.
+fn restore_rustup_targets(toolchain_state: &ToolchainState) {
+    for (toolchain, targets) in toolchain_state.rustup_targets_by_toolchain() {
+        let added = Command::new("rustup")
+            .args(["target", "add", "--toolchain", toolchain])
+            .envs(proxy_env())
+            .args(&targets)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !added {
+            warn!(
+                "Failed to add the {} targets to the '{toolchain}' toolchain, add them with 'rustup target add'",
+                targets.join(", ")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backup::Backup;
+    use std::path::Path;
+
+    #[test]
+    fn test_backup_from_path() {
+        let backup =
+            Backup::from_path(Path::new("/home/user/.espup/backups/esp-1.74-1700000000")).unwrap();
+        assert_eq!(backup.name, "esp-1.74");
+        assert_eq!(backup.timestamp, 1700000000);
+        assert_eq!(backup.id(), "esp-1.74-1700000000");
+        assert!(Backup::from_path(Path::new("/home/user/.espup/backups/esp")).is_none());
+        assert!(Backup::from_path(Path::new("/home/user/.espup/backups/esp-latest")).is_none());
+    }
+}
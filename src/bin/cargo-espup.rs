@@ -0,0 +1,23 @@
+//! Cargo subcommand entry point, allowing to run espup as `cargo espup`.
+
+use clap::{CommandFactory, FromArgMatches, Parser};
+use espup::app::{run, Cli};
+use miette::Result;
+
+/// Cargo invokes `cargo-espup` with `espup` as the first argument.
+#[derive(Parser)]
+#[command(name = "cargo", bin_name = "cargo")]
+enum CargoCli {
+    Espup(Cli),
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let matches = CargoCli::command().get_matches();
+    let cli = match matches.subcommand() {
+        Some(("espup", matches)) => Cli::from_matches(matches),
+        _ => CargoCli::from_arg_matches(&matches).map(|CargoCli::Espup(cli)| cli),
+    }
+    .unwrap_or_else(|error| error.exit());
+    run(cli).await
+}
@@ -0,0 +1,172 @@
+//! Removal of build outputs and downloads to reclaim disk space.
+
+use crate::{error::Error, paths::get_idf_tools_path};
+use log::debug;
+use std::{
+    fs::{read_dir, symlink_metadata},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+/// Directory where embuild installs the ESP-IDF and its tools for a project.
+pub const EMBUILD_DIR: &str = ".embuild";
+
+/// Levels of subdirectories of the project searched for build outputs (e.g. workspace members).
+const MAX_SEARCH_DEPTH: usize = 3;
+
+/// Directories never searched for build outputs.
+const SKIPPED_DIRS: [&str; 3] = [".git", "node_modules", "target"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    /// Path of the directory to remove.
+    pub path: PathBuf,
+    /// Size of the directory, in bytes.
+    pub size: u64,
+}
+
+impl Artifact {
+    /// Creates an artifact, measuring the size of the directory.
+    pub fn new(path: PathBuf) -> Self {
+        let size = dir_size(&path);
+        Self { path, size }
+    }
+}
+
+/// Returns the embuild directories of the project and its subdirectories.
+pub fn find_build_artifacts(project_dir: &Path) -> Vec<PathBuf> {
+    fn search(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+        let Ok(entries) = read_dir(dir) else {
+            return;
+        };
+        let mut dirs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+            .map(|entry| entry.path())
+            .collect();
+        dirs.sort();
+        for dir in dirs {
+            let name = dir.file_name().unwrap_or_default().to_string_lossy();
+            if name == EMBUILD_DIR {
+                debug!("Found build outputs in '{}'", dir.display());
+                found.push(dir);
+            } else if depth < MAX_SEARCH_DEPTH && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                search(&dir, depth + 1, found);
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    search(project_dir, 0, &mut found);
+    found
+}
+
+/// Returns the directory where the ESP-IDF tools installer keeps its downloads, if it exists.
+pub fn get_idf_tools_dist() -> Option<PathBuf> {
+    let dist = get_idf_tools_path().join("dist");
+    dist.is_dir().then_some(dist)
+}
+
+/// Returns the size of a file, or the total size of the files of a directory, in bytes.
+///
+/// Symlinks are not followed, so the size of their targets is not counted.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| dir_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+/// Formats a size in bytes with a human readable unit.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Asks the user to confirm an action, defaulting to no.
+pub fn confirm(prompt: &str) -> io::Result<bool> {
+    // The prompt is not part of the output, which may be redirected
+    eprint!("{prompt} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Removes the artifact directory.
+pub fn remove_artifact(artifact: &Artifact) -> Result<(), Error> {
+    debug!("Removing '{}'", artifact.path.display());
+    std::fs::remove_dir_all(&artifact.path)
+        .map_err(|_| Error::RemoveDirectory(artifact.path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clean::{dir_size, find_build_artifacts, format_size, EMBUILD_DIR};
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_build_artifacts() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in [
+            EMBUILD_DIR,
+            "members/app/.embuild/espressif",
+            "target/.embuild",
+            "a/b/c/d/.embuild",
+        ] {
+            create_dir_all(root.join(dir)).unwrap();
+        }
+        assert_eq!(
+            find_build_artifacts(root),
+            vec![
+                root.join(EMBUILD_DIR),
+                root.join("members").join("app").join(EMBUILD_DIR)
+            ]
+        );
+        assert!(find_build_artifacts(&root.join("missing")).is_empty());
+    }
+
+    #[test]
+    fn test_dir_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join(EMBUILD_DIR);
+        create_dir_all(dir.join("espressif").join("dist")).unwrap();
+        write(
+            dir.join("espressif").join("dist").join("tools.zip"),
+            [0; 300],
+        )
+        .unwrap();
+        write(dir.join("env"), [0; 12]).unwrap();
+        assert_eq!(dir_size(&dir), 312);
+        assert_eq!(dir_size(&dir.join("env")), 12);
+        assert_eq!(dir_size(&temp_dir.path().join("missing")), 0);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(12), "12 B");
+        assert_eq!(format_size(1_500), "1.5 KB");
+        assert_eq!(format_size(2_340_000_000), "2.3 GB");
+    }
+}
@@ -0,0 +1,128 @@
+//! Shell completions, completing the versions with the ones published.
+
+use crate::{app::Cli, versions::RemoteComponent};
+use clap::CommandFactory;
+use clap_complete::Shell;
+
+/// Options completed with the published versions of the component.
+const VERSION_OPTIONS: [(&str, &str, RemoteComponent); 2] = [
+    (
+        "--toolchain-version",
+        "TOOLCHAIN_VERSION",
+        RemoteComponent::XtensaRust,
+    ),
+    (
+        "--esp-idf-version",
+        "ESP_IDF_VERSION",
+        RemoteComponent::EspIdf,
+    ),
+];
+
+/// Returns the command listing the published versions of the component, one per line.
+fn list_remote(component: RemoteComponent) -> String {
+    format!("espup list-remote --porcelain {component} 2>/dev/null")
+}
+
+/// Generates the completion script of the shell.
+///
+/// The bash, zsh and fish scripts complete the versions by calling 'espup list-remote', the other
+/// shells only complete the options.
+pub fn generate_completions(shell: Shell) -> String {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "espup", &mut script);
+    let script = String::from_utf8_lossy(&script).to_string();
+    match shell {
+        Shell::Bash => complete_versions_bash(&script),
+        Shell::Fish => complete_versions_fish(&script),
+        Shell::Zsh => complete_versions_zsh(&script),
+        _ => script,
+    }
+}
+
+/// Replaces the file completion of the version options with the published versions.
+fn complete_versions_bash(script: &str) -> String {
+    let mut lines = Vec::new();
+    let mut component = None;
+    for line in script.lines() {
+        let trimmed = line.trim();
+        if let Some((_, _, remote)) = VERSION_OPTIONS
+            .iter()
+            .find(|(option, _, _)| trimmed == format!("{option})"))
+        {
+            component = Some(*remote);
+        } else if let Some(remote) = component.take() {
+            lines.push(line.replace(
+                r#"compgen -f "${cur}""#,
+                &format!(r#"compgen -W "$({})" -- "${{cur}}""#, list_remote(remote)),
+            ));
+            continue;
+        }
+        lines.push(line.to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Adds the published versions to the completions of the version options.
+fn complete_versions_fish(script: &str) -> String {
+    script
+        .lines()
+        .map(|line| {
+            match VERSION_OPTIONS.iter().find(|(option, _, _)| {
+                line.contains(&format!(" -l {} ", option.trim_start_matches("--")))
+            }) {
+                Some((_, _, remote)) => {
+                    format!("{line} -f -a \"({})\"", list_remote(*remote))
+                }
+                None => line.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+        + "\n"
+}
+
+/// Completes the values of the version options with the published versions.
+fn complete_versions_zsh(script: &str) -> String {
+    let mut script = script.replacen(
+        "#compdef espup\n",
+        "#compdef espup\n\n_espup_remote_versions() {\n    local -a versions\n    versions=(${(f)\"$(espup list-remote --porcelain $1 2>/dev/null)\"})\n    _describe -t versions 'version' versions\n}\n",
+        1,
+    );
+    for (_, value_name, remote) in VERSION_OPTIONS {
+        script = script.replace(
+            &format!(":{value_name}: '"),
+            &format!(":{value_name}:_espup_remote_versions {remote}'"),
+        );
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::completions::generate_completions;
+    use clap_complete::Shell;
+
+    #[test]
+    fn test_generate_completions() {
+        let bash = generate_completions(Shell::Bash);
+        assert!(bash.contains(
+            r#"COMPREPLY=($(compgen -W "$(espup list-remote --porcelain xtensa-rust 2>/dev/null)" -- "${cur}"))"#
+        ));
+        assert!(bash.contains("espup list-remote --porcelain esp-idf"));
+
+        let fish = generate_completions(Shell::Fish);
+        assert!(fish
+            .lines()
+            .any(|line| line.contains("-l toolchain-version")
+                && line.ends_with(
+                    "-f -a \"(espup list-remote --porcelain xtensa-rust 2>/dev/null)\""
+                )));
+
+        let zsh = generate_completions(Shell::Zsh);
+        assert!(zsh.starts_with("#compdef espup\n\n_espup_remote_versions() {"));
+        assert!(zsh.contains(":TOOLCHAIN_VERSION:_espup_remote_versions xtensa-rust'"));
+        assert!(zsh.contains(":ESP_IDF_VERSION:_espup_remote_versions esp-idf'"));
+
+        assert!(!generate_completions(Shell::PowerShell).contains("list-remote --porcelain"));
+    }
+}
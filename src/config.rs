@@ -0,0 +1,233 @@
+//! User configuration, default values for the installation options.
+
+use crate::{
+    env::parse_variable,
+    error::Error,
+    paths::get_home_dir,
+    targets::{parse_targets, Target},
+    toolchain::{options::InstallOptions, DEFAULT_NIGHTLY_VERSION},
+};
+use directories::BaseDirs;
+use log::debug;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    env,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+use strum::IntoEnumIterator;
+
+/// Name of the configuration file.
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Variables added to the export file.
+    pub export_append: Option<BTreeMap<String, String>>,
+    /// Relative or full path for the export file.
+    pub export_file: Option<PathBuf>,
+    /// Extra crates to install.
+    pub extra_crates: Option<Vec<String>>,
+    /// Nightly Rust toolchain version.
+    pub nightly_version: Option<String>,
+    /// Comma or space separated list of targets.
+    pub targets: Option<String>,
+    /// Xtensa Rust toolchain version.
+    pub toolchain_version: Option<String>,
+}
+
+impl Config {
+    /// Parses a configuration from its TOML representation.
+    pub fn parse(contents: &str, path: &Path) -> Result<Self, Error> {
+        toml::from_str(contents)
+            .map_err(|e| Error::InvalidConfig(path.display().to_string(), e.message().to_string()))
+    }
+
+    /// Loads the configuration file, returning an empty configuration if it does not exist.
+    pub fn load() -> Result<Self, Error> {
+        let Some(path) = get_config_file() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        debug!("Loading configuration from '{}'", path.display());
+        Self::parse(&read_to_string(&path)?, &path)
+    }
+
+    /// Sets the installation options left at their default value to the configured ones.
+    ///
+    /// Options passed in the command line take precedence over the configuration, even when they
+    /// are given their default value.
+    pub fn apply(self, args: &mut InstallOptions) -> Result<(), Error> {
+        let is_default = |args: &InstallOptions, id: &str| !args.is_explicit(id);
+        // The configured variables are kept, unless the command line sets them too
+        if let Some(export_append) = self.export_append {
+            let mut variables = Vec::new();
+            for (name, value) in export_append {
+                let variable = parse_variable(&format!("{name}={value}"))?;
+                if !args.export_append.iter().any(|(arg, _)| *arg == name) {
+                    variables.push(variable);
+                }
+            }
+            args.export_append.splice(0..0, variables);
+        }
+        if let Some(export_file) = self
+            .export_file
+            .filter(|_| args.export_file.is_none() && is_default(args, "export_file"))
+        {
+            args.export_file = Some(export_file);
+        }
+        if let Some(extra_crates) = self
+            .extra_crates
+            .filter(|_| args.extra_crates.is_empty() && is_default(args, "extra_crates"))
+        {
+            args.extra_crates = extra_crates;
+        }
+        if let Some(nightly_version) = self.nightly_version.filter(|_| {
+            args.nightly_version == DEFAULT_NIGHTLY_VERSION && is_default(args, "nightly_version")
+        }) {
+            args.nightly_version = nightly_version;
+        }
+        let all_targets: HashSet<Target> = Target::iter().collect();
+        if let Some(targets) = self
+            .targets
+            .filter(|_| args.targets == all_targets && is_default(args, "targets"))
+        {
+            args.targets = parse_targets(&targets)?;
+        }
+        if let Some(toolchain_version) = self
+            .toolchain_version
+            .filter(|_| args.toolchain_version.is_none() && is_default(args, "toolchain_version"))
+        {
+            args.toolchain_version = Some(toolchain_version);
+        }
+        Ok(())
+    }
+}
+
+/// Gets the path of the configuration file.
+///
+/// The file is '~/.config/espup/config.toml' (or under `$XDG_CONFIG_HOME`) on Unix, and
+/// '%APPDATA%\espup\config.toml' on Windows.
+pub fn get_config_file() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        BaseDirs::new()?.config_dir().to_path_buf()
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .unwrap_or_else(|| get_home_dir().join(".config"))
+    };
+    Some(config_dir.join("espup").join(CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::Config,
+        targets::{parse_targets, Target},
+        toolchain::options::InstallOptions,
+    };
+    use std::path::{Path, PathBuf};
+
+    fn config() -> Config {
+        Config::parse(
+            r#"
+targets = "esp32c3"
+toolchain-version = "1.73.0.1"
+nightly-version = "nightly-2023-11-14"
+export-file = "/home/user/export-esp.sh"
+extra-crates = ["espflash"]
+
+[export-append]
+WIFI_SSID = "espressif"
+ESP_LOG = "info"
+"#,
+            Path::new("config.toml"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let config = config();
+
+        // The configuration replaces the default values
+        let mut args = InstallOptions::default();
+        config.clone().apply(&mut args).unwrap();
+        assert_eq!(args.targets, [Target::ESP32C3].into_iter().collect());
+        assert_eq!(args.toolchain_version.as_deref(), Some("1.73.0.1"));
+        assert_eq!(args.nightly_version, "nightly-2023-11-14");
+        assert_eq!(
+            args.export_file,
+            Some(PathBuf::from("/home/user/export-esp.sh"))
+        );
+        assert_eq!(args.extra_crates, vec!["espflash"]);
+        assert_eq!(
+            args.export_append,
+            vec![
+                ("ESP_LOG".to_string(), "info".to_string()),
+                ("WIFI_SSID".to_string(), "espressif".to_string())
+            ]
+        );
+
+        // The command line options take precedence
+        let mut args = InstallOptions {
+            targets: parse_targets("esp32s3").unwrap(),
+            toolchain_version: Some("1.74.0.0".to_string()),
+            extra_crates: vec!["ldproxy".to_string()],
+            export_append: vec![("ESP_LOG".to_string(), "debug".to_string())],
+            ..Default::default()
+        };
+        config.apply(&mut args).unwrap();
+        assert_eq!(args.targets, parse_targets("esp32s3").unwrap());
+        assert_eq!(args.toolchain_version.as_deref(), Some("1.74.0.0"));
+        assert_eq!(args.nightly_version, "nightly-2023-11-14");
+        assert_eq!(args.extra_crates, vec!["ldproxy"]);
+        assert_eq!(
+            args.export_append,
+            vec![
+                ("WIFI_SSID".to_string(), "espressif".to_string()),
+                ("ESP_LOG".to_string(), "debug".to_string())
+            ]
+        );
+
+        assert!(Config::parse("unknown = true", Path::new("config.toml")).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_apply_config_explicit_args() {
+        use crate::cli::InstallOpts;
+        use clap::{CommandFactory, FromArgMatches};
+
+        // The command line options take precedence even when they are set to their default value
+        let matches = InstallOpts::command().get_matches_from([
+            "install",
+            "--targets",
+            "all",
+            "--nightly-version",
+            "nightly",
+        ]);
+        let mut opts = InstallOpts::from_arg_matches(&matches).unwrap();
+        opts.record_explicit_args(&matches);
+        let mut args = InstallOptions::from(opts);
+        assert!(args.is_explicit("targets"));
+        assert!(!args.is_explicit("name"));
+        config().apply(&mut args).unwrap();
+        assert_eq!(args.targets, parse_targets("all").unwrap());
+        assert_eq!(args.nightly_version, "nightly");
+        assert_eq!(args.toolchain_version.as_deref(), Some("1.73.0.1"));
+
+        // And so do the positional targets
+        let matches = InstallOpts::command().get_matches_from(["install", "esp32s3", "esp32c3"]);
+        let mut opts = InstallOpts::from_arg_matches(&matches).unwrap();
+        opts.record_explicit_args(&matches);
+        let mut args = InstallOptions::from(opts);
+        config().apply(&mut args).unwrap();
+        assert_eq!(args.targets, parse_targets("esp32s3,esp32c3").unwrap());
+    }
+}
@@ -0,0 +1,207 @@
+//! Comparison of the environments set up by espup.
+
+use crate::error::Error;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+};
+
+/// Variables listing paths, whose entries are compared one by one.
+const PATH_VARIABLES: [&str; 1] = ["PATH"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// Entry only present in the second environment.
+    Added { key: String, value: String },
+    /// Entry only present in the first environment.
+    Removed { key: String, value: String },
+    /// Entry present in both environments with different values.
+    Changed {
+        key: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Added { key, value } => write!(f, "+ {key}: {value}"),
+            Difference::Removed { key, value } => write!(f, "- {key}: {value}"),
+            Difference::Changed { key, old, new } => write!(f, "~ {key}: {old} -> {new}"),
+        }
+    }
+}
+
+/// Extracts the assignment of a `PATH` export guarded against repeated sourcing.
+fn unguard(line: &str) -> &str {
+    if line.starts_with("case ") {
+        if let Some(start) = line.find("export ") {
+            return line[start..].trim_end_matches(" ;; esac");
+        }
+    } else if line.starts_with("if ") {
+        if let Some(start) = line.find("{ $Env:") {
+            return line[start + 2..].trim_end_matches(" }");
+        }
+    }
+    line
+}
+
+/// Parses an export file line, returning the variable and its values.
+fn parse_export(line: &str) -> Option<(String, Vec<String>)> {
+    let line = unguard(line);
+    let (key, value, separator) = if let Some(export) = line.strip_prefix("export ") {
+        let (key, value) = export.split_once('=')?;
+        (key, value, ':')
+    } else if let Some(export) = line.strip_prefix("$Env:") {
+        let (key, value) = export.split_once('=')?;
+        let value = value.trim().trim_end_matches("+ $Env:PATH").trim();
+        (key, value, ';')
+    } else {
+        return None;
+    };
+    let key = key.trim().to_string();
+    let value = value.trim().trim_matches('"');
+    let values = if PATH_VARIABLES.contains(&key.as_str()) {
+        value
+            .split(separator)
+            .filter(|entry| !entry.is_empty() && !entry.starts_with('$'))
+            .map(str::to_string)
+            .collect()
+    } else {
+        vec![value.to_string()]
+    };
+    Some((key, values))
+}
+
+/// Parses an export file, or a downloads manifest, into its entries.
+pub fn parse_environment(contents: &str) -> Result<BTreeMap<String, Vec<String>>, Error> {
+    let mut entries: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    if contents.trim_start().starts_with('{') {
+        let manifest: serde_json::Value =
+            serde_json::from_str(contents).map_err(|_| Error::SerializeJson)?;
+        for artifact in manifest["artifacts"]
+            .as_array()
+            .ok_or(Error::SerializeJson)?
+        {
+            let file = artifact["file"].as_str().ok_or(Error::SerializeJson)?;
+            let url = artifact["url"].as_str().ok_or(Error::SerializeJson)?;
+            entries.insert(file.to_string(), vec![url.to_string()]);
+        }
+    } else {
+        for (key, values) in contents.lines().filter_map(parse_export) {
+            entries.entry(key).or_default().extend(values);
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the differences between two environments.
+pub fn diff_environments(
+    first: &BTreeMap<String, Vec<String>>,
+    second: &BTreeMap<String, Vec<String>>,
+) -> Vec<Difference> {
+    let keys: BTreeSet<&String> = first.keys().chain(second.keys()).collect();
+    let mut differences = Vec::new();
+    for key in keys {
+        let empty = Vec::new();
+        let old = first.get(key).unwrap_or(&empty);
+        let new = second.get(key).unwrap_or(&empty);
+        match (old.as_slice(), new.as_slice()) {
+            ([old], [new]) if !PATH_VARIABLES.contains(&key.as_str()) => {
+                if old != new {
+                    differences.push(Difference::Changed {
+                        key: key.clone(),
+                        old: old.clone(),
+                        new: new.clone(),
+                    });
+                }
+            }
+            _ => {
+                for value in old.iter().filter(|value| !new.contains(value)) {
+                    differences.push(Difference::Removed {
+                        key: key.clone(),
+                        value: value.clone(),
+                    });
+                }
+                for value in new.iter().filter(|value| !old.contains(value)) {
+                    differences.push(Difference::Added {
+                        key: key.clone(),
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::{diff_environments, parse_environment, Difference};
+
+    #[test]
+    fn test_diff_export_files() {
+        let first = parse_environment(
+            "export PATH=\"/home/user/.rustup/toolchains/esp/xtensa-esp-elf/esp-12.2.0_20230208/xtensa-esp-elf/bin:$PATH\"
+export LIBCLANG_PATH=\"/home/user/.rustup/toolchains/esp/xtensa-esp32-elf-clang/esp-15.0.0-20221201/esp-clang/lib\"
+export CLANG_PATH=\"/usr/bin/clang\"",
+        )
+        .unwrap();
+        let second = parse_environment(
+            "export PATH=\"/home/user/.rustup/toolchains/esp/xtensa-esp-elf/esp-13.2.0_20230928/xtensa-esp-elf/bin:$PATH\"
+export LIBCLANG_PATH=\"/home/user/.rustup/toolchains/esp/xtensa-esp32-elf-clang/esp-16.0.0-20230516/esp-clang/lib\"
+export CLANG_PATH=\"/usr/bin/clang\"",
+        )
+        .unwrap();
+        assert_eq!(
+            diff_environments(&first, &second),
+            vec![
+                Difference::Changed {
+                    key: "LIBCLANG_PATH".to_string(),
+                    old: "/home/user/.rustup/toolchains/esp/xtensa-esp32-elf-clang/esp-15.0.0-20221201/esp-clang/lib".to_string(),
+                    new: "/home/user/.rustup/toolchains/esp/xtensa-esp32-elf-clang/esp-16.0.0-20230516/esp-clang/lib".to_string(),
+                },
+                Difference::Removed {
+                    key: "PATH".to_string(),
+                    value: "/home/user/.rustup/toolchains/esp/xtensa-esp-elf/esp-12.2.0_20230208/xtensa-esp-elf/bin".to_string(),
+                },
+                Difference::Added {
+                    key: "PATH".to_string(),
+                    value: "/home/user/.rustup/toolchains/esp/xtensa-esp-elf/esp-13.2.0_20230928/xtensa-esp-elf/bin".to_string(),
+                },
+            ]
+        );
+        assert!(diff_environments(&first, &first).is_empty());
+    }
+
+    #[test]
+    fn test_parse_environment() {
+        let powershell = parse_environment(
+            "$Env:LIBCLANG_PATH = \"C:\\esp\\libclang.dll\"\n$Env:PATH = \"C:\\esp\\bin;\" + $Env:PATH",
+        )
+        .unwrap();
+        assert_eq!(powershell["LIBCLANG_PATH"], vec!["C:\\esp\\libclang.dll"]);
+        assert_eq!(powershell["PATH"], vec!["C:\\esp\\bin"]);
+
+        let guarded = parse_environment(
+            "case \":$PATH:\" in *\":/esp/a/bin:/esp/b/bin:\"*) ;; *) export PATH=\"/esp/a/bin:/esp/b/bin:$PATH\" ;; esac\n\
+            if (-not $Env:PATH.Contains(\"C:\\esp\\bin;\")) { $Env:PATH = \"C:\\esp\\bin;\" + $Env:PATH }",
+        )
+        .unwrap();
+        assert_eq!(
+            guarded["PATH"],
+            vec!["/esp/a/bin", "/esp/b/bin", "C:\\esp\\bin"]
+        );
+
+        let manifest = parse_environment(
+            r#"{ "artifacts": [{ "url": "https://example.com/v1/rust.tar.xz", "file": "rust.tar.xz", "size": 1 }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            manifest["rust.tar.xz"],
+            vec!["https://example.com/v1/rust.tar.xz"]
+        );
+        assert!(parse_environment("{ \"artifacts\": 1 }").is_err());
+    }
+}
@@ -0,0 +1,621 @@
+//! Diagnostics of the installed environment.
+
+use crate::{
+    diff::{diff_environments, parse_environment},
+    env::export_file_contents,
+    paths::{
+        get_cargo_home, get_default_rustup_home, get_espup_home, get_rustup_home, path_contains,
+        synced_folder_warning,
+    },
+    permissions::find_world_writable,
+    shell::Shell,
+    state::State,
+    toolchain::{
+        find_tool,
+        gcc::{RISCV_GCC, XTENSA_GCC},
+        llvm::get_llvm_root,
+        rust::{get_rustup_override, is_corrupted_toolchain},
+    },
+};
+use libloading::Library;
+use std::{
+    env,
+    ffi::OsString,
+    fs::{read, read_dir, read_to_string},
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use strum::Display;
+
+/// Program header type of the interpreter path.
+const PT_INTERP: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// Name of the check.
+    pub name: String,
+    /// Result of the check.
+    pub status: Status,
+    /// Details about the result.
+    pub message: String,
+}
+
+impl Check {
+    fn new(name: &str, status: Status, message: String) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfInfo {
+    /// Whether the binary is 64-bit.
+    pub is_64_bit: bool,
+    /// Architecture of the binary, using the `std::env::consts::ARCH` names.
+    pub arch: String,
+    /// Program interpreter (dynamic loader) requested by the binary.
+    pub interpreter: Option<String>,
+}
+
+/// Parses the ELF header of a binary, returning `None` if it's not an ELF file.
+pub fn parse_elf(bytes: &[u8]) -> Option<ElfInfo> {
+    if bytes.len() < 64 || &bytes[0..4] != b"\x7fELF" {
+        return None;
+    }
+    let is_64_bit = bytes[4] == 2;
+    let little_endian = bytes[5] == 1;
+    let read_u16 = |offset: usize| -> Option<u64> {
+        let b: [u8; 2] = bytes.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        } as u64)
+    };
+    let read_u32 = |offset: usize| -> Option<u64> {
+        let b: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        } as u64)
+    };
+    let read_u64 = |offset: usize| -> Option<u64> {
+        let b: [u8; 8] = bytes.get(offset..offset + 8)?.try_into().ok()?;
+        Some(if little_endian {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        })
+    };
+    let read_word = |offset_32: usize, offset_64: usize| {
+        if is_64_bit {
+            read_u64(offset_64)
+        } else {
+            read_u32(offset_32)
+        }
+    };
+
+    let arch = match read_u16(18)? {
+        3 => "x86",
+        40 => "arm",
+        62 => "x86_64",
+        183 => "aarch64",
+        _ => "unknown",
+    }
+    .to_string();
+
+    let phoff = read_word(28, 32)? as usize;
+    let phentsize = read_u16(if is_64_bit { 54 } else { 42 })? as usize;
+    let phnum = read_u16(if is_64_bit { 56 } else { 44 })? as usize;
+    let mut interpreter = None;
+    for i in 0..phnum {
+        let header = phoff + i * phentsize;
+        if read_u32(header)? as u32 == PT_INTERP {
+            let offset = read_word(header + 4, header + 8)? as usize;
+            let size = read_word(header + 16, header + 32)? as usize;
+            let path = bytes.get(offset..offset + size)?;
+            interpreter = Some(
+                String::from_utf8_lossy(path)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            );
+            break;
+        }
+    }
+
+    Some(ElfInfo {
+        is_64_bit,
+        arch,
+        interpreter,
+    })
+}
+
+/// Explains why a binary that exists can not be executed.
+fn diagnose_not_executable(tool_path: &Path) -> String {
+    let Some(elf) = read(tool_path).ok().and_then(|bytes| parse_elf(&bytes)) else {
+        return format!("'{}' exists but can not be executed", tool_path.display());
+    };
+    let bits = if elf.is_64_bit { 64 } else { 32 };
+    // 32-bit binaries can run on their 64-bit counterparts
+    let is_compatible = matches!(
+        (elf.arch.as_str(), env::consts::ARCH),
+        ("x86", "x86_64") | ("arm", "aarch64")
+    );
+    if elf.arch != env::consts::ARCH && !is_compatible {
+        return format!(
+            "'{}' is a {bits}-bit {} binary, which can not run on this {} host",
+            tool_path.display(),
+            elf.arch,
+            env::consts::ARCH
+        );
+    }
+    match elf.interpreter {
+        Some(interpreter) if !Path::new(&interpreter).exists() && !elf.is_64_bit => format!(
+            "'{}' is a 32-bit binary whose loader '{interpreter}' is missing. Install the 32-bit compatibility libraries of your distribution (e.g. 'libc6-i386' on Debian/Ubuntu)",
+            tool_path.display(),
+        ),
+        Some(interpreter) if !Path::new(&interpreter).exists() => format!(
+            "'{}' requires the loader '{interpreter}', which is missing. The host C library is not compatible with the binary (e.g. a musl based distribution)",
+            tool_path.display(),
+        ),
+        _ => format!(
+            "'{}' exists but can not be executed, some of its libraries may be missing",
+            tool_path.display()
+        ),
+    }
+}
+
+/// Checks that the tools installed by espup can be executed.
+fn check_executables(toolchain_name: &str, toolchain_dir: &Path) -> Vec<Check> {
+    let tools = [
+        "rustc".to_string(),
+        "clang".to_string(),
+        format!("{XTENSA_GCC}-gcc"),
+        format!("{RISCV_GCC}-gcc"),
+    ];
+    let mut checks = Vec::new();
+    for tool in tools {
+        let Ok(tool_path) = find_tool(toolchain_name, toolchain_dir, &tool) else {
+            continue;
+        };
+        let name = format!("Executable '{tool}'");
+        let check = match Command::new(&tool_path)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+        {
+            Ok(_) => Check::new(&name, Status::Ok, tool_path.display().to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Check::new(&name, Status::Error, diagnose_not_executable(&tool_path))
+            }
+            Err(e) => Check::new(&name, Status::Error, e.to_string()),
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+/// Returns the libclang libraries of the installed LLVM versions.
+fn find_libclang(toolchain_name: &str, toolchain_dir: &Path) -> Vec<PathBuf> {
+    // bindgen loads libclang from the 'lib' directory on Unix and from the 'bin' one on Windows
+    let lib_dir = if cfg!(windows) { "bin" } else { "lib" };
+    let mut libraries = Vec::new();
+    let Ok(versions) = read_dir(get_llvm_root(toolchain_name, toolchain_dir)) else {
+        return libraries;
+    };
+    for version in versions.filter_map(|entry| entry.ok()) {
+        let Ok(entries) = read_dir(version.path().join("esp-clang").join(lib_dir)) else {
+            continue;
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name.starts_with("libclang")
+                && (file_name.ends_with(env::consts::DLL_SUFFIX)
+                    || file_name.contains(&format!("{}.", env::consts::DLL_SUFFIX)))
+            {
+                libraries.push(entry.path());
+            }
+        }
+    }
+    libraries.sort();
+    libraries
+}
+
+/// Checks that the installed libclang can be loaded, as bindgen does.
+fn check_libclang(toolchain_name: &str, toolchain_dir: &Path) -> Vec<Check> {
+    find_libclang(toolchain_name, toolchain_dir)
+        .into_iter()
+        .map(|library| {
+            let name = format!(
+                "Library '{}'",
+                library.file_name().unwrap_or_default().to_string_lossy()
+            );
+            // SAFETY: libclang has no initialization routines with preconditions
+            match unsafe { Library::new(&library) } {
+                Ok(_) => Check::new(&name, Status::Ok, library.display().to_string()),
+                Err(e) => Check::new(
+                    &name,
+                    Status::Error,
+                    format!("'{}' can not be loaded: {e}", library.display()),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Checks a home directory variable, resolved to the path.
+fn check_home_variable(var: &str, value: Option<OsString>, resolved: &Path) -> Check {
+    let Some(value) = value.filter(|value| !value.is_empty()) else {
+        return Check::new(
+            var,
+            Status::Ok,
+            format!("'{}' (default)", resolved.display()),
+        );
+    };
+    if Path::new(&value).is_relative() {
+        Check::new(
+            var,
+            Status::Warning,
+            format!(
+                "'{}' is relative, it only resolves to '{}' in the current directory. Set it to an absolute path",
+                value.to_string_lossy(),
+                resolved.display()
+            ),
+        )
+    } else if !resolved.exists() {
+        Check::new(
+            var,
+            Status::Warning,
+            format!("'{}' does not exist", resolved.display()),
+        )
+    } else {
+        Check::new(var, Status::Ok, resolved.display().to_string())
+    }
+}
+
+/// Checks that the cargo and rustup homes are consistent with the environment.
+fn check_home_dirs(toolchain_name: &str) -> Vec<Check> {
+    let cargo_home = get_cargo_home();
+    let rustup_home = get_rustup_home();
+    let mut checks = vec![
+        check_home_variable("CARGO_HOME", env::var_os("CARGO_HOME"), &cargo_home),
+        check_home_variable("RUSTUP_HOME", env::var_os("RUSTUP_HOME"), &rustup_home),
+    ];
+    let cargo_bin = cargo_home.join("bin");
+    if !path_contains(&cargo_bin) {
+        checks.push(Check::new(
+            "Cargo binaries",
+            Status::Warning,
+            format!(
+                "'{}' is not in the PATH, the tools installed with cargo (e.g. espflash) will not be found",
+                cargo_bin.display()
+            ),
+        ));
+    }
+    let default_rustup_home = get_default_rustup_home();
+    let default_toolchain_dir = default_rustup_home.join("toolchains").join(toolchain_name);
+    if rustup_home != default_rustup_home && default_toolchain_dir.exists() {
+        checks.push(Check::new(
+            "Rustup home",
+            Status::Warning,
+            format!(
+                "'{toolchain_name}' is also installed in '{}', which is used where RUSTUP_HOME is not set (e.g. by IDEs)",
+                default_rustup_home.display()
+            ),
+        ));
+    }
+    checks
+}
+
+/// Checks whether a rustup override selects another toolchain in the current directory.
+fn check_rustup_override(toolchain_name: &str) -> Check {
+    let name = "Rustup override";
+    match get_rustup_override() {
+        Some(rustup_override) if rustup_override.toolchain != toolchain_name => Check::new(
+            name,
+            Status::Warning,
+            format!(
+                "'{}' is used in the current directory ({}) instead of '{toolchain_name}'",
+                rustup_override.toolchain, rustup_override.source
+            ),
+        ),
+        Some(rustup_override) => Check::new(
+            name,
+            Status::Ok,
+            format!(
+                "'{toolchain_name}' is selected ({})",
+                rustup_override.source
+            ),
+        ),
+        None => Check::new(
+            name,
+            Status::Ok,
+            "No override in the current directory".to_string(),
+        ),
+    }
+}
+
+/// Checks that the export file matches the exports of the installation and only points to existing paths.
+fn check_export_file(export_file: &Path, exports: &[String], shell: Shell) -> Vec<Check> {
+    let name = "Export file";
+    let expected = export_file_contents(exports, shell);
+    let Ok(contents) = read_to_string(export_file) else {
+        return vec![Check::new(
+            name,
+            Status::Warning,
+            format!(
+                "'{}' does not exist, run 'espup doctor --regenerate-exports' to create it",
+                export_file.display()
+            ),
+        )];
+    };
+
+    let mut checks = Vec::new();
+    if contents == expected {
+        checks.push(Check::new(
+            name,
+            Status::Ok,
+            export_file.display().to_string(),
+        ));
+    } else {
+        let differences = match (parse_environment(&expected), parse_environment(&contents)) {
+            (Ok(expected), Ok(actual)) => diff_environments(&expected, &actual)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>()
+                .join(", "),
+            _ => String::new(),
+        };
+        checks.push(Check::new(
+            name,
+            Status::Warning,
+            format!(
+                "'{}' differs from the exports of the installation ({differences}), run 'espup doctor --regenerate-exports' to regenerate it",
+                export_file.display()
+            ),
+        ));
+    }
+    if let Ok(entries) = parse_environment(&contents) {
+        for (key, values) in entries {
+            for value in values {
+                if Path::new(&value).is_absolute() && !Path::new(&value).exists() {
+                    checks.push(Check::new(
+                        name,
+                        Status::Error,
+                        format!("'{key}' points to '{value}', which does not exist"),
+                    ));
+                }
+            }
+        }
+    }
+    checks
+}
+
+/// Checks that other users cannot modify the files installed by espup.
+fn check_permissions(dirs: &[PathBuf]) -> Vec<Check> {
+    let name = "Permissions";
+    let mut checks = Vec::new();
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        let world_writable = find_world_writable(dir);
+        checks.push(match world_writable.first() {
+            Some(path) => Check::new(
+                name,
+                Status::Error,
+                format!(
+                    "{} files and directories in '{}' are writable by other users (e.g. '{}'), run 'chmod -R o-w {}'",
+                    world_writable.len(),
+                    dir.display(),
+                    path.display(),
+                    dir.display()
+                ),
+            ),
+            None => Check::new(
+                name,
+                Status::Ok,
+                format!("'{}' is not writable by other users", dir.display()),
+            ),
+        });
+    }
+    checks
+}
+
+/// Checks that the directories are not synced by a file hosting service.
+fn check_synced_folders(dirs: &[PathBuf]) -> Vec<Check> {
+    dirs.iter()
+        .filter_map(|dir| synced_folder_warning(dir))
+        .map(|warning| Check::new("Synced folder", Status::Warning, warning))
+        .collect()
+}
+
+/// Runs all the checks on the toolchain.
+pub fn run_checks(toolchain_name: &str, toolchain_dir: &Path) -> Vec<Check> {
+    let mut checks = check_home_dirs(toolchain_name);
+    if !toolchain_dir.exists() {
+        checks.push(Check::new(
+            "Toolchain directory",
+            Status::Error,
+            format!("'{}' does not exist", toolchain_dir.display()),
+        ));
+        return checks;
+    }
+    if is_corrupted_toolchain(toolchain_dir) {
+        checks.push(Check::new(
+            "Toolchain directory",
+            Status::Error,
+            format!(
+                "'{}' is corrupted, it does not hold 'bin/rustc'. Run 'espup install --name {toolchain_name}' to remove and reinstall it",
+                toolchain_dir.display()
+            ),
+        ));
+        return checks;
+    }
+    checks.push(Check::new(
+        "Toolchain directory",
+        Status::Ok,
+        toolchain_dir.display().to_string(),
+    ));
+    checks.extend(check_executables(toolchain_name, toolchain_dir));
+    checks.extend(check_libclang(toolchain_name, toolchain_dir));
+    let mut installed_dirs = vec![toolchain_dir.to_path_buf(), get_espup_home()];
+    let llvm_root = get_llvm_root(toolchain_name, toolchain_dir);
+    if !llvm_root.starts_with(toolchain_dir) {
+        installed_dirs.push(llvm_root);
+    }
+    checks.extend(check_permissions(&installed_dirs));
+    installed_dirs.push(get_cargo_home());
+    checks.extend(check_synced_folders(&installed_dirs));
+    checks.push(check_rustup_override(toolchain_name));
+    if let Some(toolchain_state) = State::load()
+        .ok()
+        .and_then(|state| state.toolchains.get(toolchain_name).cloned())
+    {
+        if let Some(export_file) = &toolchain_state.export_file {
+            checks.extend(check_export_file(
+                export_file,
+                &toolchain_state.exports,
+                Shell::from_installation(&toolchain_state),
+            ));
+        }
+    }
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        doctor::{
+            check_export_file, check_home_variable, find_libclang, parse_elf, Check, ElfInfo,
+            Status,
+        },
+        env::create_export_file,
+        shell::Shell,
+        toolchain::llvm::CLANG_NAME,
+    };
+    use std::{
+        env,
+        ffi::OsString,
+        fs::{create_dir_all, write},
+        path::Path,
+    };
+    use tempfile::TempDir;
+
+    /// Builds a minimal little-endian 32-bit x86 ELF with an interpreter.
+    fn elf_32(interpreter: &str) -> Vec<u8> {
+        let mut bytes = vec![0; 52 + 32];
+        bytes[0..4].copy_from_slice(b"\x7fELF");
+        bytes[4] = 1;
+        bytes[5] = 1;
+        bytes[18..20].copy_from_slice(&3u16.to_le_bytes());
+        bytes[28..32].copy_from_slice(&52u32.to_le_bytes());
+        bytes[42..44].copy_from_slice(&32u16.to_le_bytes());
+        bytes[44..46].copy_from_slice(&1u16.to_le_bytes());
+        let offset = bytes.len() as u32;
+        bytes[52..56].copy_from_slice(&3u32.to_le_bytes());
+        bytes[56..60].copy_from_slice(&offset.to_le_bytes());
+        bytes[68..72].copy_from_slice(&(interpreter.len() as u32 + 1).to_le_bytes());
+        bytes.extend_from_slice(interpreter.as_bytes());
+        bytes.push(0);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_elf() {
+        assert_eq!(
+            parse_elf(&elf_32("/lib/ld-linux.so.2")),
+            Some(ElfInfo {
+                is_64_bit: false,
+                arch: "x86".to_string(),
+                interpreter: Some("/lib/ld-linux.so.2".to_string()),
+            })
+        );
+        assert!(parse_elf(b"#!/bin/sh\n").is_none());
+        assert!(parse_elf(&[0; 64]).is_none());
+    }
+
+    #[test]
+    fn test_find_libclang() {
+        let temp_dir = TempDir::new().unwrap();
+        let lib_dir = temp_dir
+            .path()
+            .join(CLANG_NAME)
+            .join("esp-16.0.0-20230516")
+            .join("esp-clang")
+            .join(if cfg!(windows) { "bin" } else { "lib" });
+        create_dir_all(&lib_dir).unwrap();
+        let libclang = lib_dir.join(format!("libclang{}", env::consts::DLL_SUFFIX));
+        write(&libclang, "").unwrap();
+        write(lib_dir.join("libclang-cpp.a"), "").unwrap();
+        write(lib_dir.join("README"), "").unwrap();
+
+        assert_eq!(find_libclang("esp", temp_dir.path()), vec![libclang]);
+        assert!(find_libclang("esp", &temp_dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn test_check_home_variable() {
+        let temp_dir = TempDir::new().unwrap();
+        let status = |value: Option<&str>, resolved: &Path| {
+            check_home_variable("CARGO_HOME", value.map(OsString::from), resolved).status
+        };
+        assert_eq!(status(None, temp_dir.path()), Status::Ok);
+        assert_eq!(status(Some(""), temp_dir.path()), Status::Ok);
+        assert_eq!(
+            status(temp_dir.path().to_str(), temp_dir.path()),
+            Status::Ok
+        );
+        assert_eq!(
+            status(Some("cargo"), &temp_dir.path().join("cargo")),
+            Status::Warning
+        );
+        let missing = temp_dir.path().join("missing");
+        assert_eq!(status(missing.to_str(), &missing), Status::Warning);
+    }
+
+    #[test]
+    fn test_check_export_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let export_file = temp_dir.path().join("export-esp.sh");
+        let exports = vec![format!(
+            "export LIBCLANG_PATH=\"{}\"",
+            temp_dir.path().display()
+        )];
+        let statuses = |checks: Vec<Check>| -> Vec<Status> {
+            checks.into_iter().map(|check| check.status).collect()
+        };
+
+        assert_eq!(
+            statuses(check_export_file(&export_file, &exports, Shell::Posix)),
+            vec![Status::Warning]
+        );
+        create_export_file(&export_file, &exports, Shell::Posix).unwrap();
+        assert_eq!(
+            statuses(check_export_file(&export_file, &exports, Shell::Posix)),
+            vec![Status::Ok]
+        );
+        // Stale export, pointing to an uninstalled toolchain
+        let missing = temp_dir.path().join("missing");
+        write(
+            &export_file,
+            format!("export LIBCLANG_PATH=\"{}\"\n", missing.display()),
+        )
+        .unwrap();
+        assert_eq!(
+            statuses(check_export_file(&export_file, &exports, Shell::Posix)),
+            vec![Status::Warning, Status::Error]
+        );
+    }
+}
@@ -1,24 +1,42 @@
 //! Environment variables set up and export file support.
 
-use crate::error::Error;
-use directories::BaseDirs;
+use crate::{
+    error::Error,
+    paths::{get_espup_home, get_home_dir},
+    shell::Shell,
+    state::{State, ToolchainState},
+    targets::Target,
+    toolchain::{
+        gcc::{RISCV_GCC, XTENSA_GCC},
+        required_gcc_archs,
+    },
+};
 use log::debug;
+use serde::Serialize;
 use std::{
+    collections::HashSet,
     env,
-    fs::File,
+    fs::{create_dir_all, write, File},
     io::Write,
     path::{Path, PathBuf},
 };
+#[cfg(unix)]
+use std::{
+    fs::{set_permissions, Permissions},
+    os::unix::fs::PermissionsExt,
+};
 #[cfg(windows)]
 use winreg::{
     enums::{HKEY_CURRENT_USER, KEY_READ, KEY_WRITE},
     RegKey,
 };
 
+/// Export file generated in the home directory by previous versions of espup.
 #[cfg(windows)]
-const DEFAULT_EXPORT_FILE: &str = "export-esp.ps1";
+const LEGACY_EXPORT_FILE: &str = "export-esp.ps1";
+/// Export file generated in the home directory by previous versions of espup.
 #[cfg(not(windows))]
-const DEFAULT_EXPORT_FILE: &str = "export-esp.sh";
+const LEGACY_EXPORT_FILE: &str = "export-esp.sh";
 
 #[cfg(windows)]
 /// Sets an environment variable for the current user.
@@ -71,8 +89,12 @@ pub fn delete_env_variable(key: &str) -> Result<(), Error> {
     Ok(())
 }
 
-/// Returns the absolute path to the export file, uses the DEFAULT_EXPORT_FILE if no arg is provided.
-pub fn get_export_file(export_file: Option<PathBuf>) -> Result<PathBuf, Error> {
+/// Returns the absolute path to the export file, uses the default export file of the shell and toolchain under the espup home if no arg is provided.
+pub fn get_export_file(
+    export_file: Option<PathBuf>,
+    shell: Shell,
+    toolchain_name: &str,
+) -> Result<PathBuf, Error> {
     if let Some(export_file) = export_file {
         if export_file.is_dir() {
             return Err(Error::InvalidDestination(export_file.display().to_string()));
@@ -84,27 +106,238 @@ pub fn get_export_file(export_file: Option<PathBuf>) -> Result<PathBuf, Error> {
             Ok(current_dir.join(export_file))
         }
     } else {
-        Ok(BaseDirs::new()
-            .unwrap()
-            .home_dir()
-            .join(DEFAULT_EXPORT_FILE))
+        Ok(get_espup_home().join(shell.default_export_file(toolchain_name)))
     }
 }
 
-/// Creates the export file with the necessary environment variables.
-pub fn create_export_file(export_file: &PathBuf, exports: &[String]) -> Result<(), Error> {
-    debug!("Creating export file");
-    let mut file = File::create(export_file)?;
-    for e in exports.iter() {
+/// Returns the export file generated by the installation of the toolchain, unless one is provided.
+pub fn get_installed_export_file(
+    toolchain_name: &str,
+    export_file: Option<PathBuf>,
+) -> Result<PathBuf, Error> {
+    if export_file.is_some() {
+        return get_export_file(export_file, Shell::native(), toolchain_name);
+    }
+    match State::load()?
+        .toolchains
+        .get(toolchain_name)
+        .and_then(|toolchain| toolchain.export_file.clone())
+    {
+        Some(export_file) => Ok(export_file),
+        None => get_export_file(None, Shell::detect(), toolchain_name),
+    }
+}
+
+/// Returns the export file generated in the home directory by previous versions of espup.
+pub fn get_legacy_export_file() -> PathBuf {
+    get_home_dir().join(LEGACY_EXPORT_FILE)
+}
+
+/// Replaces the export file of previous versions of espup with a one line script sourcing the
+/// export file, so that the shell profiles sourcing it keep up with the installations.
+///
+/// Returns false, leaving it untouched, if it is not of the shell of the export file.
+pub fn update_legacy_export_file(
+    legacy_export_file: &Path,
+    export_file: &Path,
+    shell: Shell,
+) -> Result<bool, Error> {
+    if Shell::from_export_file(legacy_export_file) != Some(shell) {
+        return Ok(false);
+    }
+    let contents = format!(
+        "# Generated by espup, which now writes the environment to '{}'\n{}\n",
+        export_file.display(),
+        shell.source_command(export_file)
+    );
+    write(legacy_export_file, contents)?;
+    Ok(true)
+}
+
+/// Returns the directory an export prepends to the `PATH`, if it modifies the `PATH`.
+fn path_addition(export: &str) -> Option<&str> {
+    export
+        .strip_prefix("export PATH=\"")
+        .and_then(|export| export.strip_suffix(":$PATH\""))
+        .or_else(|| {
+            export
+                .strip_prefix("$Env:PATH = \"")
+                .and_then(|export| export.strip_suffix(";\" + $Env:PATH"))
+        })
+}
+
+/// Returns the name and value of the variable an export sets, if it sets one.
+fn variable_assignment(export: &str) -> Option<(&str, &str)> {
+    let (name, value) = export
+        .strip_prefix("export ")
+        .and_then(|export| export.split_once('='))
+        .or_else(|| {
+            export
+                .strip_prefix("$Env:")
+                .and_then(|export| export.split_once(" = "))
+        })?;
+    Some((name, value.strip_prefix('"')?.strip_suffix('"')?))
+}
+
+/// Returns the export, in the syntax of the export file, setting the environment variable.
+pub fn export_variable(name: &str, value: &str) -> String {
+    #[cfg(windows)]
+    return format!("$Env:{name} = \"{value}\"");
+    #[cfg(not(windows))]
+    return format!("export {name}=\"{value}\"");
+}
+
+/// Parses a variable added to the export file, given as 'KEY=VALUE'.
+pub fn parse_variable(variable: &str) -> Result<(String, String), Error> {
+    let invalid = || Error::InvalidVariable(variable.to_string());
+    let (name, value) = variable.split_once('=').ok_or_else(invalid)?;
+    let valid_name = name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_name || value.contains(['"', '\n', '\r']) {
+        return Err(invalid());
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Environment set up by the exports of an installation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Environment {
+    /// Variables set by the exports, in order.
+    pub variables: Vec<(String, String)>,
+    /// Directories prepended to the `PATH` by the exports, without duplicates.
+    pub path: Vec<PathBuf>,
+}
+
+impl Environment {
+    /// Collects the environment set up by the exports, ignoring the exports that are not recognized.
+    pub fn from_exports(exports: &[String]) -> Self {
+        let mut environment = Self::default();
+        for export in exports {
+            if let Some(path) = path_addition(export).map(PathBuf::from) {
+                if !environment.path.contains(&path) {
+                    environment.path.push(path);
+                }
+            } else if let Some((name, value)) = variable_assignment(export) {
+                environment
+                    .variables
+                    .push((name.to_string(), value.to_string()));
+            }
+        }
+        environment
+    }
+}
+
+/// Translates the exports into the syntax of the shell, merging the `PATH` additions into a single deduplicated export.
+///
+/// The merged export takes the place of the first `PATH` addition and does nothing when the
+/// export file is sourced again, so the `PATH` does not grow with every sourcing. Exports that
+/// are not recognized are kept as they are.
+fn merge_path_exports(exports: &[String], shell: Shell) -> Vec<String> {
+    let mut merged = Vec::new();
+    let mut paths: Vec<&str> = Vec::new();
+    let mut position = None;
+    for export in exports {
+        match path_addition(export) {
+            Some(path) => {
+                position.get_or_insert(merged.len());
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+            None => merged.push(match variable_assignment(export) {
+                Some((name, value)) => shell.set_variable(name, value),
+                None => export.clone(),
+            }),
+        }
+    }
+    if let Some(position) = position {
+        merged.insert(position, shell.prepend_path(&paths));
+    }
+    merged
+}
+
+/// Returns the contents of the export file setting the environment variables in the shell.
+pub fn export_file_contents(exports: &[String], shell: Shell) -> String {
+    let mut contents = String::new();
+    for e in merge_path_exports(exports, shell).iter() {
         #[cfg(windows)]
         let e = e.replace('/', r"\");
-        file.write_all(e.as_bytes())?;
-        file.write_all(b"\n")?;
+        contents.push_str(e);
+        contents.push('\n');
+    }
+    contents
+}
+
+/// Creates the export file with the necessary environment variables.
+pub fn create_export_file(
+    export_file: &PathBuf,
+    exports: &[String],
+    shell: Shell,
+) -> Result<(), Error> {
+    debug!("Creating export file for {}", shell);
+    if let Some(parent) = export_file.parent() {
+        create_dir_all(parent).map_err(|_| Error::CreateDirectory(parent.display().to_string()))?;
     }
+    let mut file = File::create(export_file)?;
+    file.write_all(export_file_contents(exports, shell).as_bytes())?;
+    // The file is sourced by the shell, only its owner may modify it whatever the umask is
+    #[cfg(unix)]
+    set_permissions(export_file, Permissions::from_mode(0o644))?;
 
     Ok(())
 }
 
+/// Returns the export file of the target, next to the export file of the installation (e.g.
+/// 'env-esp32s3' next to 'env', or 'export-esp32s3.sh' next to 'export.sh').
+pub fn get_target_export_file(export_file: &Path, target: Target) -> PathBuf {
+    let file_name = export_file
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Only the extensions of the shells are split, toolchain names hold dots (e.g. 'env-esp-1.73')
+    let file_name = match export_file.extension() {
+        Some(extension) if Shell::from_export_file(export_file).is_some() => {
+            let extension = extension.to_string_lossy();
+            let stem = file_name.trim_end_matches(&format!(".{extension}"));
+            format!("{stem}-{target}.{extension}")
+        }
+        _ => format!("{file_name}-{target}"),
+    };
+    export_file.with_file_name(file_name)
+}
+
+/// Returns the exports the target uses, leaving out the GCC toolchains only other targets use.
+pub fn target_exports(exports: &[String], target: Target) -> Vec<String> {
+    let required = required_gcc_archs(&HashSet::from([target]));
+    exports
+        .iter()
+        .filter(|export| {
+            [XTENSA_GCC, RISCV_GCC]
+                .iter()
+                .all(|arch| required.contains(arch) || !export.contains(arch))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Returns the export files of the targets recorded by the installation, with their exports.
+pub fn target_export_files(toolchain_state: &ToolchainState) -> Vec<(PathBuf, Vec<String>)> {
+    let Some(export_file) = &toolchain_state.export_file else {
+        return Vec::new();
+    };
+    toolchain_state
+        .export_targets
+        .iter()
+        .filter_map(|target| target.parse().ok())
+        .map(|target| {
+            (
+                get_target_export_file(export_file, target),
+                target_exports(&toolchain_state.exports, target),
+            )
+        })
+        .collect()
+}
+
 #[cfg(windows)]
 /// Instructions to export the environment variables.
 pub fn set_env() -> Result<(), Error> {
@@ -159,7 +392,7 @@ pub fn clean_env() -> Result<(), Error> {
 }
 
 /// Instructions to export the environment variables.
-pub fn print_post_install_msg(export_file: &Path) -> Result<(), Error> {
+pub fn print_post_install_msg(export_file: &Path, shell: Shell) -> Result<(), Error> {
     #[cfg(windows)]
     if cfg!(windows) {
         println!(
@@ -173,8 +406,8 @@ pub fn print_post_install_msg(export_file: &Path) -> Result<(), Error> {
     #[cfg(unix)]
     if cfg!(unix) {
         println!(
-            "\n\tTo get started, you need to set up some environment variables by running: '. {}'",
-            export_file.display()
+            "\n\tTo get started, you need to set up some environment variables by running: '{}'",
+            shell.source_command(export_file)
         );
         println!(
             "\tThis step must be done every time you open a new terminal.\n\t    See other methods for setting the environment in https://esp-rs.github.io/book/installation/riscv-and-xtensa.html#3-set-up-the-environment-variables",
@@ -185,11 +418,20 @@ pub fn print_post_install_msg(export_file: &Path) -> Result<(), Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::env::{create_export_file, get_export_file, DEFAULT_EXPORT_FILE};
+    use crate::{
+        env::{
+            create_export_file, get_export_file, get_target_export_file, merge_path_exports,
+            parse_variable, target_export_files, target_exports, update_legacy_export_file,
+            Environment,
+        },
+        shell::Shell,
+        state::{get_espup_home, ToolchainState},
+        targets::Target,
+    };
     use directories::BaseDirs;
     use std::{
         env::current_dir,
-        fs::{create_dir_all, read_to_string},
+        fs::{create_dir_all, read_to_string, write},
         path::PathBuf,
     };
     use tempfile::TempDir;
@@ -199,23 +441,52 @@ mod tests {
     fn test_get_export_file() {
         // No arg provided
         let home_dir = BaseDirs::new().unwrap().home_dir().to_path_buf();
-        let export_file = home_dir.join(DEFAULT_EXPORT_FILE);
-        assert!(matches!(get_export_file(None), Ok(export_file)));
+        let export_file = get_espup_home().join("env.fish");
+        assert_eq!(
+            get_export_file(None, Shell::Fish, "esp").unwrap(),
+            export_file
+        );
+        let export_file = get_espup_home().join("env-esp-1.73.fish");
+        assert_eq!(
+            get_export_file(None, Shell::Fish, "esp-1.73").unwrap(),
+            export_file
+        );
         // Relative path
         let current_dir = current_dir().unwrap();
         let export_file = current_dir.join("export.sh");
         assert!(matches!(
-            get_export_file(Some(PathBuf::from("export.sh"))),
+            get_export_file(Some(PathBuf::from("export.sh")), Shell::Posix, "esp"),
             Ok(export_file)
         ));
         // Absolute path
         let export_file = PathBuf::from("/home/user/export.sh");
         assert!(matches!(
-            get_export_file(Some(PathBuf::from("/home/user/export.sh"))),
+            get_export_file(
+                Some(PathBuf::from("/home/user/export.sh")),
+                Shell::Posix,
+                "esp"
+            ),
             Ok(export_file)
         ));
         // Path is a directory instead of a file
-        assert!(get_export_file(Some(home_dir)).is_err());
+        assert!(get_export_file(Some(home_dir), Shell::Posix, "esp").is_err());
+    }
+
+    #[test]
+    fn test_parse_variable() {
+        assert_eq!(
+            parse_variable("WIFI_SSID=My network").unwrap(),
+            ("WIFI_SSID".to_string(), "My network".to_string())
+        );
+        assert_eq!(
+            parse_variable("ESP_LOG=").unwrap(),
+            ("ESP_LOG".to_string(), String::new())
+        );
+        assert!(parse_variable("ESP_LOG").is_err());
+        assert!(parse_variable("=info").is_err());
+        assert!(parse_variable("1ESP_LOG=info").is_err());
+        assert!(parse_variable("ESP-LOG=info").is_err());
+        assert!(parse_variable("WIFI_PASSWORD=\"secret\"").is_err());
     }
 
     #[test]
@@ -227,7 +498,7 @@ mod tests {
             "export VAR1=value1".to_string(),
             "export VAR2=value2".to_string(),
         ];
-        create_export_file(&export_file, &exports).unwrap();
+        create_export_file(&export_file, &exports, Shell::Posix).unwrap();
         let contents = read_to_string(export_file).unwrap();
         assert_eq!(contents, "export VAR1=value1\nexport VAR2=value2\n");
 
@@ -239,6 +510,143 @@ mod tests {
             "export VAR1=value1".to_string(),
             "export VAR2=value2".to_string(),
         ];
-        assert!(create_export_file(&export_file, &exports).is_err());
+        assert!(create_export_file(&export_file, &exports, Shell::Posix).is_err());
+    }
+
+    #[test]
+    fn test_update_legacy_export_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let legacy_export_file = temp_dir.path().join("export-esp.sh");
+        let export_file = temp_dir.path().join(".espup").join("env");
+        write(&legacy_export_file, "export LIBCLANG_PATH=\"/old\"\n").unwrap();
+
+        // Only the export files of its shell are sourced
+        assert!(
+            !update_legacy_export_file(&legacy_export_file, &export_file, Shell::Fish).unwrap()
+        );
+        assert_eq!(
+            read_to_string(&legacy_export_file).unwrap(),
+            "export LIBCLANG_PATH=\"/old\"\n"
+        );
+        assert!(
+            update_legacy_export_file(&legacy_export_file, &export_file, Shell::Posix).unwrap()
+        );
+        let contents = read_to_string(&legacy_export_file).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        assert!(contents.starts_with('#'));
+        assert!(contents.ends_with(&format!(". {}\n", export_file.display())));
+    }
+
+    #[test]
+    fn test_target_export_files() {
+        assert_eq!(
+            get_target_export_file(&PathBuf::from("/home/user/.espup/env"), Target::ESP32S3),
+            PathBuf::from("/home/user/.espup/env-esp32s3")
+        );
+        assert_eq!(
+            get_target_export_file(&PathBuf::from("/home/user/export.sh"), Target::ESP32C3),
+            PathBuf::from("/home/user/export-esp32c3.sh")
+        );
+        assert_eq!(
+            get_target_export_file(&PathBuf::from("env-esp-1.73.fish"), Target::ESP32),
+            PathBuf::from("env-esp-1.73-esp32.fish")
+        );
+        assert_eq!(
+            get_target_export_file(&PathBuf::from("env-esp-1.73"), Target::ESP32),
+            PathBuf::from("env-esp-1.73-esp32")
+        );
+
+        let exports = vec![
+            "export PATH=\"/esp/xtensa-esp-elf/bin:$PATH\"".to_string(),
+            "export LIBCLANG_PATH=\"/esp/esp-clang/lib\"".to_string(),
+            "export PATH=\"/esp/riscv32-esp-elf/bin:$PATH\"".to_string(),
+        ];
+        assert_eq!(
+            target_exports(&exports, Target::ESP32),
+            vec![exports[0].clone(), exports[1].clone()]
+        );
+        assert_eq!(
+            target_exports(&exports, Target::ESP32C3),
+            vec![exports[1].clone(), exports[2].clone()]
+        );
+        // The ULP coprocessor of the ESP32-S3 uses the RISC-V toolchain
+        assert_eq!(target_exports(&exports, Target::ESP32S3), exports);
+
+        let toolchain_state = ToolchainState {
+            export_file: Some(PathBuf::from("/home/user/.espup/env")),
+            exports: exports.clone(),
+            export_targets: vec!["esp32c3".to_string(), "esp33".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            target_export_files(&toolchain_state),
+            vec![(
+                PathBuf::from("/home/user/.espup/env-esp32c3"),
+                vec![exports[1].clone(), exports[2].clone()]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_merge_path_exports() {
+        let exports = vec![
+            "export PATH=\"/esp/xtensa-esp-elf/bin:$PATH\"".to_string(),
+            "export LIBCLANG_PATH=\"/esp/esp-clang/lib\"".to_string(),
+            "export PATH=\"/esp/riscv32-esp-elf/bin:$PATH\"".to_string(),
+            "export PATH=\"/esp/xtensa-esp-elf/bin:$PATH\"".to_string(),
+        ];
+        assert_eq!(
+            merge_path_exports(&exports, Shell::Posix),
+            vec![
+                "case \":$PATH:\" in *\":/esp/xtensa-esp-elf/bin:/esp/riscv32-esp-elf/bin:\"*) ;; *) export PATH=\"/esp/xtensa-esp-elf/bin:/esp/riscv32-esp-elf/bin:$PATH\" ;; esac",
+                "export LIBCLANG_PATH=\"/esp/esp-clang/lib\"",
+            ]
+        );
+        assert_eq!(
+            merge_path_exports(&exports, Shell::Fish),
+            vec![
+                "fish_add_path -gP \"/esp/xtensa-esp-elf/bin\" \"/esp/riscv32-esp-elf/bin\"",
+                "set -gx LIBCLANG_PATH \"/esp/esp-clang/lib\"",
+            ]
+        );
+        let exports = vec!["export LIBCLANG_PATH=\"/esp/esp-clang/lib\"".to_string()];
+        assert_eq!(merge_path_exports(&exports, Shell::Posix), exports);
+        let exports = vec![
+            "$Env:LIBCLANG_PATH = \"C:/esp/esp-clang/bin/libclang.dll\"".to_string(),
+            "$Env:PATH = \"C:/esp/esp-clang/bin;\" + $Env:PATH".to_string(),
+            "unknown".to_string(),
+        ];
+        assert_eq!(
+            merge_path_exports(&exports, Shell::Cmd),
+            vec![
+                "set \"LIBCLANG_PATH=C:/esp/esp-clang/bin/libclang.dll\"",
+                "set \"PATH=C:/esp/esp-clang/bin;%PATH%\"",
+                "unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_environment_from_exports() {
+        let exports = vec![
+            "export PATH=\"/esp/xtensa-esp-elf/bin:$PATH\"".to_string(),
+            "export LIBCLANG_PATH=\"/esp/esp-clang/lib\"".to_string(),
+            "$Env:PATH = \"/esp/riscv32-esp-elf/bin;\" + $Env:PATH".to_string(),
+            "export PATH=\"/esp/xtensa-esp-elf/bin:$PATH\"".to_string(),
+            "unknown".to_string(),
+        ];
+        assert_eq!(
+            Environment::from_exports(&exports),
+            Environment {
+                variables: vec![(
+                    "LIBCLANG_PATH".to_string(),
+                    "/esp/esp-clang/lib".to_string()
+                )],
+                path: vec![
+                    PathBuf::from("/esp/xtensa-esp-elf/bin"),
+                    PathBuf::from("/esp/riscv32-esp-elf/bin")
+                ],
+            }
+        );
     }
 }
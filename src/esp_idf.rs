@@ -0,0 +1,120 @@
+//! Support periods of the ESP-IDF releases, to warn about the ones that reached their end of life.
+
+/// End of life of the ESP-IDF releases, as `(major, minor, year, month)`, oldest first.
+///
+/// Every release is supported for 30 months after it is published, see the support policy of the
+/// ESP-IDF: <https://github.com/espressif/esp-idf/blob/master/SUPPORT_POLICY.md>.
+const SUPPORT_PERIODS: [(u32, u32, u32, u32); 11] = [
+    (4, 0, 2022, 8),
+    (4, 1, 2023, 2),
+    (4, 2, 2023, 6),
+    (4, 3, 2023, 12),
+    (4, 4, 2025, 7),
+    (5, 0, 2025, 5),
+    (5, 1, 2025, 12),
+    (5, 2, 2026, 8),
+    (5, 3, 2027, 1),
+    (5, 4, 2027, 6),
+    (5, 5, 2028, 1),
+];
+
+/// Parses the release of an ESP-IDF version, tag or branch (e.g. 'v4.3.2' or 'release/v4.3').
+fn parse_release(version: &str) -> Option<(u32, u32)> {
+    let version = version
+        .trim_start_matches("release/")
+        .trim_start_matches('v');
+    let mut parts = version.split(['.', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map_or(Some(0), |minor| minor.parse().ok())?;
+    Some((major, minor))
+}
+
+/// Returns the year and month of the time, in seconds since the Unix epoch.
+fn year_month(timestamp: u64) -> (u32, u32) {
+    // Civil from days, from http://howardhinnant.github.io/date_algorithms.html
+    let days = timestamp / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year as u32, month as u32)
+}
+
+/// Returns the warning to show when the ESP-IDF version reached its end of life at the time, in
+/// seconds since the Unix epoch, recommending the releases that are still supported.
+///
+/// Versions older than the first known release are also reported, the newer ones are not.
+pub fn end_of_life_warning(version: &str, timestamp: u64) -> Option<String> {
+    let release = parse_release(version)?;
+    let today = year_month(timestamp);
+    let end_of_life = SUPPORT_PERIODS
+        .iter()
+        .find(|(major, minor, ..)| (*major, *minor) == release)
+        .map(|(.., year, month)| (*year, *month));
+    let (first_major, first_minor, ..) = SUPPORT_PERIODS[0];
+    let is_end_of_life = match end_of_life {
+        Some(end_of_life) => end_of_life < today,
+        None => release < (first_major, first_minor),
+    };
+    if !is_end_of_life {
+        return None;
+    }
+    let supported: Vec<String> = SUPPORT_PERIODS
+        .iter()
+        .filter(|(.., year, month)| (*year, *month) >= today)
+        .map(|(major, minor, ..)| format!("v{major}.{minor}"))
+        .collect();
+    let mut warning = format!(
+        "ESP-IDF {version} reached its end of life{}, it no longer gets bug fixes nor security fixes",
+        end_of_life.map_or(String::new(), |(year, month)| format!(" in {year}-{month:02}"))
+    );
+    if !supported.is_empty() {
+        warning.push_str(&format!(
+            ". Consider moving to a supported release: {}",
+            supported.join(", ")
+        ));
+    }
+    Some(warning)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::esp_idf::{end_of_life_warning, parse_release, year_month};
+
+    #[test]
+    fn test_parse_release() {
+        assert_eq!(parse_release("v4.3.2"), Some((4, 3)));
+        assert_eq!(parse_release("release/v4.4"), Some((4, 4)));
+        assert_eq!(parse_release("5.1"), Some((5, 1)));
+        assert_eq!(parse_release("v5"), Some((5, 0)));
+        assert_eq!(parse_release("v5.2-beta1"), Some((5, 2)));
+        assert_eq!(parse_release("master"), None);
+    }
+
+    #[test]
+    fn test_end_of_life_warning() {
+        // 2026-10-14
+        let timestamp = 1792000000;
+        assert_eq!(year_month(timestamp), (2026, 10));
+        assert_eq!(year_month(0), (1970, 1));
+        assert_eq!(
+            end_of_life_warning("v4.3", timestamp).unwrap(),
+            "ESP-IDF v4.3 reached its end of life in 2023-12, it no longer gets bug fixes nor security fixes. Consider moving to a supported release: v5.3, v5.4, v5.5"
+        );
+        assert!(end_of_life_warning("v3.3.5", timestamp)
+            .unwrap()
+            .starts_with("ESP-IDF v3.3.5 reached its end of life, "));
+        assert!(end_of_life_warning("v5.2.1", timestamp).is_some());
+        assert!(end_of_life_warning("v5.3", timestamp).is_none());
+        assert!(end_of_life_warning("v6.0", timestamp).is_none());
+        assert!(end_of_life_warning("master", timestamp).is_none());
+    }
+}
@@ -0,0 +1,187 @@
+//! Explanation of the components installed for a chip, and why they are required.
+
+use crate::{
+    targets::Target,
+    toolchain::{
+        gcc::{RISCV_GCC, XTENSA_GCC},
+        required_gcc_archs, requires_llvm,
+        rust::RiscVTarget,
+    },
+};
+use std::{collections::HashSet, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// Name of the component.
+    pub name: String,
+    /// Whether espup installs the component.
+    pub installed: bool,
+    /// Why the component is installed, or not.
+    pub reason: String,
+}
+
+impl Component {
+    fn new(name: &str, installed: bool, reason: String) -> Self {
+        Self {
+            name: name.to_string(),
+            installed,
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.installed { "+" } else { "-" };
+        write!(f, "{status} {}: {}", self.name, self.reason)
+    }
+}
+
+/// Returns the minimal installation command for the chip.
+pub fn recipe(chip: Target, std: bool) -> String {
+    let mut command = format!("espup install --targets {chip}");
+    if chip.is_riscv() {
+        command.push_str(" --arch riscv");
+    }
+    if std {
+        command.push_str(" --std");
+    }
+    command
+}
+
+/// Explains the components the minimal installation for the chip is made of.
+pub fn explain(chip: Target, std: bool) -> Vec<Component> {
+    let targets: HashSet<Target> = [chip].into_iter().collect();
+    let arch = if chip.is_xtensa() { "xtensa" } else { "riscv" };
+    let gcc_archs = if std {
+        Vec::new()
+    } else {
+        required_gcc_archs(&targets)
+    };
+    let rust_target = chip.rust_target(std);
+    let mut components = Vec::new();
+
+    components.push(if chip.is_xtensa() {
+        Component::new(
+            "Xtensa Rust toolchain",
+            true,
+            format!("{chip} is an Xtensa chip, which the upstream Rust toolchain does not support. It provides the '{rust_target}' target and its sources"),
+        )
+    } else {
+        Component::new(
+            "Xtensa Rust toolchain",
+            false,
+            format!("{chip} is a RISC-V chip, supported by the upstream Rust toolchain"),
+        )
+    });
+    components.push(if chip.is_riscv() {
+        let mut riscv_target = RiscVTarget::new("nightly", "esp");
+        riscv_target.add_chips(&targets);
+        Component::new(
+            "RISC-V Rust targets",
+            true,
+            format!(
+                "the nightly toolchain gets the 'rust-src' component and the {} targets, '{rust_target}' is built from the sources",
+                riscv_target.targets.join(", ")
+            ),
+        )
+    } else {
+        Component::new(
+            "RISC-V Rust targets",
+            false,
+            format!("{chip} applications are built with the Xtensa Rust toolchain"),
+        )
+    });
+    components.push(if requires_llvm(Some(arch), std) && chip.is_xtensa() {
+        Component::new(
+            "LLVM",
+            true,
+            "the Xtensa Rust toolchain requires the Espressif fork of LLVM, which also provides libclang to bindgen".to_string(),
+        )
+    } else if requires_llvm(Some(arch), std) {
+        Component::new(
+            "LLVM",
+            true,
+            "bindgen requires libclang to generate the bindings of the ESP-IDF".to_string(),
+        )
+    } else {
+        Component::new(
+            "LLVM",
+            false,
+            "RISC-V no_std applications do not generate bindings".to_string(),
+        )
+    });
+    for (gcc, name) in [(XTENSA_GCC, "Xtensa GCC"), (RISCV_GCC, "RISC-V GCC")] {
+        components.push(if gcc_archs.contains(&gcc) {
+            let reason = if gcc == RISCV_GCC && chip.is_xtensa() {
+                format!("builds the programs of the ULP coprocessor of the {chip}")
+            } else {
+                format!("links {chip} no_std applications")
+            };
+            Component::new(name, true, reason)
+        } else if std {
+            Component::new(
+                name,
+                false,
+                "esp-idf-sys installs the GCC of the ESP-IDF for std applications".to_string(),
+            )
+        } else {
+            Component::new(name, false, format!("{chip} does not use it"))
+        });
+    }
+    components.push(if std {
+        Component::new(
+            "ESP-IDF and its tools",
+            false,
+            "esp-idf-sys installs them on the first build, in '.embuild' or '$IDF_TOOLS_PATH'"
+                .to_string(),
+        )
+    } else {
+        Component::new(
+            "ESP-IDF and its tools",
+            false,
+            "no_std applications do not use the ESP-IDF".to_string(),
+        )
+    });
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        explain::{explain, recipe},
+        targets::Target,
+    };
+
+    fn installed(chip: Target, std: bool) -> Vec<String> {
+        explain(chip, std)
+            .into_iter()
+            .filter(|component| component.installed)
+            .map(|component| component.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_explain() {
+        assert_eq!(
+            installed(Target::ESP32C3, false),
+            vec!["RISC-V Rust targets", "RISC-V GCC"]
+        );
+        assert_eq!(
+            installed(Target::ESP32C3, true),
+            vec!["RISC-V Rust targets", "LLVM"]
+        );
+        assert_eq!(
+            installed(Target::ESP32S3, false),
+            vec!["Xtensa Rust toolchain", "LLVM", "Xtensa GCC", "RISC-V GCC"]
+        );
+        assert_eq!(
+            installed(Target::ESP32, true),
+            vec!["Xtensa Rust toolchain", "LLVM"]
+        );
+        assert_eq!(
+            recipe(Target::ESP32C3, true),
+            "espup install --targets esp32c3 --arch riscv --std"
+        );
+    }
+}
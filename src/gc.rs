@@ -0,0 +1,118 @@
+//! Detection of the installed toolchains that have not been used for a while.
+
+use crate::{error::Error, state::State};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds in a day.
+const DAY: u64 = 24 * 60 * 60;
+
+/// Usage of an installed toolchain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage<'a> {
+    /// Name of the toolchain.
+    pub name: &'a str,
+    /// Time elapsed since the toolchain was last used, if it was ever recorded.
+    pub unused_for: Option<Duration>,
+}
+
+/// Parses a duration in hours, days or weeks (e.g. '12h', '90d' or '4w').
+pub fn parse_duration(duration: &str) -> Result<Duration, Error> {
+    let duration = duration.trim();
+    let invalid = || Error::InvalidDuration(duration.to_string());
+    let (seconds, amount) = [("h", 60 * 60), ("d", DAY), ("w", 7 * DAY)]
+        .into_iter()
+        .find_map(|(unit, seconds)| Some((seconds, duration.strip_suffix(unit)?)))
+        .ok_or_else(invalid)?;
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// Returns the current time, in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Returns the toolchains that have not been used for the duration, or whose usage was never recorded.
+pub fn unused_toolchains(state: &State, now: u64, unused_for: Duration) -> Vec<Usage<'_>> {
+    state
+        .toolchains
+        .iter()
+        .map(|(name, toolchain)| Usage {
+            name,
+            unused_for: toolchain
+                .last_used
+                .map(|last_used| Duration::from_secs(now.saturating_sub(last_used))),
+        })
+        .filter(|usage| !matches!(usage.unused_for, Some(elapsed) if elapsed < unused_for))
+        .collect()
+}
+
+/// Formats the elapsed time in whole days, or whole hours when it is shorter than a day.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    match (elapsed.as_secs() / DAY, elapsed.as_secs() / 3600) {
+        (0, 1) => "1 hour".to_string(),
+        (0, hours) => format!("{hours} hours"),
+        (1, _) => "1 day".to_string(),
+        (days, _) => format!("{days} days"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        gc::{format_elapsed, parse_duration, unused_toolchains, Usage, DAY},
+        state::State,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(
+            parse_duration("90d").unwrap(),
+            Duration::from_secs(90 * DAY)
+        );
+        assert_eq!(parse_duration("2w").unwrap(), Duration::from_secs(14 * DAY));
+        assert_eq!(
+            parse_duration("12h").unwrap(),
+            Duration::from_secs(12 * 3600)
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("90").is_err());
+        assert!(parse_duration("3m").is_err());
+        assert!(parse_duration("-1d").is_err());
+        assert!(parse_duration("5é").is_err());
+        assert!(parse_duration("é").is_err());
+        assert!(parse_duration("99999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_unused_toolchains() {
+        let now = 100 * DAY;
+        let mut state = State::default();
+        state.toolchain("esp").last_used = Some(now - DAY);
+        state.toolchain("esp-1.73").last_used = Some(now - 91 * DAY);
+        state.toolchain("legacy");
+        assert_eq!(
+            unused_toolchains(&state, now, Duration::from_secs(90 * DAY)),
+            vec![
+                Usage {
+                    name: "esp-1.73",
+                    unused_for: Some(Duration::from_secs(91 * DAY)),
+                },
+                Usage {
+                    name: "legacy",
+                    unused_for: None,
+                },
+            ]
+        );
+        assert_eq!(format_elapsed(Duration::from_secs(91 * DAY + 5)), "91 days");
+        assert_eq!(format_elapsed(Duration::from_secs(DAY)), "1 day");
+        assert_eq!(format_elapsed(Duration::from_secs(12 * 3600)), "12 hours");
+    }
+}
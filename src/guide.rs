@@ -0,0 +1,268 @@
+//! Guided next steps after an installation.
+
+use crate::targets::Target;
+use std::{collections::HashSet, path::Path};
+
+/// Returns the steps to start developing with the installed targets.
+pub fn next_steps(
+    targets: &HashSet<Target>,
+    std: bool,
+    toolchain_name: &str,
+    nightly_version: &str,
+    export_file: &Path,
+) -> String {
+    let mut targets: Vec<&Target> = targets.iter().collect();
+    targets.sort_by_key(|target| target.to_string());
+    let chips = |xtensa: bool| {
+        targets
+            .iter()
+            .filter(|target| target.is_xtensa() == xtensa)
+            .map(|target| target.to_string())
+            .collect::<Vec<_>>()
+    };
+    let (xtensa_chips, riscv_chips) = (chips(true), chips(false));
+
+    let mut steps = vec![if cfg!(windows) {
+        format!(
+            "Open a new terminal so the updated environment variables are loaded (they are also listed in '{}')",
+            export_file.display()
+        )
+    } else {
+        format!(
+            "Set up the environment of every new terminal with: '. {}'",
+            export_file.display()
+        )
+    }];
+
+    let template = if std {
+        "esp-rs/esp-idf-template cargo"
+    } else {
+        "esp-rs/esp-template"
+    };
+    steps.push(format!(
+        "Generate a project with 'cargo generate {template}' and select one of the installed chips: {}",
+        [xtensa_chips.as_slice(), riscv_chips.as_slice()]
+            .concat()
+            .join(", ")
+    ));
+
+    steps.push(
+        "Install the flashing tool, if you haven't already, with 'cargo install espflash cargo-espflash'"
+            .to_string(),
+    );
+    let uart_chips: Vec<String> = targets
+        .iter()
+        .filter(|target| !target.has_usb_jtag())
+        .map(|target| target.to_string())
+        .collect();
+    if uart_chips.is_empty() {
+        steps.push(
+            "Connect the board through the USB port of the chip, no serial driver is required, and debug it with probe-rs ('espup install --probe-rs')"
+                .to_string(),
+        );
+    } else {
+        steps.push(format!(
+            "{} boards are usually connected through a USB-to-UART bridge, install its driver (e.g. CP210x or CH34x) if the serial port does not show up",
+            uart_chips.join(", ")
+        ));
+    }
+    if !xtensa_chips.is_empty() {
+        steps.push(format!(
+            "Build and flash {} applications with 'cargo +{toolchain_name} espflash flash --release --monitor'",
+            xtensa_chips.join(", ")
+        ));
+    }
+    if !riscv_chips.is_empty() {
+        steps.push(format!(
+            "Build and flash {} applications with 'cargo +{nightly_version} espflash flash --release --monitor'",
+            riscv_chips.join(", ")
+        ));
+    }
+
+    let mut guide = String::from("Next steps:\n");
+    for (i, step) in steps.iter().enumerate() {
+        guide.push_str(&format!("\t{}. {step}\n", i + 1));
+    }
+    guide.push_str("\tSee https://esp-rs.github.io/book/ for more information.");
+    guide
+}
+
+/// Tip of a chip, only given to the `std` or `no_std` installations when `std` is set.
+struct ChipTip {
+    target: Target,
+    std: Option<bool>,
+    tip: &'static str,
+}
+
+/// Knowledge base of the first-build pitfalls of each chip.
+const CHIP_TIPS: &[ChipTip] = &[
+    ChipTip {
+        target: Target::ESP32,
+        std: None,
+        tip: "Boards without an auto-reset circuit only enter the download mode while the BOOT button is held, press it when espflash prints 'Connecting...'",
+    },
+    ChipTip {
+        target: Target::ESP32C2,
+        std: None,
+        tip: "Most modules use a 26 MHz crystal, the bootloader logs are then printed at 74880 baud, monitor them with '--monitor-baud 74880'",
+    },
+    ChipTip {
+        target: Target::ESP32C3,
+        std: None,
+        tip: "Boards connected through a USB-to-UART bridge monitor at the baud rate of the application, pass it with '--monitor-baud' (e.g. '--monitor-baud 115200') if the output is garbled",
+    },
+    ChipTip {
+        target: Target::ESP32C6,
+        std: None,
+        tip: "DevKits have two USB ports, use the one labeled 'USB' for the USB-Serial-JTAG and probe-rs, the 'UART' one goes through the bridge",
+    },
+    ChipTip {
+        target: Target::ESP32H2,
+        std: None,
+        tip: "The chip has no Wi-Fi, its radio only speaks IEEE 802.15.4 (Thread, Zigbee) and Bluetooth LE",
+    },
+    ChipTip {
+        target: Target::ESP32P4,
+        std: None,
+        tip: "The chip has no radio, boards pair it with a companion chip (usually an ESP32-C6) for Wi-Fi and Bluetooth",
+    },
+    ChipTip {
+        target: Target::ESP32S2,
+        std: None,
+        tip: "Flashing through the native USB port resets it, hold BOOT and press RESET if the port disappears, then flash again",
+    },
+    ChipTip {
+        target: Target::ESP32S3,
+        std: Some(true),
+        tip: "Modules with octal PSRAM (e.g. 'N8R8' or 'N16R8') need 'CONFIG_SPIRAM=y' and 'CONFIG_SPIRAM_MODE_OCT=y' in the 'sdkconfig.defaults' of the project",
+    },
+    ChipTip {
+        target: Target::ESP32S3,
+        std: Some(false),
+        tip: "Modules with octal PSRAM (e.g. 'N8R8' or 'N16R8') need the 'octal-psram' feature of 'esp-hal', the quad PSRAM driver does not detect them",
+    },
+];
+
+/// Returns the tips of the installed chips, if there are any.
+pub fn chip_tips(targets: &HashSet<Target>, std: bool) -> Option<String> {
+    let mut tips: Vec<&ChipTip> = CHIP_TIPS
+        .iter()
+        .filter(|tip| targets.contains(&tip.target) && tip.std.unwrap_or(std) == std)
+        .collect();
+    if tips.is_empty() {
+        return None;
+    }
+    tips.sort_by_key(|tip| tip.target.to_string());
+
+    let mut text = String::from("Tips for the installed chips:\n");
+    for tip in tips {
+        text.push_str(&format!("\t- {}: {}\n", tip.target, tip.tip));
+    }
+    text.push_str("\tDisable them with '--no-tips'.");
+    Some(text)
+}
+
+/// Returns a `.cargo/config.toml` fragment to link the RISC-V targets with `rust-lld`.
+pub fn rust_lld_config(targets: &HashSet<Target>) -> Option<String> {
+    let mut rust_targets: Vec<&str> = targets
+        .iter()
+        .filter(|target| target.is_riscv())
+        .map(|target| target.rust_target(false))
+        .collect();
+    if rust_targets.is_empty() {
+        return None;
+    }
+    rust_targets.sort_unstable();
+    rust_targets.dedup();
+
+    let config: Vec<String> = rust_targets
+        .iter()
+        .map(|rust_target| {
+            format!(
+                "[target.{rust_target}]\nlinker = \"rust-lld\"\nrustflags = [\"-C\", \"link-arg=-Tlinkall.x\"]\n"
+            )
+        })
+        .collect();
+    Some(config.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        guide::{chip_tips, next_steps, rust_lld_config},
+        targets::Target,
+    };
+    use std::{collections::HashSet, path::Path};
+
+    #[test]
+    fn test_next_steps() {
+        let targets: HashSet<Target> = [Target::ESP32S3, Target::ESP32C3, Target::ESP32]
+            .into_iter()
+            .collect();
+        let guide = next_steps(
+            &targets,
+            false,
+            "esp",
+            "nightly",
+            Path::new("/home/user/export-esp.sh"),
+        );
+        assert!(guide.contains("'cargo generate esp-rs/esp-template'"));
+        assert!(guide.contains("installed chips: esp32, esp32s3, esp32c3"));
+        assert!(guide.contains(
+            "Build and flash esp32, esp32s3 applications with 'cargo +esp espflash flash --release --monitor'"
+        ));
+        assert!(guide.contains(
+            "Build and flash esp32c3 applications with 'cargo +nightly espflash flash --release --monitor'"
+        ));
+        assert!(guide.contains("esp32 boards are usually connected through a USB-to-UART bridge"));
+
+        let targets: HashSet<Target> = [Target::ESP32C6].into_iter().collect();
+        let guide = next_steps(
+            &targets,
+            true,
+            "esp",
+            "nightly",
+            Path::new("/home/user/export-esp.sh"),
+        );
+        assert!(guide.contains("'cargo generate esp-rs/esp-idf-template cargo'"));
+        assert!(!guide.contains("cargo +esp"));
+        assert!(guide.contains("no serial driver is required"));
+        assert!(!guide.contains("USB-to-UART"));
+    }
+
+    #[test]
+    fn test_chip_tips() {
+        let targets: HashSet<Target> = [Target::ESP32S3, Target::ESP32C3].into_iter().collect();
+        let tips = chip_tips(&targets, true).unwrap();
+        assert!(tips.contains("esp32c3: Boards connected through a USB-to-UART bridge"));
+        assert!(tips.contains("CONFIG_SPIRAM_MODE_OCT=y"));
+        assert!(!tips.contains("'octal-psram' feature"));
+        // The tips are sorted by chip
+        assert!(tips.find("esp32c3:").unwrap() < tips.find("esp32s3:").unwrap());
+
+        let tips = chip_tips(&targets, false).unwrap();
+        assert!(tips.contains("'octal-psram' feature"));
+        assert!(!tips.contains("CONFIG_SPIRAM_MODE_OCT=y"));
+        assert!(chip_tips(&HashSet::new(), false).is_none());
+    }
+
+    #[test]
+    fn test_rust_lld_config() {
+        let targets: HashSet<Target> = [Target::ESP32C6, Target::ESP32H2, Target::ESP32C3]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            rust_lld_config(&targets).unwrap(),
+            "[target.riscv32imac-unknown-none-elf]
+linker = \"rust-lld\"
+rustflags = [\"-C\", \"link-arg=-Tlinkall.x\"]
+
+[target.riscv32imc-unknown-none-elf]
+linker = \"rust-lld\"
+rustflags = [\"-C\", \"link-arg=-Tlinkall.x\"]
+"
+        );
+        let targets: HashSet<Target> = [Target::ESP32].into_iter().collect();
+        assert!(rust_lld_config(&targets).is_none());
+    }
+}
@@ -0,0 +1,229 @@
+//! Detection of the host environment, used for compatibility reports and support requests.
+
+use crate::host_triple::missing_components;
+use guess_host_triple::guess_host_triple;
+use std::{
+    env,
+    fmt::{self, Display},
+    fs::read_to_string,
+    path::PathBuf,
+    process::Command,
+};
+
+/// Oldest glibc supported by the released espup binaries and the downloaded toolchains, built on Ubuntu 20.04.
+pub const MIN_GLIBC_VERSION: (u32, u32) = (2, 31);
+
+/// Shells espup can generate export files or completions for.
+const SHELLS: [&str; 5] = ["bash", "zsh", "fish", "pwsh", "powershell"];
+
+/// Components espup installs.
+const COMPONENTS: [&str; 3] = ["Xtensa Rust", "LLVM", "GCC"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostInfo {
+    /// Operating system, using the `std::env::consts::OS` names.
+    pub os: String,
+    /// Name of the distribution or OS release, if known.
+    pub os_release: Option<String>,
+    /// Architecture, using the `std::env::consts::ARCH` names.
+    pub arch: String,
+    /// Detected host triple.
+    pub host_triple: String,
+    /// C library provided by the system.
+    pub libc: String,
+    /// C library espup has been linked against.
+    pub espup_libc: &'static str,
+    /// Shell espup has been launched from.
+    pub current_shell: Option<String>,
+    /// Supported shells available in the `PATH`.
+    pub shells: Vec<String>,
+}
+
+impl HostInfo {
+    /// Detects the information of the running host.
+    pub fn detect() -> Self {
+        let libc = match env::consts::OS {
+            "linux" => match get_glibc_version() {
+                Some((major, minor)) => format!("glibc {major}.{minor}"),
+                None if is_musl() => "musl".to_string(),
+                None => "unknown".to_string(),
+            },
+            "windows" => "msvcrt".to_string(),
+            _ => "system".to_string(),
+        };
+        let current_shell = env::var("SHELL")
+            .ok()
+            .and_then(|shell| shell.rsplit('/').next().map(str::to_string));
+        let shells = SHELLS
+            .iter()
+            .filter(|shell| find_executable(shell).is_some())
+            .map(|shell| shell.to_string())
+            .collect();
+
+        Self {
+            os: env::consts::OS.to_string(),
+            os_release: read_to_string("/etc/os-release")
+                .ok()
+                .and_then(|contents| parse_os_release(&contents)),
+            arch: env::consts::ARCH.to_string(),
+            host_triple: guess_host_triple().unwrap_or("unknown").to_string(),
+            libc,
+            espup_libc: espup_libc(),
+            current_shell,
+            shells,
+        }
+    }
+
+    /// Components with published artifacts for the host.
+    pub fn supported_components(&self) -> Vec<&'static str> {
+        let missing = missing_components(&self.host_triple);
+        COMPONENTS
+            .into_iter()
+            .filter(|component| !missing.contains(component))
+            .collect()
+    }
+}
+
+impl Display for HostInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.os_release {
+            Some(release) => writeln!(f, "OS:          {} ({release})", self.os)?,
+            None => writeln!(f, "OS:          {}", self.os)?,
+        }
+        writeln!(f, "Arch:        {}", self.arch)?;
+        writeln!(f, "Host triple: {}", self.host_triple)?;
+        writeln!(f, "libc:        {}", self.libc)?;
+        writeln!(f, "espup libc:  {}", self.espup_libc)?;
+        writeln!(
+            f,
+            "Shell:       {}",
+            self.current_shell.as_deref().unwrap_or("unknown")
+        )?;
+        writeln!(f, "Shells:      {}", self.shells.join(", "))?;
+        let supported = self.supported_components();
+        write!(
+            f,
+            "Artifacts:   {}",
+            if supported.is_empty() {
+                "none".to_string()
+            } else {
+                supported.join(", ")
+            }
+        )
+    }
+}
+
+/// C library espup has been linked against.
+fn espup_libc() -> &'static str {
+    if cfg!(target_env = "musl") {
+        "musl (static)"
+    } else if cfg!(all(target_os = "linux", target_env = "gnu")) {
+        "glibc"
+    } else {
+        "system"
+    }
+}
+
+/// Finds an executable in the `PATH`.
+pub fn find_executable(executable: &str) -> Option<PathBuf> {
+    let file_name = format!("{executable}{}", env::consts::EXE_SUFFIX);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Checks if the system uses musl as its C library.
+fn is_musl() -> bool {
+    std::fs::read_dir("/lib")
+        .map(|entries| {
+            entries
+                .flatten()
+                .any(|entry| entry.file_name().to_string_lossy().starts_with("ld-musl-"))
+        })
+        .unwrap_or(false)
+}
+
+/// Parses the `PRETTY_NAME` of an `os-release` file.
+fn parse_os_release(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|name| name.trim_matches('"').to_string())
+}
+
+/// Parses the output of `getconf GNU_LIBC_VERSION` (e.g. `glibc 2.35`).
+fn parse_glibc_version(output: &str) -> Option<(u32, u32)> {
+    let version = output.split_whitespace().last()?;
+    let mut numbers = version.split('.');
+    let major = numbers.next()?.parse().ok()?;
+    let minor = numbers.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Gets the glibc version of the system, if it uses glibc.
+fn get_glibc_version() -> Option<(u32, u32)> {
+    let output = Command::new("getconf")
+        .arg("GNU_LIBC_VERSION")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_glibc_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Explains the glibc incompatibilities between the downloaded artifacts and the system.
+fn glibc_report(glibc_version: Option<(u32, u32)>) -> Option<String> {
+    let (min_major, min_minor) = MIN_GLIBC_VERSION;
+    match glibc_version {
+        Some(version) if version >= MIN_GLIBC_VERSION => None,
+        Some((major, minor)) => Some(format!(
+            "This system provides glibc {major}.{minor}, but the espup release binaries and the downloaded toolchains require glibc {min_major}.{min_minor} or newer. \
+            Consider upgrading the distribution or using a container with a newer one (e.g. the espressif/idf-rust image)"
+        )),
+        None => Some(format!(
+            "This system does not seem to use glibc, but the downloaded toolchains require glibc {min_major}.{min_minor} or newer. \
+            Consider installing a glibc compatibility layer (e.g. 'gcompat' on Alpine) or using a glibc based container (e.g. the espressif/idf-rust image)"
+        )),
+    }
+}
+
+/// Returns a compatibility report if the system C library can't run the downloaded toolchains.
+pub fn check_libc_compatibility() -> Option<String> {
+    if env::consts::OS != "linux" {
+        return None;
+    }
+    glibc_report(get_glibc_version())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::host_info::{glibc_report, parse_glibc_version, parse_os_release};
+
+    #[test]
+    fn test_parse_glibc_version() {
+        assert_eq!(parse_glibc_version("glibc 2.35\n"), Some((2, 35)));
+        assert_eq!(parse_glibc_version("glibc 2.17"), Some((2, 17)));
+        assert_eq!(parse_glibc_version(""), None);
+        assert_eq!(parse_glibc_version("glibc"), None);
+    }
+
+    #[test]
+    fn test_parse_os_release() {
+        let contents =
+            "NAME=\"Ubuntu\"\nVERSION_ID=\"22.04\"\nPRETTY_NAME=\"Ubuntu 22.04.3 LTS\"\n";
+        assert_eq!(
+            parse_os_release(contents),
+            Some("Ubuntu 22.04.3 LTS".to_string())
+        );
+        assert_eq!(parse_os_release("NAME=Alpine"), None);
+    }
+
+    #[test]
+    fn test_glibc_report() {
+        assert!(glibc_report(Some((2, 35))).is_none());
+        assert!(glibc_report(Some((2, 31))).is_none());
+        assert!(glibc_report(Some((2, 17))).unwrap().contains("2.17"));
+        assert!(glibc_report(None).unwrap().contains("gcompat"));
+    }
+}
@@ -3,7 +3,7 @@
 use crate::error::Error;
 use guess_host_triple::guess_host_triple;
 use miette::Result;
-use std::str::FromStr;
+use std::{env, str::FromStr};
 use strum::{Display, EnumString};
 
 #[derive(Display, Debug, Clone, EnumString, Default)]
@@ -29,20 +29,58 @@ pub enum HostTriple {
     Aarch64AppleDarwin,
 }
 
+/// Unsupported hosts for which GCC artifacts are published.
+const GCC_ONLY_HOSTS: [&str; 5] = [
+    "i686-unknown-linux-gnu",
+    "arm-unknown-linux-gnueabi",
+    "armv7-unknown-linux-gnueabihf",
+    "i686-pc-windows-msvc",
+    "i686-pc-windows-gnu",
+];
+
+/// Returns the components without published artifacts for the host triple.
+pub fn missing_components(host_triple: &str) -> Vec<&'static str> {
+    if HostTriple::from_str(host_triple).is_ok() {
+        vec![]
+    } else if GCC_ONLY_HOSTS.contains(&host_triple) {
+        vec!["Xtensa Rust", "LLVM"]
+    } else {
+        vec!["Xtensa Rust", "LLVM", "GCC"]
+    }
+}
+
+/// Builds a host triple from the compilation target when it can not be guessed.
+fn fallback_host_triple() -> String {
+    format!("{}-unknown-{}", env::consts::ARCH, env::consts::OS)
+}
+
 /// Parse the host triple if specified, otherwise guess it.
 pub fn get_host_triple(host_triple_arg: Option<String>) -> Result<HostTriple, Error> {
-    let host_triple = if let Some(host_triple) = &host_triple_arg {
+    let host_triple = if let Some(host_triple) = host_triple_arg {
         host_triple
     } else {
-        guess_host_triple().unwrap()
+        guess_host_triple()
+            .map(str::to_string)
+            .unwrap_or_else(fallback_host_triple)
     };
 
-    HostTriple::from_str(host_triple).map_err(|_| Error::UnsupportedHostTriple(host_triple.into()))
+    HostTriple::from_str(&host_triple).map_err(|_| {
+        let missing = missing_components(&host_triple);
+        let reason = if missing.len() == 3 {
+            format!("there are no {} artifacts for it", missing.join(", "))
+        } else {
+            format!(
+                "there are no {} artifacts for it, only GCC is available",
+                missing.join(", ")
+            )
+        };
+        Error::UnsupportedHostTriple(host_triple, reason)
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::host_triple::{get_host_triple, HostTriple};
+    use crate::host_triple::{get_host_triple, missing_components, HostTriple};
 
     #[test]
     fn test_get_host_triple() {
@@ -72,6 +110,15 @@ mod tests {
         ));
 
         assert!(get_host_triple(Some("some-fake-triple".to_string())).is_err());
+        assert!(missing_components("x86_64-unknown-linux-gnu").is_empty());
+        assert_eq!(
+            missing_components("i686-unknown-linux-gnu"),
+            vec!["Xtensa Rust", "LLVM"]
+        );
+        assert_eq!(
+            missing_components("x86_64-unknown-freebsd"),
+            vec!["Xtensa Rust", "LLVM", "GCC"]
+        );
 
         // Guessed Host Triples
         #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
@@ -0,0 +1,194 @@
+//! Trust store of the public keys the downloaded artifacts are verified against.
+
+use crate::{error::Error, paths::get_espup_home};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{debug, warn};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use std::{
+    fs::{create_dir_all, read_dir, read_to_string, remove_file, write},
+    path::{Path, PathBuf},
+};
+
+/// Name of the directory, under the espup home, storing the trusted keys.
+const KEYS_DIR: &str = "keys";
+/// Extension of the trusted key files.
+const KEY_EXTENSION: &str = "pub";
+/// Extension of the detached signatures, appended to the URL of the artifacts.
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// Ed25519 public key trusted to sign the artifacts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedKey {
+    /// Name the key was added under.
+    pub name: String,
+    /// Raw public key.
+    pub key: Vec<u8>,
+}
+
+impl TrustedKey {
+    /// Parses a base64 encoded Ed25519 public key.
+    pub fn parse(name: &str, contents: &str) -> Result<Self, Error> {
+        let key = STANDARD
+            .decode(contents.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| Error::InvalidKey(name.to_string()))?;
+        Ok(Self {
+            name: name.to_string(),
+            key,
+        })
+    }
+
+    /// Returns the key encoded in base64, as stored in the trust store.
+    pub fn encoded(&self) -> String {
+        STANDARD.encode(&self.key)
+    }
+}
+
+/// Gets the directory storing the trusted keys.
+pub fn get_keys_dir() -> PathBuf {
+    get_espup_home().join(KEYS_DIR)
+}
+
+/// Returns true if the name can be used as the file name of a key, without leaving the trust store.
+fn is_valid_key_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Gets the file of a trusted key.
+fn get_key_file(name: &str) -> Result<PathBuf, Error> {
+    if !is_valid_key_name(name) {
+        return Err(Error::InvalidKeyName(name.to_string()));
+    }
+    Ok(get_keys_dir().join(format!("{name}.{KEY_EXTENSION}")))
+}
+
+/// Adds the key to the trust store, replacing a key of the same name.
+pub fn add_key(key: &TrustedKey) -> Result<(), Error> {
+    let keys_dir = get_keys_dir();
+    create_dir_all(&keys_dir)
+        .map_err(|_| Error::CreateDirectory(keys_dir.display().to_string()))?;
+    write(get_key_file(&key.name)?, format!("{}\n", key.encoded()))?;
+    Ok(())
+}
+
+/// Removes the key from the trust store.
+pub fn remove_key(name: &str) -> Result<(), Error> {
+    let key_file = get_key_file(name)?;
+    if !key_file.exists() {
+        return Err(Error::MissingKey(name.to_string()));
+    }
+    remove_file(key_file)?;
+    Ok(())
+}
+
+/// Returns the keys of the trust store, sorted by name.
+pub fn list_keys() -> Result<Vec<TrustedKey>, Error> {
+    list_keys_in(&get_keys_dir())
+}
+
+/// Returns the keys of the trust store in the directory, sorted by name.
+///
+/// Malformed key files are skipped with a warning, so one of them does not prevent verifying
+/// the artifacts against the other keys.
+pub fn list_keys_in(keys_dir: &Path) -> Result<Vec<TrustedKey>, Error> {
+    if !keys_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut keys = Vec::new();
+    for entry in read_dir(keys_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some(KEY_EXTENSION) {
+            continue;
+        }
+        let key = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .filter(|name| is_valid_key_name(name))
+            .and_then(|name| TrustedKey::parse(name, &read_to_string(&path).ok()?).ok());
+        match key {
+            Some(key) => keys.push(key),
+            None => warn!("Skipping the malformed key file '{}'", path.display()),
+        }
+    }
+    keys.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(keys)
+}
+
+/// Verifies the detached signature, raw or base64 encoded, of an artifact against the trusted keys.
+pub fn verify_signature(
+    file_name: &str,
+    contents: &[u8],
+    signature: &[u8],
+    keys: &[TrustedKey],
+) -> Result<(), Error> {
+    let signature = match signature.len() {
+        64 => signature.to_vec(),
+        _ => STANDARD
+            .decode(String::from_utf8_lossy(signature).trim())
+            .map_err(|_| Error::InvalidSignature(file_name.to_string()))?,
+    };
+    let key = keys
+        .iter()
+        .find(|key| {
+            UnparsedPublicKey::new(&ED25519, &key.key)
+                .verify(contents, &signature)
+                .is_ok()
+        })
+        .ok_or_else(|| Error::InvalidSignature(file_name.to_string()))?;
+    debug!(
+        "Verified the signature of '{}' with '{}'",
+        file_name, key.name
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keys::{get_key_file, list_keys_in, verify_signature, TrustedKey};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+    use std::fs::write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify_signature() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let key = TrustedKey::parse("espressif", &STANDARD.encode(key_pair.public_key())).unwrap();
+        let signature = key_pair.sign(b"llvm");
+        let keys = [key];
+
+        assert!(verify_signature("llvm.tar.xz", b"llvm", signature.as_ref(), &keys).is_ok());
+        let encoded = STANDARD.encode(signature.as_ref());
+        assert!(verify_signature("llvm.tar.xz", b"llvm", encoded.as_bytes(), &keys).is_ok());
+        assert!(verify_signature("llvm.tar.xz", b"gcc", signature.as_ref(), &keys).is_err());
+        assert!(verify_signature("llvm.tar.xz", b"llvm", signature.as_ref(), &[]).is_err());
+        assert!(TrustedKey::parse("short", "c2hvcnQ=").is_err());
+    }
+
+    #[test]
+    fn test_list_keys() {
+        let keys_dir = TempDir::new().unwrap();
+        let key = STANDARD.encode([7; 32]);
+        write(keys_dir.path().join("espressif.pub"), &key).unwrap();
+        write(keys_dir.path().join("bad key.pub"), &key).unwrap();
+        write(keys_dir.path().join("truncated.pub"), "c2hvcnQ=").unwrap();
+        write(keys_dir.path().join("README"), "").unwrap();
+
+        // The malformed key files are skipped
+        let keys = list_keys_in(keys_dir.path()).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].name, "espressif");
+
+        assert!(get_key_file("esp-rs_2").is_ok());
+        assert!(get_key_file("../espressif").is_err());
+        assert!(get_key_file("").is_err());
+    }
+}
@@ -1,44 +1,58 @@
+//! Installation of the Rust ecosystem for Espressif SoCs.
+//!
+//! Besides the `espup` binary, tools like IDE plugins or provisioning scripts can embed the
+//! installation through [`toolchain::install`], [`toolchain::update`] and
+//! [`toolchain::uninstall`], which return the installed paths and environment:
+//!
+//! ```no_run
+//! use espup::{
+//!     targets::parse_targets,
+//!     toolchain::{install, options::InstallOptions, InstallMode},
+//! };
+//!
+//! # async fn example() -> miette::Result<()> {
+//! let args = InstallOptions {
+//!     targets: parse_targets("esp32c3")?,
+//!     ..Default::default()
+//! };
+//! let installation = install(args, InstallMode::Install).await?;
+//! for (name, value) in &installation.environment.variables {
+//!     println!("{name}={value}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod activate;
+#[cfg(feature = "cli")]
+pub mod app;
+pub mod backup;
+pub mod clean;
+#[cfg(feature = "cli")]
 pub mod cli;
+#[cfg(feature = "cli")]
+pub mod completions;
+pub mod config;
+pub mod diff;
+pub mod doctor;
 pub mod env;
 pub mod error;
+pub mod esp_idf;
+pub mod explain;
+pub mod gc;
+pub mod guide;
+pub mod host_info;
 pub mod host_triple;
+pub mod keys;
+pub mod logging;
+pub mod paths;
+pub mod permissions;
+pub mod profile;
+pub mod project;
+pub mod shell;
+pub mod state;
 pub mod targets;
+pub mod tasks;
 pub mod toolchain;
-
-pub mod logging {
-    use env_logger::{Builder, Env, WriteStyle};
-
-    /// Initializes the logger
-    pub fn initialize_logger(log_level: &str) {
-        Builder::from_env(Env::default().default_filter_or(log_level))
-            .format(|buf, record| {
-                use std::io::Write;
-                writeln!(
-                    buf,
-                    "[{}]: {}",
-                    record.level().to_string().to_lowercase(),
-                    record.args()
-                )
-            })
-            .write_style(WriteStyle::Always)
-            .init();
-    }
-}
-
-pub mod update {
-    use log::warn;
-    use std::time::Duration;
-    use update_informer::{registry, Check};
-
-    /// Check crates.io for a new version of the application
-    pub fn check_for_update(name: &str, version: &str) {
-        // By setting the interval to 0 seconds we invalidate the cache with each
-        // invocation and ensure we're getting up-to-date results
-        let informer =
-            update_informer::new(registry::Crates, name, version).interval(Duration::ZERO);
-
-        if let Some(version) = informer.check_version().ok().flatten() {
-            warn!("A new version of {name} ('{version}') is available");
-        }
-    }
-}
+pub mod update;
+pub mod versions;
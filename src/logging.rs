@@ -0,0 +1,99 @@
+//! Logging of the espup events.
+
+use env_logger::{Builder, Env, Logger, WriteStyle};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{fs::File, io::Write, path::Path, sync::Mutex};
+
+/// Warnings logged by espup, reported in the JSON output.
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Returns the warnings logged by espup since the last call.
+pub fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut WARNINGS.lock().unwrap())
+}
+
+/// Logs to the console and, optionally, writes the espup events to a file as JSON lines.
+struct EventLogger {
+    console: Logger,
+    events: Option<Mutex<File>>,
+}
+
+impl EventLogger {
+    /// Returns true if the record is an espup event to write to the events file.
+    fn is_event(&self, metadata: &Metadata) -> bool {
+        self.events.is_some()
+            && metadata.level() <= Level::Info
+            && metadata.target().starts_with(env!("CARGO_PKG_NAME"))
+    }
+
+    /// Returns true if the record is an espup warning, recorded for the JSON output.
+    fn is_warning(metadata: &Metadata) -> bool {
+        metadata.level() == Level::Warn && metadata.target().starts_with(env!("CARGO_PKG_NAME"))
+    }
+}
+
+impl Log for EventLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console.enabled(metadata) || self.is_event(metadata) || Self::is_warning(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.console.matches(record) {
+            self.console.log(record);
+        }
+        if Self::is_warning(record.metadata()) {
+            WARNINGS.lock().unwrap().push(record.args().to_string());
+        }
+        if let Some(events) = self
+            .events
+            .as_ref()
+            .filter(|_| self.is_event(record.metadata()))
+        {
+            let event = serde_json::json!({
+                "level": record.level().to_string().to_lowercase(),
+                "message": record.args().to_string(),
+            });
+            let _ = writeln!(events.lock().unwrap(), "{event}");
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        if let Some(events) = &self.events {
+            let _ = events.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Initializes the logger
+pub fn initialize_logger(log_level: &str) {
+    initialize_logger_with_events(log_level, None).unwrap();
+}
+
+/// Initializes the logger, also writing the espup events to the file, if provided.
+pub fn initialize_logger_with_events(
+    log_level: &str,
+    events_file: Option<&Path>,
+) -> std::io::Result<()> {
+    let console = Builder::from_env(Env::default().default_filter_or(log_level))
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{}]: {}",
+                record.level().to_string().to_lowercase(),
+                record.args()
+            )
+        })
+        .write_style(WriteStyle::Always)
+        .build();
+    let events = events_file.map(File::create).transpose()?.map(Mutex::new);
+    let max_level = if events.is_some() {
+        console.filter().max(LevelFilter::Info)
+    } else {
+        console.filter().max(LevelFilter::Warn)
+    };
+
+    log::set_boxed_logger(Box::new(EventLogger { console, events })).unwrap();
+    log::set_max_level(max_level);
+    Ok(())
+}
@@ -0,0 +1,207 @@
+//! Resolution of the home directories used by espup, rustup and cargo.
+
+use directories::BaseDirs;
+use log::debug;
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// Gets the home directory of the user.
+pub fn get_home_dir() -> PathBuf {
+    BaseDirs::new().unwrap().home_dir().to_path_buf()
+}
+
+/// Resolves a home directory the way rustup does: an empty variable is ignored and a relative
+/// one is relative to the current directory.
+fn resolve_home(value: Option<OsString>, default_dir: &str) -> PathBuf {
+    match value.filter(|value| !value.is_empty()).map(PathBuf::from) {
+        Some(path) if path.is_relative() => env::current_dir()
+            .map(|current_dir| current_dir.join(&path))
+            .unwrap_or(path),
+        Some(path) => path,
+        None => get_home_dir().join(default_dir),
+    }
+}
+
+/// Gets the cargo home path, `$CARGO_HOME` or '~/.cargo'.
+pub fn get_cargo_home() -> PathBuf {
+    resolve_home(env::var_os("CARGO_HOME"), ".cargo")
+}
+
+/// Gets the rustup home path, `$RUSTUP_HOME` or '~/.rustup'.
+pub fn get_rustup_home() -> PathBuf {
+    resolve_home(env::var_os("RUSTUP_HOME"), ".rustup")
+}
+
+/// Gets the rustup home path used when `$RUSTUP_HOME` is not set.
+pub fn get_default_rustup_home() -> PathBuf {
+    resolve_home(None, ".rustup")
+}
+
+/// Gets the directory where espup stores its files.
+pub fn get_espup_home() -> PathBuf {
+    get_home_dir().join(".espup")
+}
+
+/// Gets the directory where the ESP-IDF tools are installed, `$IDF_TOOLS_PATH` or '~/.espressif'.
+pub fn get_idf_tools_path() -> PathBuf {
+    env::var_os("IDF_TOOLS_PATH")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| get_home_dir().join(".espressif"))
+}
+
+/// Gets the directory where esp-idf-sys installs the ESP-IDF of the version (e.g. 'v5.1.2').
+pub fn get_esp_idf_dir(version: &str) -> PathBuf {
+    get_idf_tools_path().join("esp-idf").join(version)
+}
+
+/// Logs the resolved home directories, and the variables they come from.
+pub fn log_paths() {
+    debug!("Home directory: {}", get_home_dir().display());
+    for (var, path) in [
+        ("CARGO_HOME", get_cargo_home()),
+        ("RUSTUP_HOME", get_rustup_home()),
+    ] {
+        match env::var_os(var).filter(|value| !value.is_empty()) {
+            Some(value) => debug!(
+                "{var}: {} (set to '{}')",
+                path.display(),
+                value.to_string_lossy()
+            ),
+            None => debug!("{var}: {} (default)", path.display()),
+        }
+    }
+    debug!("espup home: {}", get_espup_home().display());
+    debug!("ESP-IDF tools: {}", get_idf_tools_path().display());
+}
+
+/// Variables the OneDrive client sets to the folders it syncs.
+const ONEDRIVE_VARIABLES: [&str; 3] = ["OneDrive", "OneDriveConsumer", "OneDriveCommercial"];
+
+/// Returns the file hosting service syncing the path, and the synced folder holding it.
+///
+/// The folders are the ones of the OneDrive variables, or the ones named after the service (e.g.
+/// 'OneDrive - Contoso' or 'Dropbox (Personal)').
+fn find_synced_folder(
+    path: &Path,
+    onedrive_folders: &[PathBuf],
+) -> Option<(&'static str, PathBuf)> {
+    if let Some(folder) = onedrive_folders
+        .iter()
+        .find(|folder| path.starts_with(folder))
+    {
+        return Some(("OneDrive", folder.clone()));
+    }
+    path.ancestors().find_map(|ancestor| {
+        let name = ancestor.file_name()?.to_string_lossy();
+        let service = if name == "OneDrive" || name.starts_with("OneDrive - ") {
+            "OneDrive"
+        } else if name == "Dropbox" || name.starts_with("Dropbox (") {
+            "Dropbox"
+        } else {
+            return None;
+        };
+        Some((service, ancestor.to_path_buf()))
+    })
+}
+
+/// Returns the warning to show when the path is synced by OneDrive or Dropbox, suggesting a root
+/// next to the synced folder instead.
+///
+/// Syncing the toolchains locks their files while they are installed and used, and uploads
+/// thousands of them.
+pub fn synced_folder_warning(path: &Path) -> Option<String> {
+    let onedrive_folders: Vec<PathBuf> = ONEDRIVE_VARIABLES
+        .iter()
+        .filter_map(env::var_os)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    let (service, folder) = find_synced_folder(path, &onedrive_folders)?;
+    let root = folder.parent().unwrap_or(&folder);
+    Some(format!(
+        "'{}' is synced by {service}, which locks the files of the toolchains and syncs thousands of them. Install them outside of '{}', setting RUSTUP_HOME to '{}' and CARGO_HOME to '{}', or using '--toolchain-destination'",
+        path.display(),
+        folder.display(),
+        root.join(".rustup").display(),
+        root.join(".cargo").display()
+    ))
+}
+
+/// Returns true if the directory is one of the entries of the `PATH`.
+pub fn path_contains(dir: &Path) -> bool {
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).any(|entry| entry == dir))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paths::{
+        find_synced_folder, get_cargo_home, get_home_dir, get_rustup_home, resolve_home,
+    };
+    use std::{env, ffi::OsString, path::PathBuf};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_get_cargo_home() {
+        // No CARGO_HOME set
+        env::remove_var("CARGO_HOME");
+        assert_eq!(get_cargo_home(), get_home_dir().join(".cargo"));
+        // CARGO_HOME set
+        let temp_dir = TempDir::new().unwrap();
+        let cargo_home = temp_dir.path().to_path_buf();
+        env::set_var("CARGO_HOME", cargo_home.to_str().unwrap());
+        assert_eq!(get_cargo_home(), cargo_home);
+    }
+
+    #[test]
+    fn test_get_rustup_home() {
+        // No RUSTUP_HOME set
+        env::remove_var("RUSTUP_HOME");
+        assert_eq!(get_rustup_home(), get_home_dir().join(".rustup"));
+        // RUSTUP_HOME set
+        let temp_dir = TempDir::new().unwrap();
+        let rustup_home = temp_dir.path().to_path_buf();
+        env::set_var("RUSTUP_HOME", rustup_home.to_str().unwrap());
+        assert_eq!(get_rustup_home(), rustup_home);
+    }
+
+    #[test]
+    fn test_find_synced_folder() {
+        let onedrive = vec![PathBuf::from("/home/user/Cloud")];
+        assert_eq!(
+            find_synced_folder(&onedrive[0].join(".rustup").join("toolchains"), &onedrive),
+            Some(("OneDrive", onedrive[0].clone()))
+        );
+        let onedrive = PathBuf::from("/home/user/OneDrive - Contoso");
+        assert_eq!(
+            find_synced_folder(&onedrive.join(".cargo"), &[]),
+            Some(("OneDrive", onedrive))
+        );
+        let dropbox = PathBuf::from("/home/user/Dropbox");
+        assert_eq!(
+            find_synced_folder(&dropbox.join("esp"), &[]),
+            Some(("Dropbox", dropbox))
+        );
+        assert_eq!(
+            find_synced_folder(&PathBuf::from("/home/user/.rustup/toolchains/esp"), &[]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_home() {
+        assert_eq!(
+            resolve_home(Some(OsString::new()), ".cargo"),
+            get_home_dir().join(".cargo")
+        );
+        assert_eq!(
+            resolve_home(Some(OsString::from("tools/cargo")), ".cargo"),
+            env::current_dir().unwrap().join("tools").join("cargo")
+        );
+    }
+}
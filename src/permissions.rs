@@ -0,0 +1,108 @@
+//! Permissions of the files installed by espup, which other users of the machine must not be able to modify.
+
+#[cfg(unix)]
+use std::{
+    fs::{read_dir, set_permissions, symlink_metadata, Metadata, Permissions},
+    os::unix::fs::PermissionsExt,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Permission bit allowing other users to write to a file or directory.
+#[cfg(unix)]
+const OTHERS_WRITE: u32 = 0o002;
+
+/// Returns true if other users can write to the file or directory, symlinks being ignored.
+#[cfg(unix)]
+fn is_world_writable(metadata: &Metadata) -> bool {
+    !metadata.file_type().is_symlink() && metadata.permissions().mode() & OTHERS_WRITE != 0
+}
+
+/// Calls the function with every file and directory under the directory, itself included, without following symlinks.
+#[cfg(unix)]
+fn walk(path: &Path, f: &mut impl FnMut(&Path, &Metadata) -> io::Result<()>) -> io::Result<()> {
+    let metadata = symlink_metadata(path)?;
+    f(path, &metadata)?;
+    if metadata.is_dir() {
+        for entry in read_dir(path)? {
+            walk(&entry?.path(), f)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the files and directories under the directory, itself included, that other users can write to.
+#[cfg(unix)]
+pub fn find_world_writable(dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let _ = walk(dir, &mut |path, metadata| {
+        if is_world_writable(metadata) {
+            found.push(path.to_path_buf());
+        }
+        Ok(())
+    });
+    found
+}
+
+/// Returns the files and directories under the directory, itself included, that other users can write to.
+#[cfg(not(unix))]
+pub fn find_world_writable(_dir: &Path) -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// Removes the permission of other users to write to the files and directories under the directory.
+///
+/// Returns the number of files and directories whose permissions were changed.
+#[cfg(unix)]
+pub fn restrict_permissions(dir: &Path) -> io::Result<usize> {
+    let mut restricted = 0;
+    if !dir.exists() {
+        return Ok(restricted);
+    }
+    walk(dir, &mut |path, metadata| {
+        if is_world_writable(metadata) {
+            let mode = metadata.permissions().mode() & !OTHERS_WRITE;
+            set_permissions(path, Permissions::from_mode(mode))?;
+            restricted += 1;
+        }
+        Ok(())
+    })?;
+    Ok(restricted)
+}
+
+/// Removes the permission of other users to write to the files and directories under the directory.
+///
+/// Returns the number of files and directories whose permissions were changed.
+#[cfg(not(unix))]
+pub fn restrict_permissions(_dir: &Path) -> io::Result<usize> {
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    #[test]
+    fn test_restrict_permissions() {
+        use crate::permissions::{find_world_writable, restrict_permissions};
+        use std::{
+            fs::{create_dir_all, metadata, set_permissions, write, Permissions},
+            os::unix::fs::{symlink, PermissionsExt},
+        };
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bin = temp_dir.path().join("esp").join("bin");
+        create_dir_all(&bin).unwrap();
+        write(bin.join("rustc"), "").unwrap();
+        set_permissions(&bin, Permissions::from_mode(0o777)).unwrap();
+        set_permissions(bin.join("rustc"), Permissions::from_mode(0o755)).unwrap();
+        symlink(bin.join("rustc"), bin.join("cargo")).unwrap();
+
+        assert_eq!(find_world_writable(temp_dir.path()), vec![bin.clone()]);
+        assert_eq!(restrict_permissions(temp_dir.path()).unwrap(), 1);
+        assert_eq!(metadata(&bin).unwrap().permissions().mode() & 0o777, 0o775);
+        assert!(find_world_writable(temp_dir.path()).is_empty());
+    }
+}
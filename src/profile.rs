@@ -0,0 +1,120 @@
+//! Installation profiles, shared settings for provisioning identical environments.
+
+use crate::{
+    error::Error,
+    targets::parse_targets,
+    toolchain::{
+        cache::sha256,
+        fetch::{default_fetcher, fetch_with_retry, retry_policy},
+        options::InstallOptions,
+    },
+};
+use log::info;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    /// Extends the LLVM installation.
+    pub extended_llvm: Option<bool>,
+    /// Extra crates to install.
+    pub extra_crates: Option<Vec<String>>,
+    /// Xtensa Rust toolchain name.
+    pub name: Option<String>,
+    /// Nightly Rust toolchain version.
+    pub nightly_version: Option<String>,
+    /// Only install toolchains required for STD applications.
+    pub std: Option<bool>,
+    /// Comma or space separated list of targets.
+    pub targets: Option<String>,
+    /// Xtensa Rust toolchain version.
+    pub toolchain_version: Option<String>,
+}
+
+impl Profile {
+    /// Parses a profile from its TOML representation.
+    pub fn parse(contents: &str) -> Result<Self, Error> {
+        toml::from_str(contents).map_err(|e| Error::InvalidProfile(e.message().to_string()))
+    }
+
+    /// Overrides the installation options with the settings of the profile.
+    pub fn apply(self, args: &mut InstallOptions) -> Result<(), Error> {
+        if let Some(extended_llvm) = self.extended_llvm {
+            args.extended_llvm = extended_llvm;
+        }
+        if let Some(extra_crates) = self.extra_crates {
+            args.extra_crates = extra_crates;
+        }
+        if let Some(name) = self.name {
+            args.name = name;
+        }
+        if let Some(nightly_version) = self.nightly_version {
+            args.nightly_version = nightly_version;
+        }
+        if let Some(std) = self.std {
+            args.std = std;
+        }
+        if let Some(targets) = self.targets {
+            args.targets = parse_targets(&targets)?;
+        }
+        if let Some(toolchain_version) = self.toolchain_version {
+            args.toolchain_version = Some(toolchain_version);
+        }
+        Ok(())
+    }
+}
+
+/// Verifies that the contents match the expected SHA-256.
+pub fn verify_sha256(contents: &str, expected: &str) -> Result<(), Error> {
+    let actual = sha256(contents.as_bytes())?;
+    if actual != expected.to_lowercase() {
+        return Err(Error::ProfileHashMismatch(expected.to_string(), actual));
+    }
+    Ok(())
+}
+
+/// Downloads a profile.
+pub async fn fetch_profile(url: &str) -> Result<String, Error> {
+    info!("Fetching installation profile from '{}'", url);
+    let bytes = fetch_with_retry(default_fetcher().as_ref(), url, &retry_policy()).await?;
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| Error::InvalidProfile("the profile is not valid UTF-8".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profile::{verify_sha256, Profile};
+
+    #[test]
+    fn test_parse_profile() {
+        let profile = Profile::parse(
+            r#"
+targets = "esp32,esp32c3"
+toolchain-version = "1.73.0.1"
+extra-crates = ["espflash", "cargo-generate"]
+std = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile,
+            Profile {
+                extra_crates: Some(vec!["espflash".to_string(), "cargo-generate".to_string()]),
+                std: Some(true),
+                targets: Some("esp32,esp32c3".to_string()),
+                toolchain_version: Some("1.73.0.1".to_string()),
+                ..Default::default()
+            }
+        );
+        assert!(Profile::parse("unknown = 1").is_err());
+        assert!(Profile::parse("std = \"yes\"").is_err());
+    }
+
+    #[test]
+    fn test_verify_sha256() {
+        let hash = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(verify_sha256("abc", hash).is_ok());
+        assert!(verify_sha256("abc", &hash.to_uppercase()).is_ok());
+        assert!(verify_sha256("abd", hash).is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! Detection of the settings of the project espup is run from.
+
+use crate::{
+    env::Environment, error::Error, state::ToolchainState, targets::parse_targets,
+    toolchain::options::InstallOptions,
+};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+/// Project manifest declaring the environment of the project.
+pub const PROJECT_MANIFEST: &str = "espup.toml";
+
+/// Toolchain files, in order of precedence, as rustup looks them up.
+const TOOLCHAIN_FILES: [&str; 2] = ["rust-toolchain", "rust-toolchain.toml"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ProjectManifest {
+    /// Xtensa Rust toolchain version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toolchain_version: Option<String>,
+    /// Comma or space separated list of targets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub targets: Option<String>,
+    /// ESP-IDF version used by esp-idf-sys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub esp_idf_version: Option<String>,
+    /// Nightly Rust toolchain version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nightly_version: Option<String>,
+    /// Only install toolchains required for STD applications.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub std: Option<bool>,
+    /// Extends the LLVM installation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extended_llvm: Option<bool>,
+    /// Extra crates to install.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_crates: Option<Vec<String>>,
+}
+
+impl ProjectManifest {
+    /// Parses a project manifest from its TOML representation.
+    pub fn parse(contents: &str, path: &Path) -> Result<Self, Error> {
+        toml::from_str(contents).map_err(|e| {
+            Error::InvalidProjectManifest(path.display().to_string(), e.message().to_string())
+        })
+    }
+
+    /// Returns the manifest of the project containing the directory, and its path.
+    pub fn find(dir: &Path) -> Result<(Self, PathBuf), Error> {
+        let dir = env::current_dir()?.join(dir);
+        let path = dir
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_MANIFEST))
+            .find(|path| path.is_file())
+            .ok_or_else(|| Error::MissingProjectManifest(dir.display().to_string()))?;
+        debug!("Found project manifest '{}'", path.display());
+        Ok((Self::parse(&read_to_string(&path)?, &path)?, path))
+    }
+
+    /// Reads the manifest file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = read_to_string(path)
+            .map_err(|_| Error::MissingEnvironmentFile(path.display().to_string()))?;
+        Self::parse(&contents, path)
+    }
+
+    /// Returns the manifest declaring the environment of the installed toolchain, as far as its
+    /// state recorded it.
+    pub fn freeze(toolchain_state: &ToolchainState) -> Self {
+        let esp_idf_version = Environment::from_exports(&toolchain_state.exports)
+            .variables
+            .into_iter()
+            .find(|(name, _)| name == "ESP_IDF_VERSION")
+            .map(|(_, version)| version);
+        Self {
+            toolchain_version: toolchain_state.xtensa_rust_version.clone(),
+            targets: (!toolchain_state.targets.is_empty())
+                .then(|| toolchain_state.targets.join(",")),
+            esp_idf_version,
+            nightly_version: toolchain_state.nightly_version.clone(),
+            std: toolchain_state.std.then_some(true),
+            extended_llvm: toolchain_state.extended_llvm.then_some(true),
+            extra_crates: (!toolchain_state.crates.is_empty())
+                .then(|| toolchain_state.crates.clone()),
+        }
+    }
+
+    /// Returns the TOML representation of the manifest.
+    pub fn to_toml(&self) -> String {
+        // The manifest only holds strings, booleans and arrays of strings
+        toml::to_string(self).unwrap()
+    }
+
+    /// Overrides the installation options with the pins of the manifest.
+    ///
+    /// The pinned options are marked explicit, so that the configuration file does not replace them.
+    pub fn apply(self, args: &mut InstallOptions) -> Result<(), Error> {
+        if let Some(esp_idf_version) = self.esp_idf_version {
+            args.esp_idf_version = Some(esp_idf_version);
+            args.set_explicit("esp_idf_version");
+        }
+        if let Some(extended_llvm) = self.extended_llvm {
+            args.extended_llvm = extended_llvm;
+            args.set_explicit("extended_llvm");
+        }
+        if let Some(extra_crates) = self.extra_crates {
+            args.extra_crates = extra_crates;
+            args.set_explicit("extra_crates");
+        }
+        if let Some(nightly_version) = self.nightly_version {
+            args.nightly_version = nightly_version;
+            args.set_explicit("nightly_version");
+        }
+        if let Some(std) = self.std {
+            args.std = std;
+            args.set_explicit("std");
+        }
+        if let Some(targets) = self.targets {
+            args.targets = parse_targets(&targets)?;
+            args.set_explicit("targets");
+        }
+        if let Some(toolchain_version) = self.toolchain_version {
+            args.toolchain_version = Some(toolchain_version);
+            args.set_explicit("toolchain_version");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolchainFile {
+    toolchain: ToolchainSection,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolchainSection {
+    channel: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectToolchain {
+    /// Toolchain file pinning the channel.
+    pub file: PathBuf,
+    /// Channel pinned by the project.
+    pub channel: String,
+}
+
+/// Parses the channel of a toolchain file, either in TOML or in the legacy single line format.
+fn parse_channel(contents: &str) -> Option<String> {
+    match toml::from_str::<ToolchainFile>(contents) {
+        Ok(toolchain_file) => toolchain_file.toolchain.channel,
+        Err(_) => {
+            let channel = contents.trim();
+            (!channel.is_empty() && !channel.contains(['\n', '[', '=']))
+                .then(|| channel.to_string())
+        }
+    }
+}
+
+/// Returns the toolchain pinned by the project containing the directory, if any.
+pub fn get_project_toolchain(dir: &Path) -> Option<ProjectToolchain> {
+    for dir in dir.ancestors() {
+        for file_name in TOOLCHAIN_FILES {
+            let file = dir.join(file_name);
+            let Ok(contents) = read_to_string(&file) else {
+                continue;
+            };
+            debug!("Found toolchain file '{}'", file.display());
+            return parse_channel(&contents).map(|channel| ProjectToolchain { file, channel });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::Config,
+        project::{
+            get_project_toolchain, parse_channel, ProjectManifest, ProjectToolchain,
+            PROJECT_MANIFEST,
+        },
+        state::ToolchainState,
+        targets::parse_targets,
+        toolchain::options::InstallOptions,
+    };
+    use std::{
+        fs::{create_dir_all, write},
+        path::Path,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_channel() {
+        assert_eq!(
+            parse_channel("[toolchain]\nchannel = \"esp\"\n"),
+            Some("esp".to_string())
+        );
+        assert_eq!(
+            parse_channel("[toolchain]\ncomponents = [\"rust-src\"]\n"),
+            None
+        );
+        assert_eq!(
+            parse_channel("nightly-2023-11-14\n"),
+            Some("nightly-2023-11-14".to_string())
+        );
+        assert_eq!(parse_channel("[toolchain\n"), None);
+    }
+
+    #[test]
+    fn test_get_project_toolchain() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_dir = temp_dir.path().join("project").join("src");
+        create_dir_all(&src_dir).unwrap();
+        let file = temp_dir.path().join("project").join("rust-toolchain.toml");
+        write(&file, "[toolchain]\nchannel = \"nightly-2023-11-14\"\n").unwrap();
+
+        assert_eq!(
+            get_project_toolchain(&src_dir),
+            Some(ProjectToolchain {
+                file,
+                channel: "nightly-2023-11-14".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_find_project_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_dir = temp_dir.path().join("project").join("src");
+        create_dir_all(&src_dir).unwrap();
+        assert!(ProjectManifest::find(&src_dir).is_err());
+
+        let file = temp_dir.path().join("project").join(PROJECT_MANIFEST);
+        write(
+            &file,
+            r#"
+toolchain-version = "1.74.0.0"
+targets = "esp32s3"
+esp-idf-version = "v5.1.2"
+"#,
+        )
+        .unwrap();
+        let (manifest, path) = ProjectManifest::find(&src_dir).unwrap();
+        assert_eq!(path, file);
+        assert_eq!(
+            manifest,
+            ProjectManifest {
+                esp_idf_version: Some("v5.1.2".to_string()),
+                targets: Some("esp32s3".to_string()),
+                toolchain_version: Some("1.74.0.0".to_string()),
+                ..Default::default()
+            }
+        );
+
+        write(&file, "esp-idf = \"v5.1.2\"\n").unwrap();
+        assert!(ProjectManifest::find(&src_dir).is_err());
+    }
+
+    #[test]
+    fn test_apply_manifest() {
+        let manifest = ProjectManifest {
+            nightly_version: Some("nightly".to_string()),
+            targets: Some("esp32s3".to_string()),
+            ..Default::default()
+        };
+        let config = Config::parse(
+            r#"
+targets = "esp32c3"
+toolchain-version = "1.73.0.1"
+nightly-version = "nightly-2023-11-14"
+"#,
+            Path::new("config.toml"),
+        )
+        .unwrap();
+
+        // The pins of the manifest take precedence over the configuration, even the ones set to
+        // their default value
+        let mut args = InstallOptions::default();
+        manifest.apply(&mut args).unwrap();
+        config.apply(&mut args).unwrap();
+        assert_eq!(args.targets, parse_targets("esp32s3").unwrap());
+        assert_eq!(args.nightly_version, "nightly");
+        assert_eq!(args.toolchain_version.as_deref(), Some("1.73.0.1"));
+    }
+
+    #[test]
+    fn test_freeze() {
+        let toolchain_state = ToolchainState {
+            xtensa_rust_version: Some("1.82.0.3".to_string()),
+            targets: vec!["esp32".to_string(), "esp32c3".to_string()],
+            nightly_version: Some("nightly".to_string()),
+            exports: vec!["export ESP_IDF_VERSION=\"v5.1.2\"".to_string()],
+            crates: vec!["ldproxy".to_string()],
+            ..Default::default()
+        };
+        let manifest = ProjectManifest::freeze(&toolchain_state);
+        let contents = manifest.to_toml();
+        assert_eq!(
+            contents,
+            r#"toolchain-version = "1.82.0.3"
+targets = "esp32,esp32c3"
+esp-idf-version = "v5.1.2"
+nightly-version = "nightly"
+extra-crates = ["ldproxy"]
+"#
+        );
+        let file = std::path::Path::new(PROJECT_MANIFEST);
+        assert_eq!(ProjectManifest::parse(&contents, file).unwrap(), manifest);
+
+        // Applying it installs the toolchain again as it was
+        let mut args = InstallOptions::default();
+        manifest.apply(&mut args).unwrap();
+        assert_eq!(args.toolchain_version.as_deref(), Some("1.82.0.3"));
+        assert_eq!(args.targets, parse_targets("esp32,esp32c3").unwrap());
+        assert_eq!(args.esp_idf_version.as_deref(), Some("v5.1.2"));
+        assert_eq!(args.nightly_version, "nightly");
+        assert_eq!(args.extra_crates, vec!["ldproxy"]);
+        assert!(!args.std);
+    }
+}
@@ -0,0 +1,252 @@
+//! Shells the export file can be generated for.
+
+use crate::state::ToolchainState;
+use log::debug;
+use std::{env, path::Path};
+use strum::{Display, EnumString};
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq, Eq)]
+pub enum Shell {
+    /// POSIX compatible shells (e.g. sh, bash or zsh)
+    #[strum(to_string = "sh", serialize = "bash", serialize = "zsh")]
+    Posix,
+    /// fish shell
+    #[strum(serialize = "fish")]
+    Fish,
+    /// PowerShell Core and Windows PowerShell
+    #[strum(to_string = "powershell", serialize = "pwsh")]
+    PowerShell,
+    /// Windows Command Prompt
+    #[strum(serialize = "cmd")]
+    Cmd,
+}
+
+impl Shell {
+    /// Returns the shell used when none can be detected.
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            Shell::PowerShell
+        } else {
+            Shell::Posix
+        }
+    }
+
+    /// Returns the shell matching the name of an executable (e.g. 'zsh' or '/usr/bin/fish').
+    pub fn from_executable(executable: &str) -> Option<Self> {
+        let name = Path::new(executable.trim())
+            .file_stem()?
+            .to_string_lossy()
+            .trim_start_matches('-')
+            .to_lowercase();
+        match name.as_str() {
+            "sh" | "bash" | "zsh" | "dash" | "ksh" => Some(Shell::Posix),
+            "fish" => Some(Shell::Fish),
+            "pwsh" | "powershell" => Some(Shell::PowerShell),
+            "cmd" => Some(Shell::Cmd),
+            _ => None,
+        }
+    }
+
+    /// Returns the shell matching the extension of an export file.
+    pub fn from_export_file(export_file: &Path) -> Option<Self> {
+        match export_file.extension()?.to_str()? {
+            "sh" => Some(Shell::Posix),
+            "fish" => Some(Shell::Fish),
+            "ps1" => Some(Shell::PowerShell),
+            "bat" | "cmd" => Some(Shell::Cmd),
+            _ => None,
+        }
+    }
+
+    /// Returns the shell of the export file of an installation.
+    pub fn from_installation(toolchain: &ToolchainState) -> Self {
+        toolchain
+            .shell
+            .as_deref()
+            .and_then(|shell| shell.parse().ok())
+            .or_else(|| {
+                toolchain
+                    .export_file
+                    .as_deref()
+                    .and_then(Self::from_export_file)
+            })
+            .unwrap_or_else(Self::native)
+    }
+
+    /// Detects the shell espup has been launched from.
+    ///
+    /// On Unix, the parent process is inspected first and `$SHELL` is used as fallback. On
+    /// Windows, the Command Prompt is told apart from PowerShell by the `PROMPT` variable it sets.
+    pub fn detect() -> Self {
+        #[cfg(windows)]
+        let shell = env::var_os("PROMPT").map(|_| Shell::Cmd);
+        #[cfg(not(windows))]
+        let shell = parent_process_name()
+            .and_then(|name| Self::from_executable(&name))
+            .or_else(|| {
+                env::var("SHELL")
+                    .ok()
+                    .and_then(|shell| Self::from_executable(&shell))
+            });
+        let shell = shell.unwrap_or_else(Self::native);
+        debug!("Detected shell: {}", shell);
+        shell
+    }
+
+    /// Returns the name of the export file generated under the espup home for the toolchain.
+    ///
+    /// Toolchains other than 'esp' get their own export file (e.g. 'env-esp-1.73'), so they can be
+    /// installed side by side.
+    pub fn default_export_file(&self, toolchain_name: &str) -> String {
+        let stem = if toolchain_name == "esp" {
+            "env".to_string()
+        } else {
+            format!("env-{toolchain_name}")
+        };
+        match self {
+            Shell::Posix => stem,
+            Shell::Fish => format!("{stem}.fish"),
+            Shell::PowerShell => format!("{stem}.ps1"),
+            Shell::Cmd => format!("{stem}.bat"),
+        }
+    }
+
+    /// Returns the command loading the export file into the current session.
+    pub fn source_command(&self, export_file: &Path) -> String {
+        match self {
+            Shell::Posix | Shell::PowerShell => format!(". {}", export_file.display()),
+            Shell::Fish => format!("source {}", export_file.display()),
+            Shell::Cmd => format!("call {}", export_file.display()),
+        }
+    }
+
+    /// Returns the name of the export script of the ESP-IDF for the shell.
+    pub fn esp_idf_export_script(&self) -> &'static str {
+        match self {
+            Shell::Posix => "export.sh",
+            Shell::Fish => "export.fish",
+            Shell::PowerShell => "export.ps1",
+            Shell::Cmd => "export.bat",
+        }
+    }
+
+    /// Returns the command setting an environment variable.
+    pub fn set_variable(&self, name: &str, value: &str) -> String {
+        match self {
+            Shell::Posix => format!("export {name}=\"{value}\""),
+            Shell::Fish => format!("set -gx {name} \"{value}\""),
+            Shell::PowerShell => format!("$Env:{name} = \"{value}\""),
+            Shell::Cmd => format!("set \"{name}={value}\""),
+        }
+    }
+
+    /// Returns the command prepending the directories to the `PATH`, unless they are already prepended.
+    pub fn prepend_path(&self, paths: &[&str]) -> String {
+        match self {
+            Shell::Posix => {
+                let prefix = paths.join(":");
+                format!(
+                    "case \":$PATH:\" in *\":{prefix}:\"*) ;; *) export PATH=\"{prefix}:$PATH\" ;; esac"
+                )
+            }
+            Shell::Fish => {
+                let paths: Vec<String> = paths.iter().map(|path| format!("\"{path}\"")).collect();
+                format!("fish_add_path -gP {}", paths.join(" "))
+            }
+            Shell::PowerShell => {
+                let prefix = format!("{};", paths.join(";"));
+                format!(
+                    "if (-not $Env:PATH.Contains(\"{prefix}\")) {{ $Env:PATH = \"{prefix}\" + $Env:PATH }}"
+                )
+            }
+            Shell::Cmd => format!("set \"PATH={};%PATH%\"", paths.join(";")),
+        }
+    }
+}
+
+/// Returns the name of the parent process, where the system exposes it.
+#[cfg(not(windows))]
+fn parent_process_name() -> Option<String> {
+    let parent = std::os::unix::process::parent_id();
+    std::fs::read_to_string(format!("/proc/{parent}/comm"))
+        .ok()
+        .map(|name| name.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{shell::Shell, state::ToolchainState};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_shell_from_names() {
+        assert_eq!(Shell::from_executable("/bin/zsh"), Some(Shell::Posix));
+        assert_eq!(Shell::from_executable("-bash"), Some(Shell::Posix));
+        assert_eq!(Shell::from_executable("/usr/bin/fish"), Some(Shell::Fish));
+        assert_eq!(Shell::from_executable("pwsh.exe"), Some(Shell::PowerShell));
+        assert_eq!(Shell::from_executable("cargo"), None);
+        assert_eq!("bash".parse::<Shell>().unwrap(), Shell::Posix);
+        assert_eq!("pwsh".parse::<Shell>().unwrap(), Shell::PowerShell);
+        assert!("tcsh".parse::<Shell>().is_err());
+        assert_eq!(
+            Shell::from_export_file(Path::new("/home/user/export-esp.sh")),
+            Some(Shell::Posix)
+        );
+        assert_eq!(
+            Shell::from_export_file(Path::new("env.fish")),
+            Some(Shell::Fish)
+        );
+        assert_eq!(Shell::from_export_file(Path::new("env")), None);
+    }
+
+    #[test]
+    fn test_default_export_file() {
+        assert_eq!(Shell::Posix.default_export_file("esp"), "env");
+        assert_eq!(Shell::Cmd.default_export_file("esp"), "env.bat");
+        assert_eq!(Shell::Posix.default_export_file("esp-1.73"), "env-esp-1.73");
+        assert_eq!(
+            Shell::PowerShell.default_export_file("esp-1.82"),
+            "env-esp-1.82.ps1"
+        );
+    }
+
+    #[test]
+    fn test_shell_from_installation() {
+        let mut toolchain = ToolchainState {
+            export_file: Some(PathBuf::from("/home/user/.espup/env.bat")),
+            ..Default::default()
+        };
+        assert_eq!(Shell::from_installation(&toolchain), Shell::Cmd);
+        toolchain.shell = Some("fish".to_string());
+        assert_eq!(Shell::from_installation(&toolchain), Shell::Fish);
+        assert_eq!(
+            Shell::from_installation(&ToolchainState::default()),
+            Shell::native()
+        );
+    }
+
+    #[test]
+    fn test_shell_commands() {
+        let paths = ["/esp/xtensa-esp-elf/bin", "/esp/riscv32-esp-elf/bin"];
+        assert_eq!(
+            Shell::Fish.set_variable("LIBCLANG_PATH", "/esp/esp-clang/lib"),
+            "set -gx LIBCLANG_PATH \"/esp/esp-clang/lib\""
+        );
+        assert_eq!(
+            Shell::Fish.prepend_path(&paths),
+            "fish_add_path -gP \"/esp/xtensa-esp-elf/bin\" \"/esp/riscv32-esp-elf/bin\""
+        );
+        assert_eq!(
+            Shell::Cmd.set_variable("LIBCLANG_PATH", "C:\\esp\\esp-clang\\bin"),
+            "set \"LIBCLANG_PATH=C:\\esp\\esp-clang\\bin\""
+        );
+        assert_eq!(
+            Shell::Cmd.prepend_path(&["C:\\esp\\bin"]),
+            "set \"PATH=C:\\esp\\bin;%PATH%\""
+        );
+        assert_eq!(
+            Shell::Fish.source_command(Path::new("/home/user/.espup/env.fish")),
+            "source /home/user/.espup/env.fish"
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! Persistent record of the changes espup made to the system.
+
+pub use crate::paths::get_espup_home;
+use crate::{error::Error, gc::now, toolchain::Change};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+/// Name of the file storing the state.
+const STATE_FILE: &str = "state.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RustupTarget {
+    /// Rustup toolchain the target was added to.
+    pub toolchain: String,
+    /// Target triple.
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentRecord {
+    /// Name of the component.
+    pub name: String,
+    /// Installed version of the component, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Files and directories installed, or reused, by the component.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathBuf>,
+    /// Time of the installation, in seconds since the Unix epoch.
+    pub installed_at: u64,
+}
+
+impl ComponentRecord {
+    /// Records the component installed by the changes.
+    pub fn new(name: &str, version: Option<String>, changes: &[Change]) -> Self {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for path in changes.iter().filter_map(Change::installed_path) {
+            if !paths.iter().any(|recorded| recorded == path) {
+                paths.push(path.to_path_buf());
+            }
+        }
+        Self {
+            name: name.to_string(),
+            version,
+            paths,
+            installed_at: now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolchainState {
+    /// Custom directory where LLVM was installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llvm_path: Option<PathBuf>,
+    /// Rustup targets added by espup, which were not installed beforehand.
+    #[serde(default)]
+    pub rustup_targets: Vec<RustupTarget>,
+    /// Custom directory where the toolchain was installed, linked into the rustup home.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toolchain_destination: Option<PathBuf>,
+    /// Installed Xtensa Rust version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xtensa_rust_version: Option<String>,
+    /// Export file generated during the installation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export_file: Option<PathBuf>,
+    /// Targets with their own export file, next to the export file, as '--per-target-exports'
+    /// generated them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub export_targets: Vec<String>,
+    /// Shell whose syntax the export file uses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// Exports written to the export file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exports: Vec<String>,
+    /// Extra crates installed with the toolchain.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crates: Vec<String>,
+    /// Targets the toolchain was installed for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+    /// Nightly Rust toolchain version installed for the RISC-V targets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nightly_version: Option<String>,
+    /// Whether the toolchain was only installed for STD applications.
+    #[serde(default)]
+    pub std: bool,
+    /// Whether the extended LLVM was installed.
+    #[serde(default)]
+    pub extended_llvm: bool,
+    /// Optional components added with 'espup component add'.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<String>,
+    /// Last time the toolchain was installed or used through espup, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
+    /// Components installed with the toolchain, with their versions and the paths they installed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub installed: Vec<ComponentRecord>,
+    /// Version of espup that last installed or updated the toolchain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub espup_version: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    /// State of every installation, indexed by the Xtensa Rust toolchain name.
+    #[serde(default)]
+    pub toolchains: BTreeMap<String, ToolchainState>,
+}
+
+impl State {
+    /// Loads the state from the file, returning an empty state if it does not exist.
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        debug!("Loading state from '{}'", path.display());
+        serde_json::from_str(&read_to_string(path)?).map_err(|_| Error::SerializeJson)
+    }
+
+    /// Loads the state from the default location.
+    pub fn load() -> Result<Self, Error> {
+        Self::load_from(&get_state_file())
+    }
+
+    /// Saves the state to the file.
+    pub fn save_to(&self, path: &Path) -> Result<(), Error> {
+        debug!("Saving state to '{}'", path.display());
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)
+                .map_err(|_| Error::CreateDirectory(parent.display().to_string()))?;
+        }
+        let contents = serde_json::to_string_pretty(self).map_err(|_| Error::SerializeJson)?;
+        write(path, contents)?;
+        Ok(())
+    }
+
+    /// Saves the state to the default location.
+    pub fn save(&self) -> Result<(), Error> {
+        self.save_to(&get_state_file())
+    }
+
+    /// Returns the state of a toolchain, creating it if it does not exist.
+    pub fn toolchain(&mut self, name: &str) -> &mut ToolchainState {
+        self.toolchains.entry(name.to_string()).or_default()
+    }
+}
+
+/// Records that an installed toolchain has been used.
+///
+/// Usage is only informative, failing to record it does not fail the command using the toolchain.
+pub fn record_usage(name: &str) {
+    let mut state = match State::load() {
+        Ok(state) => state,
+        Err(error) => {
+            debug!("Failed to record the usage of '{}': {}", name, error);
+            return;
+        }
+    };
+    let Some(toolchain) = state.toolchains.get_mut(name) else {
+        return;
+    };
+    toolchain.last_used = Some(now());
+    if let Err(error) = state.save() {
+        debug!("Failed to record the usage of '{}': {}", name, error);
+    }
+}
+
+impl ToolchainState {
+    /// Records a rustup target added by espup, if it is not already recorded.
+    pub fn add_rustup_target(&mut self, toolchain: &str, target: &str) {
+        let rustup_target = RustupTarget {
+            toolchain: toolchain.to_string(),
+            target: target.to_string(),
+        };
+        if !self.rustup_targets.contains(&rustup_target) {
+            self.rustup_targets.push(rustup_target);
+        }
+    }
+
+    /// Records an installed component, replacing its previous record.
+    pub fn record_component(&mut self, record: ComponentRecord) {
+        self.installed
+            .retain(|installed| installed.name != record.name);
+        self.installed.push(record);
+    }
+
+    /// Forgets the record of a removed component.
+    pub fn forget_component(&mut self, name: &str) {
+        self.installed.retain(|installed| installed.name != name);
+    }
+
+    /// Returns the added rustup targets, grouped by toolchain.
+    pub fn rustup_targets_by_toolchain(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut targets: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for rustup_target in &self.rustup_targets {
+            targets
+                .entry(&rustup_target.toolchain)
+                .or_default()
+                .push(&rustup_target.target);
+        }
+        targets
+    }
+}
+
+/// Gets the path of the state file.
+pub fn get_state_file() -> PathBuf {
+    get_espup_home().join(STATE_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        state::{ComponentRecord, RustupTarget, State},
+        toolchain::Change,
+    };
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_component_record() {
+        let changes = vec![
+            Change::Download {
+                url: "https://example.com/gcc.tar.xz".to_string(),
+                destination: PathBuf::from("/esp/xtensa-esp-elf"),
+            },
+            Change::Download {
+                url: "https://example.com/gcc-libs.tar.xz".to_string(),
+                destination: PathBuf::from("/esp/xtensa-esp-elf"),
+            },
+            Change::path("/esp/xtensa-esp-elf/bin"),
+            Change::Reuse {
+                path: PathBuf::from("/esp/esp-clang"),
+            },
+        ];
+        let record = ComponentRecord::new("GCC", Some("13.2.0".to_string()), &changes);
+        assert_eq!(
+            record.paths,
+            vec![
+                PathBuf::from("/esp/xtensa-esp-elf"),
+                PathBuf::from("/esp/esp-clang")
+            ]
+        );
+
+        let mut state = State::default();
+        let toolchain = state.toolchain("esp");
+        toolchain.record_component(record.clone());
+        toolchain.record_component(ComponentRecord {
+            version: Some("14.2.0".to_string()),
+            ..record
+        });
+        assert_eq!(toolchain.installed.len(), 1);
+        assert_eq!(toolchain.installed[0].version.as_deref(), Some("14.2.0"));
+        toolchain.forget_component("GCC");
+        assert!(toolchain.installed.is_empty());
+    }
+
+    #[test]
+    fn test_state_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let state_file = temp_dir.path().join("espup").join("state.json");
+        assert_eq!(State::load_from(&state_file).unwrap(), State::default());
+
+        let mut state = State::default();
+        let toolchain = state.toolchain("esp");
+        toolchain.add_rustup_target("nightly", "riscv32imc-unknown-none-elf");
+        toolchain.add_rustup_target("nightly", "riscv32imc-unknown-none-elf");
+        state.save_to(&state_file).unwrap();
+
+        let mut state = State::load_from(&state_file).unwrap();
+        assert_eq!(
+            state.toolchain("esp").rustup_targets,
+            vec![RustupTarget {
+                toolchain: "nightly".to_string(),
+                target: "riscv32imc-unknown-none-elf".to_string(),
+            }]
+        );
+        assert!(state.toolchain("other").rustup_targets.is_empty());
+
+        let toolchain = state.toolchain("esp");
+        toolchain.add_rustup_target("nightly-2023-11-14", "riscv32imac-unknown-none-elf");
+        toolchain.add_rustup_target("nightly", "riscv32imac-unknown-none-elf");
+        assert_eq!(
+            toolchain.rustup_targets_by_toolchain(),
+            [
+                (
+                    "nightly",
+                    vec![
+                        "riscv32imc-unknown-none-elf",
+                        "riscv32imac-unknown-none-elf"
+                    ]
+                ),
+                ("nightly-2023-11-14", vec!["riscv32imac-unknown-none-elf"]),
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+}
@@ -1,12 +1,19 @@
 //! ESP32 chip variants support.
 
 use crate::error::Error;
-use log::debug;
+#[cfg(feature = "cli")]
+use clap::{
+    builder::{PossibleValue, PossibleValuesParser, TypedValueParser},
+    Arg, Command,
+};
+use log::{debug, info};
 use miette::Result;
+#[cfg(feature = "cli")]
+use std::ffi::OsStr;
 use std::{collections::HashSet, str::FromStr};
-use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator, IntoStaticStr};
 
-#[derive(Clone, Copy, EnumIter, EnumString, PartialEq, Hash, Eq, Debug, Display)]
+#[derive(Clone, Copy, EnumIter, EnumString, IntoStaticStr, PartialEq, Hash, Eq, Debug, Display)]
 #[strum(serialize_all = "lowercase")]
 pub enum Target {
     /// Xtensa LX6 based dual core
@@ -19,6 +26,8 @@ pub enum Target {
     ESP32C6,
     /// RISC-V based single core
     ESP32H2,
+    /// RISC-V based dual core, with a floating point unit
+    ESP32P4,
     /// Xtensa LX7 based single core
     ESP32S2,
     /// Xtensa LX7 based dual core
@@ -31,27 +40,149 @@ impl Target {
         !self.is_xtensa()
     }
 
+    /// Returns true if the chip has a built-in USB-Serial-JTAG peripheral.
+    ///
+    /// These chips can be flashed and debugged through USB without a USB-to-UART bridge driver
+    /// or an external debug probe.
+    pub fn has_usb_jtag(&self) -> bool {
+        matches!(
+            self,
+            Target::ESP32C3 | Target::ESP32C6 | Target::ESP32H2 | Target::ESP32P4 | Target::ESP32S3
+        )
+    }
+
     /// Returns true if the target is a Xtensa based chip.
     pub fn is_xtensa(&self) -> bool {
         matches!(self, Target::ESP32 | Target::ESP32S2 | Target::ESP32S3)
     }
+
+    /// Returns the Rust target triple used to build applications for the chip.
+    pub fn rust_target(&self, std: bool) -> &'static str {
+        match (self, std) {
+            (Target::ESP32, false) => "xtensa-esp32-none-elf",
+            (Target::ESP32, true) => "xtensa-esp32-espidf",
+            (Target::ESP32C2 | Target::ESP32C3, false) => "riscv32imc-unknown-none-elf",
+            (Target::ESP32C2 | Target::ESP32C3, true) => "riscv32imc-esp-espidf",
+            (Target::ESP32C6 | Target::ESP32H2, false) => "riscv32imac-unknown-none-elf",
+            (Target::ESP32C6 | Target::ESP32H2, true) => "riscv32imac-esp-espidf",
+            (Target::ESP32P4, false) => "riscv32imafc-unknown-none-elf",
+            (Target::ESP32P4, true) => "riscv32imafc-esp-espidf",
+            (Target::ESP32S2, false) => "xtensa-esp32s2-none-elf",
+            (Target::ESP32S2, true) => "xtensa-esp32s2-espidf",
+            (Target::ESP32S3, false) => "xtensa-esp32s3-none-elf",
+            (Target::ESP32S3, true) => "xtensa-esp32s3-espidf",
+        }
+    }
+}
+
+/// Boards whose identifier does not start with the name of their chip followed by a separator.
+const BOARDS: [(&str, Target); 12] = [
+    ("esp32cam", Target::ESP32),
+    ("esp32dev", Target::ESP32),
+    ("esp32doitdevkitv1", Target::ESP32),
+    ("esp32s3box", Target::ESP32S3),
+    ("featheresp32", Target::ESP32),
+    ("heltecwifikit32", Target::ESP32),
+    ("lolin32", Target::ESP32),
+    ("lolinc3mini", Target::ESP32C3),
+    ("lolins2mini", Target::ESP32S2),
+    ("m5stackcoreesp32", Target::ESP32),
+    ("nodemcu32s", Target::ESP32),
+    ("ttgotdisplay", Target::ESP32),
+];
+
+/// Returns the chip of a board identifier used by other ecosystems, like Arduino or PlatformIO.
+///
+/// Accepts the PlatformIO board names (e.g. 'esp32dev' or 'esp32-s3-devkitc-1') and the Arduino
+/// FQBNs (e.g. 'esp32:esp32:esp32c3').
+pub fn board_to_target(board: &str) -> Option<Target> {
+    let board = board.strip_prefix("esp32:esp32:").unwrap_or(board);
+    let board = board.split(':').next()?;
+    let normalized: String = board
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    if let Some((_, target)) = BOARDS.iter().find(|(name, _)| normalized == *name) {
+        return Some(*target);
+    }
+    // Otherwise the chip, written 'esp32s3' or 'esp32-s3', is followed by the name of the board
+    // after a separator (e.g. 'esp32-s3-devkitc-1'). Unknown chips like 'esp32c5' are not
+    // mistaken for a chip whose name they start with.
+    let board = board.to_lowercase().replace('_', "-");
+    Target::iter()
+        .flat_map(|target| {
+            let name = target.to_string();
+            let variant = name.strip_prefix("esp32").unwrap_or_default().to_string();
+            let mut spellings = vec![name.clone()];
+            if !variant.is_empty() {
+                spellings.push(format!("esp32-{variant}"));
+            }
+            spellings
+                .into_iter()
+                .map(move |spelling| (target, spelling))
+        })
+        .filter(
+            |(_, spelling)| match board.strip_prefix(spelling.as_str()) {
+                Some("") => true,
+                Some(rest) => rest.strip_prefix('-').is_some_and(|rest| {
+                    !is_chip_variant(rest.split('-').next().unwrap_or_default())
+                }),
+                None => false,
+            },
+        )
+        .max_by_key(|(_, spelling)| spelling.len())
+        .map(|(target, _)| target)
+}
+
+/// Returns true if the segment of a board identifier names a chip variant (e.g. 's3' or 'c5').
+fn is_chip_variant(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.as_str().chars().next().is_some()
+        && chars.all(|c| c.is_ascii_digit())
+}
+
+/// Returns the chips of a group of targets: 'xtensa', 'riscv' or 'all'.
+pub fn target_group(name: &str) -> Option<HashSet<Target>> {
+    let is_in_group: fn(&Target) -> bool = match name {
+        "all" => |_| true,
+        "xtensa" => Target::is_xtensa,
+        "riscv" => Target::is_riscv,
+        _ => return None,
+    };
+    Some(Target::iter().filter(is_in_group).collect())
 }
 
 /// Returns a vector of Chips from a comma or space separated string.
+///
+/// Groups of targets (see [`target_group`]) are expanded to their chips, and board identifiers
+/// of other ecosystems are translated to their chip.
 pub fn parse_targets(targets_str: &str) -> Result<HashSet<Target>, Error> {
     debug!("Parsing targets: {}", targets_str);
 
     let targets_str = targets_str.to_lowercase();
     let targets_str = targets_str.trim();
 
-    let targets: HashSet<Target> = if targets_str.contains("all") {
+    let targets: HashSet<Target> = if targets_str.split([',', ' ']).any(|t| t == "all") {
         Target::iter().collect()
     } else {
         let mut targets = HashSet::new();
         for target in targets_str.split([',', ' ']) {
-            targets.insert(
-                Target::from_str(target).map_err(|_| Error::UnsupportedTarget(target.into()))?,
-            );
+            if let Some(group) = target_group(target) {
+                targets.extend(group);
+                continue;
+            }
+            let chip = match Target::from_str(target) {
+                Ok(chip) => chip,
+                Err(_) => {
+                    let chip = board_to_target(target)
+                        .ok_or_else(|| Error::UnsupportedTarget(target.into()))?;
+                    info!("Selecting '{chip}' for the '{target}' board, targets are named after their chip");
+                    chip
+                }
+            };
+            targets.insert(chip);
         }
 
         targets
@@ -61,9 +192,46 @@ pub fn parse_targets(targets_str: &str) -> Result<HashSet<Target>, Error> {
     Ok(targets)
 }
 
+/// Returns the parser of a single target, which also provides the targets to the shell completions.
+#[cfg(feature = "cli")]
+pub fn target_parser() -> impl TypedValueParser<Value = Target> {
+    PossibleValuesParser::new(Target::iter().map(|target| PossibleValue::new(<&str>::from(target))))
+        .map(|target| Target::from_str(&target).unwrap())
+}
+
+/// Parser of the target lists, which also provides the targets to the shell completions.
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TargetsParser;
+
+#[cfg(feature = "cli")]
+impl TypedValueParser for TargetsParser {
+    type Value = HashSet<Target>;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        parse_targets.parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            Target::iter()
+                .map(|target| PossibleValue::new(<&str>::from(target)))
+                .chain(["xtensa", "riscv", "all"].map(PossibleValue::new)),
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::targets::{parse_targets, Target};
+    use crate::{
+        error::Error,
+        targets::{board_to_target, parse_targets, target_group, Target},
+    };
     use std::collections::HashSet;
 
     #[test]
@@ -88,6 +256,7 @@ mod tests {
             Target::ESP32C3,
             Target::ESP32C6,
             Target::ESP32H2,
+            Target::ESP32P4,
             Target::ESP32S2,
             Target::ESP32S3,
         ]
@@ -95,4 +264,105 @@ mod tests {
         .collect();
         assert!(matches!(parse_targets("all"), Ok(targets)));
     }
+
+    #[test]
+    fn test_target_groups() {
+        assert_eq!(
+            parse_targets("xtensa").unwrap(),
+            [Target::ESP32, Target::ESP32S2, Target::ESP32S3]
+                .into_iter()
+                .collect()
+        );
+        let riscv = parse_targets("RISCV").unwrap();
+        assert_eq!(riscv.len(), 5);
+        assert!(riscv.iter().all(Target::is_riscv));
+        assert!(riscv.contains(&Target::ESP32P4));
+        // Groups are combined with the chips and the other groups
+        let targets = parse_targets("xtensa,esp32c3").unwrap();
+        assert_eq!(targets.len(), 4);
+        assert!(targets.contains(&Target::ESP32C3));
+        assert_eq!(
+            parse_targets("xtensa riscv").unwrap(),
+            target_group("all").unwrap()
+        );
+        assert!(target_group("arm").is_none());
+    }
+
+    #[test]
+    fn test_esp32c2() {
+        let targets = parse_targets("esp32c2").unwrap();
+        assert_eq!(targets, [Target::ESP32C2].into_iter().collect());
+        assert!(Target::ESP32C2.is_riscv());
+        assert!(!Target::ESP32C2.has_usb_jtag());
+        assert_eq!(
+            Target::ESP32C2.rust_target(false),
+            "riscv32imc-unknown-none-elf"
+        );
+        assert_eq!(Target::ESP32C2.rust_target(true), "riscv32imc-esp-espidf");
+    }
+
+    #[test]
+    fn test_board_to_target() {
+        assert_eq!(board_to_target("esp32dev"), Some(Target::ESP32));
+        assert_eq!(board_to_target("esp32-s3-devkitc-1"), Some(Target::ESP32S3));
+        assert_eq!(board_to_target("esp32-c3-devkitm-1"), Some(Target::ESP32C3));
+        assert_eq!(board_to_target("esp32s3box"), Some(Target::ESP32S3));
+        assert_eq!(
+            board_to_target("esp32:esp32:esp32c6"),
+            Some(Target::ESP32C6)
+        );
+        assert_eq!(
+            board_to_target("esp32:esp32:esp32s2:PSRAM=enabled"),
+            Some(Target::ESP32S2)
+        );
+        assert_eq!(board_to_target("nodemcu-32s"), Some(Target::ESP32));
+        assert_eq!(board_to_target("lolin_c3_mini"), Some(Target::ESP32C3));
+        assert_eq!(board_to_target("esp8266"), None);
+        assert_eq!(board_to_target("uno"), None);
+        // Unknown chips, and typos, are not mistaken for a known chip
+        assert_eq!(board_to_target("esp32c5"), None);
+        assert_eq!(board_to_target("esp32s4"), None);
+        assert_eq!(board_to_target("esp32c61"), None);
+        assert_eq!(board_to_target("esp32-c5-devkitc-1"), None);
+        assert_eq!(board_to_target("esp32s3x"), None);
+        assert_eq!(board_to_target("esp32-devkit"), Some(Target::ESP32));
+        assert_eq!(board_to_target("esp32c6_devkit"), Some(Target::ESP32C6));
+        assert!(matches!(
+            parse_targets("esp32c5"),
+            Err(Error::UnsupportedTarget(target)) if target == "esp32c5"
+        ));
+        assert_eq!(
+            parse_targets("esp32dev,esp32-c3-devkitm-1").unwrap(),
+            [Target::ESP32, Target::ESP32C3].into_iter().collect()
+        );
+        assert!(parse_targets("esp8266").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_targets_parser() {
+        use crate::targets::TargetsParser;
+        use clap::builder::TypedValueParser;
+
+        let values: Vec<String> = TargetsParser
+            .possible_values()
+            .unwrap()
+            .map(|value| value.get_name().to_string())
+            .collect();
+        assert_eq!(values.len(), 11);
+        assert!(values.contains(&"riscv".to_string()));
+        assert!(values.contains(&"esp32c3".to_string()));
+        assert!(values.contains(&"all".to_string()));
+
+        let cmd = clap::Command::new("espup");
+        assert_eq!(
+            TargetsParser
+                .parse_ref(&cmd, None, "esp32c3,esp32c6".as_ref())
+                .unwrap(),
+            [Target::ESP32C3, Target::ESP32C6].into_iter().collect()
+        );
+        assert!(TargetsParser
+            .parse_ref(&cmd, None, "esp8266".as_ref())
+            .is_err());
+    }
 }
@@ -0,0 +1,76 @@
+//! Task files with the common commands of an application.
+
+use crate::targets::Target;
+
+/// Returns the contents of a task file to build, flash and monitor an application for the target.
+///
+/// `format` is either "just" or "make". When an export file is provided, it's sourced before
+/// every command.
+pub fn generate_tasks(
+    target: &Target,
+    std: bool,
+    toolchain: &str,
+    export_file: Option<&str>,
+    format: &str,
+) -> String {
+    let rust_target = target.rust_target(std);
+    let env = export_file
+        .map(|export_file| format!(". {export_file} && "))
+        .unwrap_or_default();
+    let tasks = [
+        (
+            "build",
+            "Builds the application",
+            format!("{env}cargo +{toolchain} build --release --target {rust_target}"),
+        ),
+        (
+            "flash",
+            "Flashes the application and opens the serial monitor",
+            format!(
+                "{env}cargo +{toolchain} espflash flash --release --target {rust_target} --monitor"
+            ),
+        ),
+        (
+            "monitor",
+            "Opens the serial monitor",
+            "espflash monitor".to_string(),
+        ),
+    ];
+
+    let mut contents = format!("# Generated by espup for {target}\n");
+    if format == "make" {
+        let names: Vec<&str> = tasks.iter().map(|(name, _, _)| *name).collect();
+        contents.push_str(&format!("\n.PHONY: {}\n", names.join(" ")));
+    }
+    for (name, description, command) in tasks {
+        match format {
+            "make" => contents.push_str(&format!("\n# {description}\n{name}:\n\t{command}\n")),
+            _ => contents.push_str(&format!("\n# {description}\n{name}:\n    {command}\n")),
+        }
+    }
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{targets::Target, tasks::generate_tasks};
+
+    #[test]
+    fn test_generate_tasks() {
+        let justfile = generate_tasks(&Target::ESP32S3, false, "esp", None, "just");
+        assert!(justfile.starts_with("# Generated by espup for esp32s3\n"));
+        assert!(justfile
+            .contains("build:\n    cargo +esp build --release --target xtensa-esp32s3-none-elf\n"));
+        assert!(justfile.contains("monitor:\n    espflash monitor\n"));
+
+        let makefile = generate_tasks(
+            &Target::ESP32C3,
+            true,
+            "nightly",
+            Some("/home/user/export-esp.sh"),
+            "make",
+        );
+        assert!(makefile.contains(".PHONY: build flash monitor\n"));
+        assert!(makefile.contains("build:\n\t. /home/user/export-esp.sh && cargo +nightly build --release --target riscv32imc-esp-espidf\n"));
+    }
+}
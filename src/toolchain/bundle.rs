@@ -0,0 +1,291 @@
+//! Offline installations from a bundle of pre-downloaded artifacts.
+//!
+//! A bundle is a directory, or a tar archive of it, holding the artifacts and the manifest listing
+//! them, as written by '--keep-downloads'. The artifacts are served by their download URL, so the
+//! installation runs as if they were downloaded. 'espup bundle' creates them on a machine with
+//! network access.
+
+use crate::{
+    error::Error,
+    toolchain::{
+        fetch::{fetch_with_retry, retry_policy, Fetch},
+        keep_download,
+        releases::{asset_sha256, parse_asset_url, XTENSA_RUST_REPOSITORY},
+        verify_checksum, DOWNLOADS_MANIFEST,
+    },
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs::{read, read_to_string, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tar::{Archive, Builder};
+use tempfile::TempDir;
+use xz2::{read::XzDecoder, write::XzEncoder};
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BundleArtifact {
+    /// Download URL of the artifact.
+    pub url: String,
+    /// File of the artifact, relative to the bundle.
+    pub file: String,
+    /// Size of the artifact, in bytes.
+    pub size: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BundleManifest {
+    artifacts: Vec<BundleArtifact>,
+}
+
+/// Bundle of artifacts, for installations without network access.
+#[derive(Debug)]
+pub struct Bundle {
+    /// Directory of the bundle, or of its extracted archive.
+    pub dir: PathBuf,
+    /// Artifacts of the bundle, indexed by their download URL.
+    artifacts: HashMap<String, BundleArtifact>,
+    /// Directory the archive of the bundle is extracted into, removed with the bundle.
+    _extracted: Option<TempDir>,
+}
+
+impl Bundle {
+    /// Opens the bundle directory, or extracts the bundle archive ('.tar', '.tar.gz' or '.tar.xz').
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let invalid =
+            |reason: &str| Error::InvalidBundle(path.display().to_string(), reason.to_string());
+        if path.is_dir() {
+            return Self::from_dir(path.to_path_buf(), None);
+        }
+        let file = File::open(path).map_err(|_| invalid("it does not exist"))?;
+        let name = path.to_string_lossy();
+        let extracted = TempDir::new()?;
+        info!("Extracting the bundle '{}'", path.display());
+        let unpacked = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Archive::new(GzDecoder::new(file)).unpack(extracted.path())
+        } else if name.ends_with(".tar.xz") {
+            Archive::new(XzDecoder::new(file)).unpack(extracted.path())
+        } else if name.ends_with(".tar") {
+            Archive::new(file).unpack(extracted.path())
+        } else {
+            return Err(invalid("it is neither a directory nor a tar archive"));
+        };
+        unpacked.map_err(|_| invalid("its archive cannot be extracted"))?;
+        Self::from_dir(extracted.path().to_path_buf(), Some(extracted))
+    }
+
+    /// Reads the manifest of the bundle directory.
+    fn from_dir(dir: PathBuf, extracted: Option<TempDir>) -> Result<Self, Error> {
+        let manifest_path = dir.join(DOWNLOADS_MANIFEST);
+        let contents = read_to_string(&manifest_path).map_err(|_| {
+            Error::InvalidBundle(
+                dir.display().to_string(),
+                format!("'{DOWNLOADS_MANIFEST}' is missing"),
+            )
+        })?;
+        let manifest: BundleManifest = serde_json::from_str(&contents)
+            .map_err(|e| Error::InvalidBundle(dir.display().to_string(), e.to_string()))?;
+        debug!(
+            "Bundle '{}' holds {} artifacts",
+            dir.display(),
+            manifest.artifacts.len()
+        );
+        let artifacts = manifest
+            .artifacts
+            .into_iter()
+            .map(|artifact| (artifact.url.clone(), artifact))
+            .collect();
+        Ok(Self {
+            dir,
+            artifacts,
+            _extracted: extracted,
+        })
+    }
+
+    /// Returns the artifact downloaded from the URL, if the bundle holds it.
+    pub fn artifact(&self, url: &str) -> Option<&BundleArtifact> {
+        self.artifacts.get(url)
+    }
+
+    /// Returns the version of the Xtensa Rust toolchain of the bundle, if it holds one.
+    pub fn xtensa_rust_version(&self) -> Option<String> {
+        self.artifacts.keys().find_map(|url| {
+            let (repository, tag, asset) = parse_asset_url(url)?;
+            (repository == XTENSA_RUST_REPOSITORY
+                && asset.starts_with("rust-")
+                && !asset.starts_with("rust-src-"))
+            .then(|| tag.trim_start_matches('v').to_string())
+        })
+    }
+}
+
+#[async_trait]
+impl Fetch for Bundle {
+    async fn fetch(&self, url: &str) -> Result<Bytes, Error> {
+        let artifact = self
+            .artifact(url)
+            .ok_or_else(|| Error::MissingFromBundle(url.to_string()))?;
+        let path = self.dir.join(&artifact.file);
+        debug!("Reading '{}' from the bundle", path.display());
+        let bytes = read(&path).map_err(|_| Error::MissingFromBundle(url.to_string()))?;
+        if bytes.len() as u64 != artifact.size {
+            return Err(Error::InvalidBundle(
+                self.dir.display().to_string(),
+                format!("'{}' does not have the recorded size", artifact.file),
+            ));
+        }
+        Ok(Bytes::from(bytes))
+    }
+}
+
+/// Downloads the artifacts into a new bundle: a tar archive when `path` ends with '.tar',
+/// '.tar.gz' or '.tar.xz', a directory otherwise.
+///
+/// Returns the size of the downloaded artifacts.
+pub async fn create_bundle(
+    urls: &[String],
+    path: &Path,
+    fetcher: &dyn Fetch,
+) -> Result<u64, Error> {
+    if path.exists() {
+        return Err(Error::FileExists(path.display().to_string()));
+    }
+    let staging = TempDir::new()?;
+    let archived = is_archive(path);
+    let dir = if archived { staging.path() } else { path };
+    let mut size = 0;
+    for url in urls {
+        let file_name = url.rsplit('/').next().unwrap_or(url);
+        info!("Downloading '{file_name}' into the bundle");
+        let bytes = fetch_with_retry(fetcher, url, &retry_policy()).await?;
+        let checksum_url = url.clone();
+        match tokio::task::spawn_blocking(move || asset_sha256(&checksum_url))
+            .await
+            .unwrap()
+        {
+            Ok(expected_sha256) => {
+                verify_checksum(file_name, &bytes, expected_sha256.as_deref(), false)?
+            }
+            Err(error) => warn!(
+                "Failed to get the published checksum of '{}' ({}), it has not been verified",
+                file_name, error
+            ),
+        }
+        keep_download(dir, url, file_name, &bytes)?;
+        size += bytes.len() as u64;
+    }
+    if archived {
+        info!("Archiving the bundle into '{}'", path.display());
+        write_archive(dir, path)?;
+    }
+    Ok(size)
+}
+
+/// Returns true if the bundle is a tar archive rather than a directory.
+fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    [".tar", ".tar.gz", ".tgz", ".tar.xz"]
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+/// Archives the bundle directory, compressing it according to the extension of the archive.
+fn write_archive(dir: &Path, path: &Path) -> Result<(), Error> {
+    fn append<W: Write>(writer: W, dir: &Path) -> io::Result<W> {
+        let mut builder = Builder::new(writer);
+        builder.append_dir_all(".", dir)?;
+        builder.into_inner()
+    }
+
+    let file = File::create(path)?;
+    let name = path.to_string_lossy();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        append(GzEncoder::new(file, Compression::default()), dir)?.finish()?;
+    } else if name.ends_with(".tar.xz") {
+        append(XzEncoder::new(file, 6), dir)?.finish()?;
+    } else {
+        append(file, dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        toolchain::{
+            bundle::{create_bundle, Bundle},
+            fetch::Fetch,
+            DOWNLOADS_MANIFEST,
+        },
+    };
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use std::fs::write;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_bundle() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = "https://github.com/esp-rs/rust-build/releases/download/v1.74.0.0/rust-1.74.0.0-x86_64-unknown-linux-gnu.tar.xz";
+        write(temp_dir.path().join("rust.tar.xz"), "rust").unwrap();
+        write(
+            temp_dir.path().join(DOWNLOADS_MANIFEST),
+            format!(r#"{{"artifacts": [{{"url": "{url}", "file": "rust.tar.xz", "size": 4}}]}}"#),
+        )
+        .unwrap();
+
+        let bundle = Bundle::open(temp_dir.path()).unwrap();
+        assert_eq!(bundle.xtensa_rust_version(), Some("1.74.0.0".to_string()));
+        assert_eq!(bundle.fetch(url).await.unwrap().as_ref(), b"rust");
+        assert!(matches!(
+            bundle.fetch("https://example.com/gcc.tar.xz").await,
+            Err(Error::MissingFromBundle(_))
+        ));
+        write(temp_dir.path().join("rust.tar.xz"), "truncated").unwrap();
+        assert!(matches!(
+            bundle.fetch(url).await,
+            Err(Error::InvalidBundle(_, _))
+        ));
+        assert!(Bundle::open(&temp_dir.path().join("missing.tar")).is_err());
+    }
+
+    #[derive(Debug)]
+    struct StaticFetch;
+
+    #[async_trait]
+    impl Fetch for StaticFetch {
+        async fn fetch(&self, url: &str) -> Result<Bytes, Error> {
+            Ok(Bytes::from(url.rsplit('/').next().unwrap().to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_bundle() {
+        let temp_dir = TempDir::new().unwrap();
+        let urls = vec![
+            "https://example.com/llvm.tar.xz".to_string(),
+            "https://example.com/gcc.zip".to_string(),
+        ];
+        for name in ["bundle.tar.gz", "bundle.tar.xz", "bundle"] {
+            let path = temp_dir.path().join(name);
+            assert_eq!(create_bundle(&urls, &path, &StaticFetch).await.unwrap(), 18);
+            let bundle = Bundle::open(&path).unwrap();
+            assert_eq!(
+                bundle.fetch(&urls[0]).await.unwrap().as_ref(),
+                b"llvm.tar.xz"
+            );
+            assert_eq!(bundle.fetch(&urls[1]).await.unwrap().as_ref(), b"gcc.zip");
+            assert!(matches!(
+                create_bundle(&urls, &path, &StaticFetch).await,
+                Err(Error::FileExists(_))
+            ));
+        }
+    }
+}
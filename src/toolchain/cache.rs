@@ -0,0 +1,384 @@
+//! Cache of the downloaded artifacts.
+//!
+//! Every cached artifact is recorded in an index with its size and SHA-256, which are validated
+//! before reusing it, so corrupted entries are downloaded again instead of being extracted.
+//!
+//! Several processes, possibly of different users sharing the cache (see '--shared-cache'), can
+//! use the same cache at once: the artifacts and the index are written to temporary files renamed
+//! into place, and the updates of the index are serialized by an advisory lock of the OS.
+
+use crate::error::Error;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read_to_string, remove_file, rename, File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    process,
+    thread::sleep,
+    time::Duration,
+};
+
+/// Name of the index of the cache.
+pub const CACHE_INDEX: &str = "index.json";
+
+/// Size of the chunks read when hashing a file.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Name of the lock file serializing the updates of the index.
+const INDEX_LOCK: &str = "index.lock";
+
+/// Time waited before trying to take a lock held by another installation again.
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// Name of the cached file, relative to the cache directory.
+    pub file: String,
+    /// Size of the artifact, in bytes.
+    pub size: u64,
+    /// SHA-256 of the artifact, hex encoded.
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheIndex {
+    /// Cached artifacts, indexed by URL.
+    #[serde(default)]
+    pub entries: BTreeMap<String, CacheEntry>,
+}
+
+impl CacheIndex {
+    /// Loads the index of the cache directory, returning an empty one if it does not exist or is corrupted.
+    fn load(cache_dir: &Path) -> Self {
+        read_to_string(cache_dir.join(CACHE_INDEX))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the index into the cache directory.
+    fn save(&self, cache_dir: &Path, shared: bool) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(self).map_err(|_| Error::SerializeJson)?;
+        write_atomically(&cache_dir.join(CACHE_INDEX), contents.as_bytes(), shared)
+    }
+}
+
+/// Lock of the index of a cache directory, held by one installation at a time and released when
+/// dropped.
+///
+/// The lock is an advisory lock of the OS on the lock file, which is released with the file, even
+/// when the installation holding it crashes.
+#[derive(Debug)]
+struct IndexLock {
+    _file: File,
+}
+
+impl IndexLock {
+    /// Waits until the lock of the cache directory is free and takes it.
+    fn acquire(cache_dir: &Path, shared: bool) -> Result<Self, Error> {
+        let path = cache_dir.join(INDEX_LOCK);
+        let is_new = !path.exists();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        if is_new {
+            set_shared_permissions(&path, shared, 0o664)?;
+        }
+        let mut is_waiting = false;
+        while !try_lock_exclusive(&file)? {
+            if !is_waiting {
+                debug!(
+                    "Waiting for another installation to update '{}'",
+                    cache_dir.display()
+                );
+                is_waiting = true;
+            }
+            sleep(LOCK_RETRY_INTERVAL);
+        }
+        Ok(Self { _file: file })
+    }
+}
+
+/// Takes the advisory lock of the file, returning false if another process holds it.
+#[cfg(unix)]
+fn try_lock_exclusive(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: the descriptor is owned by the file, which outlives the call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::EWOULDBLOCK) => Ok(false),
+        _ => Err(error),
+    }
+}
+
+/// Takes the advisory lock of the file, returning false if another process holds it.
+#[cfg(windows)]
+fn try_lock_exclusive(file: &File) -> io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::um::{
+        fileapi::LockFileEx,
+        minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED},
+    };
+
+    // SAFETY: the handle is owned by the file, which outlives the call, and the overlapped
+    // structure is only used during it
+    let is_locked = unsafe {
+        let mut overlapped: OVERLAPPED = std::mem::zeroed();
+        LockFileEx(
+            file.as_raw_handle().cast(),
+            LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY,
+            0,
+            u32::MAX,
+            u32::MAX,
+            &mut overlapped,
+        )
+    } != 0;
+    if is_locked {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(code) if code == winapi::shared::winerror::ERROR_LOCK_VIOLATION as i32 => Ok(false),
+        _ => Err(error),
+    }
+}
+
+/// Writes the file through a temporary file renamed into place, so the other installations never
+/// read it partially written.
+///
+/// The files of shared caches are made writable by the group of their users.
+fn write_atomically(path: &Path, bytes: &[u8], shared: bool) -> Result<(), Error> {
+    let temp_path = path.with_file_name(format!(
+        "{}.{}.tmp",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        process::id()
+    ));
+    let result = File::create(&temp_path)
+        .and_then(|mut file| file.write_all(bytes))
+        .and_then(|_| set_shared_permissions(&temp_path, shared, 0o664))
+        .and_then(|_| rename(&temp_path, path));
+    if result.is_err() {
+        let _ = remove_file(&temp_path);
+    }
+    Ok(result?)
+}
+
+/// Sets the Unix mode of the path, if it is in a shared cache.
+#[cfg(unix)]
+fn set_shared_permissions(path: &Path, shared: bool, mode: u32) -> io::Result<()> {
+    use std::{fs::set_permissions, os::unix::fs::PermissionsExt};
+
+    if shared {
+        set_permissions(path, PermissionsExt::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+/// Sets the Unix mode of the path, if it is in a shared cache.
+#[cfg(not(unix))]
+fn set_shared_permissions(_path: &Path, _shared: bool, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// Encodes bytes as lowercase hexadecimal.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Computes the SHA-256 of the reader contents, reading them in chunks.
+pub fn sha256<R: Read>(mut reader: R) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&chunk[..read]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Returns the name of the cached file of an URL.
+fn cache_file_name(url: &str) -> String {
+    let hash = to_hex(&Sha256::digest(url.as_bytes()));
+    let base_name = url.rsplit('/').next().unwrap_or_default();
+    format!("{}-{base_name}", &hash[..16])
+}
+
+/// Returns the cached artifact of the URL, if it exists and is valid.
+pub fn get_cached(cache_dir: &Path, url: &str) -> Option<Vec<u8>> {
+    let entry = CacheIndex::load(cache_dir).entries.remove(url)?;
+    let path = cache_dir.join(&entry.file);
+    let is_valid = File::open(&path)
+        .and_then(|file| {
+            let size = file.metadata()?.len();
+            Ok(size == entry.size && sha256(file)? == entry.sha256)
+        })
+        .unwrap_or(false);
+    if !is_valid {
+        warn!(
+            "Cached artifact '{}' is corrupted, downloading it again",
+            path.display()
+        );
+        return None;
+    }
+    debug!("Using cached artifact '{}'", path.display());
+    std::fs::read(path).ok()
+}
+
+/// Returns the size of the cached artifact of the URL, if it is recorded in the index.
+///
+/// Unlike [`get_cached`], the artifact is not validated.
+pub fn get_cached_size(cache_dir: &Path, url: &str) -> Option<u64> {
+    let entry = CacheIndex::load(cache_dir).entries.remove(url)?;
+    cache_dir.join(&entry.file).exists().then_some(entry.size)
+}
+
+/// Stores an artifact in the cache and records it in the index.
+///
+/// Shared caches (see '--shared-cache') are created writable by the group of their users, and the
+/// users without write access only reuse them: failing to store an artifact is not an error.
+pub fn store(cache_dir: &Path, url: &str, bytes: &[u8], shared: bool) -> Result<(), Error> {
+    let result = store_entry(cache_dir, url, bytes, shared);
+    if shared && result.is_err() {
+        warn!(
+            "Shared cache '{}' is not writable, '{url}' is not cached",
+            cache_dir.display()
+        );
+        return Ok(());
+    }
+    result
+}
+
+/// Writes an artifact into the cache and records it in the index.
+fn store_entry(cache_dir: &Path, url: &str, bytes: &[u8], shared: bool) -> Result<(), Error> {
+    if !cache_dir.exists() {
+        create_dir_all(cache_dir)
+            .map_err(|_| Error::CreateDirectory(cache_dir.display().to_string()))?;
+        // The artifacts added by every user inherit the group of the directory
+        set_shared_permissions(cache_dir, shared, 0o2775)?;
+    }
+    let entry = CacheEntry {
+        file: cache_file_name(url),
+        size: bytes.len() as u64,
+        sha256: sha256(bytes)?,
+    };
+    debug!("Caching '{}' as '{}'", url, entry.file);
+    write_atomically(&cache_dir.join(&entry.file), bytes, shared)?;
+
+    let _lock = IndexLock::acquire(cache_dir, shared)?;
+    let mut index = CacheIndex::load(cache_dir);
+    index.entries.insert(url.to_string(), entry);
+    index.save(cache_dir, shared)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::toolchain::cache::{
+        get_cached, sha256, store, try_lock_exclusive, CacheIndex, IndexLock, CACHE_INDEX,
+        INDEX_LOCK,
+    };
+    use std::{
+        fs::{read_dir, write, File},
+        thread,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256("abc".as_bytes()).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let large = vec![0u8; 200 * 1024];
+        assert_eq!(sha256(large.as_slice()).unwrap().len(), 64);
+    }
+
+    #[test]
+    fn test_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_dir = temp_dir.path().join("cache");
+        let url = "https://example.com/v1/rust.tar.xz";
+        assert!(get_cached(&cache_dir, url).is_none());
+
+        store(&cache_dir, url, b"rust", false).unwrap();
+        assert_eq!(get_cached(&cache_dir, url).unwrap(), b"rust");
+        assert!(get_cached(&cache_dir, "https://example.com/v2/rust.tar.xz").is_none());
+
+        // Corrupted entries are not reused
+        let entry = CacheIndex::load(&cache_dir).entries.remove(url).unwrap();
+        write(cache_dir.join(&entry.file), "tsur").unwrap();
+        assert!(get_cached(&cache_dir, url).is_none());
+    }
+
+    #[test]
+    fn test_shared_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_dir = temp_dir.path().join("shared");
+        // Installations storing artifacts at the same time keep every entry of the index
+        thread::scope(|scope| {
+            for version in 0..4 {
+                let cache_dir = &cache_dir;
+                scope.spawn(move || {
+                    let url = format!("https://example.com/v{version}/rust.tar.xz");
+                    store(cache_dir, &url, url.as_bytes(), true).unwrap();
+                });
+            }
+        });
+        assert_eq!(CacheIndex::load(&cache_dir).entries.len(), 4);
+        let mut files: Vec<String> = read_dir(&cache_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        // The artifacts, the index and its lock file
+        assert_eq!(files.len(), 6);
+        assert!(files.contains(&CACHE_INDEX.to_string()));
+        assert!(!files.iter().any(|file| file.ends_with(".tmp")));
+
+        // The lock is only taken by one installation at a time, and released when dropped
+        let lock = IndexLock::acquire(&cache_dir, true).unwrap();
+        let file = File::open(cache_dir.join(INDEX_LOCK)).unwrap();
+        assert!(!try_lock_exclusive(&file).unwrap());
+        drop(lock);
+        assert!(try_lock_exclusive(&file).unwrap());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = |path: &std::path::Path| path.metadata().unwrap().permissions().mode();
+            assert_eq!(mode(&cache_dir) & 0o7777, 0o2775);
+            assert_eq!(mode(&cache_dir.join(CACHE_INDEX)) & 0o777, 0o664);
+        }
+
+        // Users without write access still install, without caching
+        let file = temp_dir.path().join("file");
+        write(&file, "").unwrap();
+        assert!(store(
+            &file.join("cache"),
+            "https://example.com/rust.tar.xz",
+            b"rust",
+            true
+        )
+        .is_ok());
+        assert!(store(
+            &file.join("cache"),
+            "https://example.com/rust.tar.xz",
+            b"rust",
+            false
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,470 @@
+//! Optional tools installed on top of a toolchain, managed with 'espup component'.
+
+use crate::{
+    env::{create_export_file, target_export_files},
+    error::Error,
+    host_triple::{get_host_triple, HostTriple},
+    permissions::restrict_permissions,
+    shell::Shell,
+    state::{ComponentRecord, State, ToolchainState},
+    toolchain::{
+        crates::{Crate, PROBE_RS},
+        download_file,
+        gcc::{RISCV_GCC, XTENSA_GCC},
+        prune_documentation,
+        releases::{asset_url, GDB_REPOSITORY, OPENOCD_REPOSITORY, QEMU_REPOSITORY},
+        rust::get_toolchain_dir,
+        Change, DownloadOptions, Installable,
+    },
+};
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
+use strum::{Display, EnumIter, EnumString};
+use tokio::fs::remove_dir_all;
+
+/// Name of the directory, in the toolchain directory, where the components are installed.
+pub const COMPONENTS_DIR: &str = "components";
+/// OpenOCD release installed by the `openocd` component.
+const OPENOCD_VERSION: &str = "0.12.0-esp32-20230921";
+/// QEMU release installed by the `qemu` component.
+const QEMU_VERSION: &str = "8.1.3_20231206";
+/// GDB release installed by the `gdb` component.
+const GDB_VERSION: &str = "12.1_20231023";
+
+/// Optional tool that can be added to an installation.
+#[derive(Clone, Copy, Debug, Display, EnumIter, EnumString, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[strum(serialize_all = "kebab-case")]
+pub enum Component {
+    /// GDB for Xtensa and RISC-V chips.
+    Gdb,
+    /// OpenOCD, with the Espressif chips support.
+    Openocd,
+    /// probe-rs, to flash and debug through a debug probe or the built-in USB-JTAG.
+    ProbeRs,
+    /// QEMU, emulating the Xtensa and RISC-V chips.
+    Qemu,
+    /// sccache, used as the rustc wrapper to cache the compilations.
+    Sccache,
+}
+
+impl Component {
+    /// Describes the component.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Component::Gdb => "GDB for Xtensa and RISC-V chips",
+            Component::Openocd => "OpenOCD, with the Espressif chips support",
+            Component::ProbeRs => {
+                "probe-rs, to flash and debug through a debug probe or the built-in USB-JTAG"
+            }
+            Component::Qemu => "QEMU, emulating the Xtensa and RISC-V chips",
+            Component::Sccache => "sccache, used as the rustc wrapper to cache the compilations",
+        }
+    }
+
+    /// Returns the crate the component is installed from, if it is installed with cargo.
+    fn krate(&self) -> Option<Crate> {
+        match self {
+            Component::ProbeRs => Some(Crate::with_features(PROBE_RS, &["cli"])),
+            Component::Sccache => Some(Crate::new("sccache")),
+            _ => None,
+        }
+    }
+}
+
+/// Release archive of a component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Artifact {
+    /// URL of the archive.
+    url: String,
+    /// Name the archive is downloaded as.
+    file_name: String,
+    /// Directory the archive is extracted into.
+    destination: PathBuf,
+    /// Directory of the binaries, added to the `PATH`.
+    bin_path: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tool {
+    /// Component the tool provides.
+    pub component: Component,
+    /// Host triple.
+    pub host_triple: HostTriple,
+    /// Directory the tool is installed into, unless it is a crate.
+    pub path: PathBuf,
+}
+
+impl Tool {
+    /// Create a new instance installing the component into the toolchain directory.
+    pub fn new(component: Component, host_triple: &HostTriple, toolchain_dir: &Path) -> Self {
+        Self {
+            component,
+            host_triple: host_triple.clone(),
+            path: toolchain_dir
+                .join(COMPONENTS_DIR)
+                .join(component.to_string()),
+        }
+    }
+
+    /// Returns the name of the crate the tool is installed from, if it is installed with cargo.
+    pub fn crate_name(&self) -> Option<String> {
+        self.component.krate().map(|krate| krate.name)
+    }
+
+    /// Gets the release archives of the tool for the host.
+    fn artifacts(&self) -> Vec<Artifact> {
+        let is_windows = matches!(
+            self.host_triple,
+            HostTriple::X86_64PcWindowsMsvc | HostTriple::X86_64PcWindowsGnu
+        );
+        let host = get_host(&self.host_triple);
+        match self.component {
+            Component::Gdb => [XTENSA_GCC, RISCV_GCC]
+                .into_iter()
+                .map(|arch| {
+                    let extension = if is_windows { "zip" } else { "tar.gz" };
+                    let asset = format!("{arch}-gdb-{GDB_VERSION}-{host}.{extension}");
+                    Artifact {
+                        url: asset_url(GDB_REPOSITORY, &format!("esp-gdb-v{GDB_VERSION}"), &asset),
+                        file_name: format!("{arch}-gdb.{extension}"),
+                        destination: self.path.clone(),
+                        bin_path: self.path.join(format!("{arch}-gdb")).join("bin"),
+                    }
+                })
+                .collect(),
+            Component::Openocd => {
+                let extension = if is_windows { "zip" } else { "tar.gz" };
+                let asset = format!(
+                    "openocd-esp32-{}-{OPENOCD_VERSION}.{extension}",
+                    get_openocd_host(&self.host_triple)
+                );
+                vec![Artifact {
+                    url: asset_url(OPENOCD_REPOSITORY, &format!("v{OPENOCD_VERSION}"), &asset),
+                    file_name: format!("openocd.{extension}"),
+                    destination: self.path.clone(),
+                    bin_path: self.path.join("openocd-esp32").join("bin"),
+                }]
+            }
+            Component::Qemu => ["xtensa", "riscv32"]
+                .into_iter()
+                .map(|arch| {
+                    let asset =
+                        format!("qemu-{arch}-softmmu-esp_develop_{QEMU_VERSION}-{host}.tar.xz");
+                    // Both archives extract a 'qemu' directory
+                    let destination = self.path.join(arch);
+                    Artifact {
+                        url: asset_url(
+                            QEMU_REPOSITORY,
+                            &format!("esp-develop-{}", QEMU_VERSION.replace('_', "-")),
+                            &asset,
+                        ),
+                        file_name: format!("qemu-{arch}.tar.xz"),
+                        bin_path: destination.join("qemu").join("bin"),
+                        destination,
+                    }
+                })
+                .collect(),
+            Component::ProbeRs | Component::Sccache => Vec::new(),
+        }
+    }
+
+    /// Returns the download URLs of the release archives, none for the crates.
+    pub fn urls(&self) -> Vec<String> {
+        self.artifacts()
+            .into_iter()
+            .map(|artifact| artifact.url)
+            .collect()
+    }
+
+    /// Returns true if the tool is already installed.
+    fn is_installed(&self) -> bool {
+        self.artifacts()
+            .iter()
+            .all(|artifact| artifact.bin_path.exists())
+    }
+
+    /// Returns the exports the tool requires.
+    pub fn exports(&self) -> Vec<String> {
+        let mut exports: Vec<String> = self
+            .artifacts()
+            .iter()
+            .map(|artifact| {
+                if cfg!(windows) {
+                    format!(
+                        "$Env:PATH = \"{};\" + $Env:PATH",
+                        artifact.bin_path.display()
+                    )
+                } else {
+                    format!("export PATH=\"{}:$PATH\"", artifact.bin_path.display())
+                }
+            })
+            .collect();
+        if self.component == Component::Sccache {
+            if cfg!(windows) {
+                exports.push("$Env:RUSTC_WRAPPER = \"sccache\"".to_string());
+            } else {
+                exports.push("export RUSTC_WRAPPER=\"sccache\"".to_string());
+            }
+        }
+        exports
+    }
+
+    /// Removes the tool.
+    pub async fn uninstall(&self) -> Result<(), Error> {
+        info!("Uninstalling {}", self.name());
+        if let Some(krate) = self.component.krate() {
+            return krate.uninstall();
+        }
+        if self.path.exists() {
+            remove_dir_all(&self.path)
+                .await
+                .map_err(|_| Error::RemoveDirectory(self.path.display().to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Installable for Tool {
+    async fn install(&self, options: &DownloadOptions) -> Result<Vec<String>, Error> {
+        if let Some(krate) = self.component.krate() {
+            krate.install(options).await?;
+            return Ok(self.exports());
+        }
+
+        info!("Installing {}", self.name());
+        debug!("{} path: {}", self.component, self.path.display());
+        if self.is_installed() {
+            warn!(
+                "Previous installation of {} exists in: '{}'. Reusing this installation",
+                self.component,
+                self.path.display()
+            );
+        } else {
+            for artifact in self.artifacts() {
+                download_file(
+                    artifact.url,
+                    &artifact.file_name,
+                    &artifact.destination.display().to_string(),
+                    true,
+                    false,
+                    options,
+                )
+                .await?;
+            }
+        }
+        if options.minimal {
+            prune_documentation(&self.path)?;
+        }
+
+        Ok(self.exports())
+    }
+
+    fn name(&self) -> String {
+        format!("Component ({})", self.component)
+    }
+
+    fn plan(&self, options: &DownloadOptions) -> Vec<Change> {
+        let mut changes = match self.component.krate() {
+            Some(krate) => krate.plan(options),
+            None if self.is_installed() => vec![Change::Reuse {
+                path: self.path.clone(),
+            }],
+            None => self
+                .artifacts()
+                .into_iter()
+                .map(|artifact| Change::Download {
+                    url: artifact.url,
+                    destination: artifact.destination,
+                })
+                .collect(),
+        };
+        for artifact in self.artifacts() {
+            changes.push(Change::path(&artifact.bin_path.display().to_string()));
+        }
+        if self.component == Component::Sccache {
+            changes.push(Change::variable("RUSTC_WRAPPER", "sccache"));
+        }
+        changes
+    }
+
+    fn version(&self) -> Option<String> {
+        match self.component {
+            Component::Gdb => Some(GDB_VERSION.to_string()),
+            Component::Openocd => Some(OPENOCD_VERSION.to_string()),
+            Component::Qemu => Some(QEMU_VERSION.to_string()),
+            Component::ProbeRs | Component::Sccache => None,
+        }
+    }
+}
+
+/// Gets the name of the host in the GDB and QEMU release archives.
+fn get_host(host_triple: &HostTriple) -> &'static str {
+    match host_triple {
+        HostTriple::X86_64AppleDarwin => "x86_64-apple-darwin",
+        HostTriple::Aarch64AppleDarwin => "aarch64-apple-darwin",
+        HostTriple::X86_64UnknownLinuxGnu => "x86_64-linux-gnu",
+        HostTriple::Aarch64UnknownLinuxGnu => "aarch64-linux-gnu",
+        HostTriple::X86_64PcWindowsMsvc | HostTriple::X86_64PcWindowsGnu => "x86_64-w64-mingw32",
+    }
+}
+
+/// Gets the name of the host in the OpenOCD release archives.
+fn get_openocd_host(host_triple: &HostTriple) -> &'static str {
+    match host_triple {
+        HostTriple::X86_64AppleDarwin => "macos",
+        HostTriple::Aarch64AppleDarwin => "macos-arm64",
+        HostTriple::X86_64UnknownLinuxGnu => "linux-amd64",
+        HostTriple::Aarch64UnknownLinuxGnu => "linux-arm64",
+        HostTriple::X86_64PcWindowsMsvc | HostTriple::X86_64PcWindowsGnu => "win64",
+    }
+}
+
+/// Returns the components recorded in the state of the toolchain.
+pub fn recorded_components(toolchain_state: &ToolchainState) -> Vec<Component> {
+    toolchain_state
+        .components
+        .iter()
+        .filter_map(|name| name.parse().ok())
+        .collect()
+}
+
+/// Regenerates the export file of the toolchain, and the ones of its targets, with its current
+/// exports.
+pub fn update_export_file(toolchain_state: &ToolchainState) -> Result<(), Error> {
+    let shell = Shell::from_installation(toolchain_state);
+    if let Some(export_file) = &toolchain_state.export_file {
+        create_export_file(export_file, &toolchain_state.exports, shell)?;
+    }
+    for (export_file, exports) in target_export_files(toolchain_state) {
+        create_export_file(&export_file, &exports, shell)?;
+    }
+    Ok(())
+}
+
+/// Installs the components on top of the toolchain, adding their exports to its export file.
+pub async fn add_components(name: &str, components: &[Component]) -> Result<(), Error> {
+    let mut state = State::load()?;
+    if !state.toolchains.contains_key(name) {
+        return Err(Error::MissingToolchain(name.to_string()));
+    }
+    let toolchain_dir = get_toolchain_dir(name);
+    let host_triple = get_host_triple(None)?;
+    let options = DownloadOptions::default();
+
+    for component in components {
+        let tool = Tool::new(*component, &host_triple, &toolchain_dir);
+        let changes = tool.plan(&options);
+        let exports = tool.install(&options).await?;
+        let toolchain_state = state.toolchain(name);
+        // Recorded once installed, its files are not newer than the record
+        toolchain_state.record_component(ComponentRecord::new(
+            &tool.name(),
+            tool.version(),
+            &changes,
+        ));
+        for export in exports {
+            if !toolchain_state.exports.contains(&export) {
+                toolchain_state.exports.push(export);
+            }
+        }
+        if !toolchain_state.components.contains(&component.to_string()) {
+            toolchain_state.components.push(component.to_string());
+        }
+        // Record every component once installed, a later one may fail
+        update_export_file(toolchain_state)?;
+        state.save()?;
+        info!("Component '{}' added to '{}'", component, name);
+    }
+    restrict_permissions(&toolchain_dir.join(COMPONENTS_DIR)).map_err(Error::IoError)?;
+
+    Ok(())
+}
+
+/// Removes the components from the toolchain, and their exports from its export file.
+pub async fn remove_components(name: &str, components: &[Component]) -> Result<(), Error> {
+    let mut state = State::load()?;
+    if !state.toolchains.contains_key(name) {
+        return Err(Error::MissingToolchain(name.to_string()));
+    }
+    let toolchain_dir = get_toolchain_dir(name);
+    let host_triple = get_host_triple(None)?;
+
+    for component in components {
+        let toolchain_state = state.toolchain(name);
+        if !toolchain_state.components.contains(&component.to_string()) {
+            warn!("Component '{}' is not installed in '{}'", component, name);
+            continue;
+        }
+        let tool = Tool::new(*component, &host_triple, &toolchain_dir);
+        tool.uninstall().await?;
+        let exports = tool.exports();
+        toolchain_state
+            .exports
+            .retain(|export| !exports.contains(export));
+        toolchain_state
+            .components
+            .retain(|installed| *installed != component.to_string());
+        toolchain_state.forget_component(&tool.name());
+        update_export_file(toolchain_state)?;
+        state.save()?;
+        info!("Component '{}' removed from '{}'", component, name);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        host_triple::HostTriple,
+        toolchain::components::{Component, Tool},
+    };
+    use std::path::Path;
+
+    #[test]
+    fn test_component_artifacts() {
+        let toolchain_dir = Path::new("/home/user/.rustup/toolchains/esp");
+        let gdb = Tool::new(
+            Component::Gdb,
+            &HostTriple::X86_64UnknownLinuxGnu,
+            toolchain_dir,
+        );
+        assert_eq!(
+            gdb.artifacts()[0].url,
+            "https://github.com/espressif/binutils-gdb/releases/download/esp-gdb-v12.1_20231023/xtensa-esp-elf-gdb-12.1_20231023-x86_64-linux-gnu.tar.gz"
+        );
+        let qemu = Tool::new(
+            Component::Qemu,
+            &HostTriple::Aarch64AppleDarwin,
+            toolchain_dir,
+        );
+        assert_eq!(
+            qemu.artifacts()[1].url,
+            "https://github.com/espressif/qemu/releases/download/esp-develop-8.1.3-20231206/qemu-riscv32-softmmu-esp_develop_8.1.3_20231206-aarch64-apple-darwin.tar.xz"
+        );
+        assert_eq!(
+            qemu.artifacts()[1].bin_path,
+            toolchain_dir.join("components/qemu/riscv32/qemu/bin")
+        );
+        let openocd = Tool::new(
+            Component::Openocd,
+            &HostTriple::X86_64UnknownLinuxGnu,
+            toolchain_dir,
+        );
+        assert_eq!(
+            openocd.artifacts()[0].url,
+            "https://github.com/espressif/openocd-esp32/releases/download/v0.12.0-esp32-20230921/openocd-esp32-linux-amd64-0.12.0-esp32-20230921.tar.gz"
+        );
+        let sccache = Tool::new(
+            Component::Sccache,
+            &HostTriple::X86_64UnknownLinuxGnu,
+            toolchain_dir,
+        );
+        assert!(sccache.artifacts().is_empty());
+        assert_eq!(sccache.crate_name().as_deref(), Some("sccache"));
+        assert_eq!("probe-rs".parse::<Component>().unwrap(), Component::ProbeRs);
+    }
+}
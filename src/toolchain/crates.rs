@@ -0,0 +1,243 @@
+//! Extra crates installation.
+
+use crate::{
+    error::Error,
+    host_info::find_executable,
+    toolchain::{
+        fetch::proxy_env, progress::Heartbeat, rust::get_cargo_home, Change, DownloadOptions,
+        Installable,
+    },
+};
+use async_trait::async_trait;
+use log::{info, warn};
+use std::{
+    env,
+    fs::read_to_string,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+/// Crate installed with `--probe-rs`.
+pub const PROBE_RS: &str = "probe-rs";
+
+/// Memory, in bytes, required by every job compiling a crate from source.
+const MEMORY_PER_JOB: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Serializes the crate installations on hosts without enough memory to run them in parallel.
+static CONSTRAINED_INSTALL_LOCK: Mutex<()> = Mutex::new(());
+
+/// Parses the available memory, in bytes, of a `/proc/meminfo` file.
+fn parse_available_memory(meminfo: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Returns the available memory of the host, in bytes, if it can be detected.
+fn get_available_memory() -> Option<u64> {
+    parse_available_memory(&read_to_string("/proc/meminfo").ok()?)
+}
+
+/// Returns the number of jobs to compile crates with, if the memory does not allow one job per CPU.
+fn constrained_jobs(available_memory: u64, cpus: usize) -> Option<usize> {
+    let jobs = (available_memory / MEMORY_PER_JOB).max(1) as usize;
+    (jobs < cpus).then_some(jobs)
+}
+
+#[derive(Debug, Clone)]
+pub struct Crate {
+    /// Crate name.
+    pub name: String,
+    /// Features enabled when installing the crate.
+    pub features: Vec<String>,
+    /// Whether an installed crate is updated, 'cargo install' skips it when it is already current.
+    pub update: bool,
+}
+
+impl Crate {
+    /// Create a crate instance.
+    pub fn new(name: &str) -> Self {
+        Crate {
+            name: name.to_string(),
+            features: vec![],
+            update: false,
+        }
+    }
+
+    /// Create a crate instance installed with the features.
+    pub fn with_features(name: &str, features: &[&str]) -> Self {
+        Crate {
+            name: name.to_string(),
+            features: features.iter().map(|feature| feature.to_string()).collect(),
+            update: false,
+        }
+    }
+
+    /// Gets the path of the binary of the crate in the cargo home.
+    fn bin_path(&self) -> PathBuf {
+        get_cargo_home()
+            .join("bin")
+            .join(format!("{}{}", self.name, env::consts::EXE_SUFFIX))
+    }
+
+    /// Returns the arguments of cargo installing the crate, with the number of jobs the memory
+    /// allows, or with cargo-binstall.
+    fn install_args(&self, jobs: Option<usize>, binstall: bool) -> Vec<String> {
+        if binstall {
+            return vec!["binstall".to_string(), "-y".to_string(), self.name.clone()];
+        }
+        let mut args = vec!["install".to_string(), self.name.clone()];
+        if !self.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(self.features.join(","));
+        }
+        if let Some(jobs) = jobs {
+            args.push("--jobs".to_string());
+            args.push(jobs.to_string());
+        }
+        args
+    }
+
+    /// Returns true if the binary of the crate is already installed in the cargo home.
+    fn is_installed(&self) -> bool {
+        self.bin_path().exists()
+    }
+
+    /// Uninstalls the crate, if it is installed.
+    pub fn uninstall(&self) -> Result<(), Error> {
+        if !self.is_installed() {
+            return Ok(());
+        }
+        if !Command::new("cargo")
+            .args(["uninstall", &self.name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?
+            .success()
+        {
+            return Err(Error::UninstallCrate(self.name.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Installable for Crate {
+    async fn install(&self, options: &DownloadOptions) -> Result<Vec<String>, Error> {
+        if self.is_installed() && !self.update {
+            warn!(
+                "Crate '{}' is already installed. Reusing this installation",
+                self.name
+            );
+            return Ok(vec![]);
+        }
+
+        if self.is_installed() {
+            info!("Updating '{}' crate", self.name);
+        } else {
+            info!("Installing '{}' crate", self.name);
+        }
+        let available_memory = get_available_memory();
+        let jobs = available_memory
+            .and_then(|memory| constrained_jobs(memory, DownloadOptions::default_jobs()));
+        // The prebuilt binaries of cargo-binstall are built with the default features only
+        let binstall = jobs.is_some()
+            && self.features.is_empty()
+            && find_executable("cargo-binstall").is_some();
+        if let (Some(jobs), Some(memory)) = (jobs, available_memory) {
+            let memory = memory / (1024 * 1024);
+            if binstall {
+                warn!(
+                    "Only {} MB of memory are available, installing the '{}' binary with cargo-binstall instead of compiling it",
+                    memory, self.name
+                );
+            } else {
+                warn!(
+                    "Only {} MB of memory are available, compiling '{}' with {} jobs. Consider installing cargo-binstall to install prebuilt binaries instead",
+                    memory, self.name, jobs
+                );
+            }
+        }
+        let args = self.install_args(jobs, binstall);
+        let _heartbeat = Heartbeat::start(
+            &format!("the '{}' crate", self.name),
+            options.heartbeat_interval,
+        );
+        // The installation blocks, and so does waiting for the other constrained ones
+        let success = tokio::task::spawn_blocking(move || {
+            let _lock = jobs.map(|_| CONSTRAINED_INSTALL_LOCK.lock().unwrap());
+            Command::new("cargo")
+                .args(&args)
+                .envs(proxy_env())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map(|status| status.success())
+        })
+        .await
+        .unwrap()?;
+        if !success {
+            return Err(Error::InstallCrate(self.name.clone()));
+        }
+
+        Ok(vec![]) // No exports
+    }
+
+    fn name(&self) -> String {
+        format!("Crate ({})", self.name)
+    }
+
+    fn plan(&self, _options: &DownloadOptions) -> Vec<Change> {
+        if self.is_installed() && !self.update {
+            return vec![Change::Reuse {
+                path: self.bin_path(),
+            }];
+        }
+        let mut command = format!("cargo install {}", self.name);
+        if !self.features.is_empty() {
+            command.push_str(&format!(" --features {}", self.features.join(",")));
+        }
+        vec![Change::Run { command }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::toolchain::crates::{constrained_jobs, parse_available_memory, Crate, PROBE_RS};
+
+    #[test]
+    fn test_parse_available_memory() {
+        let meminfo = "MemTotal:        2014512 kB\nMemFree:          120456 kB\nMemAvailable:    1048576 kB\n";
+        assert_eq!(parse_available_memory(meminfo), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_available_memory("MemTotal: 2014512 kB"), None);
+    }
+
+    #[test]
+    fn test_install_args() {
+        let probe_rs = Crate::with_features(PROBE_RS, &["cli"]);
+        assert_eq!(
+            probe_rs.install_args(Some(2), false),
+            ["install", "probe-rs", "--features", "cli", "--jobs", "2"]
+        );
+        assert_eq!(
+            Crate::new("ldproxy").install_args(Some(1), true),
+            ["binstall", "-y", "ldproxy"]
+        );
+        assert_eq!(
+            Crate::new("ldproxy").install_args(None, false),
+            ["install", "ldproxy"]
+        );
+    }
+
+    #[test]
+    fn test_constrained_jobs() {
+        let gigabyte = 1024 * 1024 * 1024;
+        assert_eq!(constrained_jobs(gigabyte, 4), Some(1));
+        assert_eq!(constrained_jobs(5 * gigabyte, 4), Some(2));
+        assert_eq!(constrained_jobs(8 * gigabyte, 4), None);
+        assert_eq!(constrained_jobs(64 * gigabyte, 4), None);
+    }
+}
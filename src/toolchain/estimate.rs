@@ -0,0 +1,194 @@
+//! Estimate of the download size and disk usage of an installation, made before it starts.
+
+use crate::{clean::format_size, error::Error, toolchain::Change};
+use serde::Serialize;
+use std::fmt;
+
+/// Installed size of the archives, relative to their compressed size, by extension.
+const EXPANSION_RATIOS: [(&str, u64); 3] = [(".tar.xz", 5), (".tar.gz", 3), (".zip", 3)];
+/// Units of the sizes, in the decimal multiples '--confirm-above' is given in.
+const UNITS: [(&str, u64); 5] = [
+    ("TB", 1_000_000_000_000),
+    ("GB", 1_000_000_000),
+    ("MB", 1_000_000),
+    ("KB", 1_000),
+    ("B", 1),
+];
+
+/// Download size and disk usage of a component.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Estimate {
+    /// Name of the component.
+    pub name: String,
+    /// Bytes to download.
+    pub download: u64,
+    /// Estimated bytes used on disk once installed.
+    pub installed: u64,
+    /// Whether some of the sizes are unknown (e.g. crates installed by cargo), and not included.
+    pub partial: bool,
+}
+
+impl Estimate {
+    /// Estimates the component from the changes installing it.
+    ///
+    /// `size_of` returns the size of the archive a URL downloads, `cached_size` the size of its
+    /// copy in the cache, which is not downloaded again.
+    pub fn new(
+        name: &str,
+        changes: &[Change],
+        size_of: impl Fn(&str) -> Option<u64>,
+        cached_size: impl Fn(&str) -> Option<u64>,
+    ) -> Self {
+        let mut estimate = Self {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        for change in changes {
+            match change {
+                Change::Download { url, .. } => {
+                    let cached = cached_size(url);
+                    match cached.or_else(|| size_of(url)).filter(|size| *size > 0) {
+                        Some(size) => {
+                            if cached.is_none() {
+                                estimate.download += size;
+                            }
+                            estimate.installed += size * expansion_ratio(url);
+                        }
+                        None => estimate.partial = true,
+                    }
+                }
+                Change::Run { .. } => estimate.partial = true,
+                _ => {}
+            }
+        }
+        estimate
+    }
+
+    /// Sums the estimates of the components.
+    pub fn total(estimates: &[Estimate]) -> Self {
+        estimates.iter().fold(
+            Self {
+                name: "Total".to_string(),
+                ..Default::default()
+            },
+            |total, estimate| Self {
+                download: total.download + estimate.download,
+                installed: total.installed + estimate.installed,
+                partial: total.partial || estimate.partial,
+                ..total
+            },
+        )
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at_least = if self.partial { "at least " } else { "" };
+        write!(
+            f,
+            "{}: {at_least}{} to download, about {} on disk",
+            self.name,
+            format_size(self.download),
+            format_size(self.installed)
+        )
+    }
+}
+
+/// Returns the installed size of the archive downloaded from the URL, relative to its size.
+fn expansion_ratio(url: &str) -> u64 {
+    EXPANSION_RATIOS
+        .iter()
+        .find(|(extension, _)| url.ends_with(extension))
+        .map_or(1, |(_, ratio)| *ratio)
+}
+
+/// Parses a size in bytes, or in decimal multiples of bytes (e.g. '500MB' or '2GB').
+pub fn parse_size(size: &str) -> Result<u64, Error> {
+    let size = size.trim();
+    let invalid = || Error::InvalidSize(size.to_string());
+    let upper = size.to_uppercase();
+    let (amount, multiple) = UNITS
+        .iter()
+        .find_map(|(unit, multiple)| Some((upper.strip_suffix(unit)?, *multiple)))
+        .unwrap_or((&upper, 1));
+    let amount: u64 = amount.trim().parse().map_err(|_| invalid())?;
+    amount.checked_mul(multiple).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::toolchain::{
+        estimate::{parse_size, Estimate},
+        Change,
+    };
+    use std::path::PathBuf;
+
+    fn download(url: &str) -> Change {
+        Change::Download {
+            url: url.to_string(),
+            destination: PathBuf::from("/esp"),
+        }
+    }
+
+    #[test]
+    fn test_estimate() {
+        let size_of = |url: &str| match url {
+            "https://example.com/llvm.tar.xz" => Some(100),
+            "https://example.com/gcc.tar.gz" => Some(50),
+            _ => None,
+        };
+        let cached_size = |url: &str| (url == "https://example.com/gcc.tar.gz").then_some(50);
+
+        let llvm = Estimate::new(
+            "LLVM",
+            &[download("https://example.com/llvm.tar.xz")],
+            size_of,
+            cached_size,
+        );
+        assert_eq!(
+            (llvm.download, llvm.installed, llvm.partial),
+            (100, 500, false)
+        );
+        let gcc = Estimate::new(
+            "GCC",
+            &[download("https://example.com/gcc.tar.gz")],
+            size_of,
+            cached_size,
+        );
+        assert_eq!((gcc.download, gcc.installed, gcc.partial), (0, 150, false));
+        let rust = Estimate::new(
+            "rust",
+            &[
+                download("https://example.com/rust-src.tar.xz"),
+                Change::Run {
+                    command: "rustup toolchain install nightly".to_string(),
+                },
+            ],
+            size_of,
+            cached_size,
+        );
+        assert_eq!((rust.download, rust.installed, rust.partial), (0, 0, true));
+
+        let total = Estimate::total(&[llvm, gcc, rust]);
+        assert_eq!(
+            (total.download, total.installed, total.partial),
+            (100, 650, true)
+        );
+        assert_eq!(
+            total.to_string(),
+            "Total: at least 100 B to download, about 650 B on disk"
+        );
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("500MB").unwrap(), 500_000_000);
+        assert_eq!(parse_size("2gb").unwrap(), 2_000_000_000);
+        assert_eq!(parse_size("1 KB").unwrap(), 1_000);
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("10B").unwrap(), 10);
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("1.5GB").is_err());
+        assert!(parse_size("99999999999TB").is_err());
+    }
+}
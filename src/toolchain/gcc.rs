@@ -3,18 +3,24 @@
 use crate::{
     error::Error,
     host_triple::HostTriple,
-    toolchain::{download_file, Installable},
+    toolchain::{
+        download_file, prune_documentation,
+        releases::{asset_url, GCC_REPOSITORY},
+        Change, DownloadOptions, Installable,
+    },
 };
 use async_trait::async_trait;
 use log::{debug, info, warn};
 use miette::Result;
-use std::path::{Path, PathBuf};
 #[cfg(windows)]
 use std::{env, fs::File};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
 use tokio::fs::remove_dir_all;
 
-const DEFAULT_GCC_REPOSITORY: &str = "https://github.com/espressif/crosstool-NG/releases/download";
-const DEFAULT_GCC_RELEASE: &str = "13.2.0_20230928";
+pub const DEFAULT_GCC_RELEASE: &str = "13.2.0_20230928";
 pub const RISCV_GCC: &str = "riscv32-esp-elf";
 pub const XTENSA_GCC: &str = "xtensa-esp-elf";
 
@@ -53,15 +59,9 @@ impl Gcc {
             path,
         }
     }
-}
-
-#[async_trait]
-impl Installable for Gcc {
-    async fn install(&self) -> Result<Vec<String>, Error> {
-        let extension = get_artifact_extension(&self.host_triple);
-        info!("Installing GCC ({})", self.arch);
-        debug!("GCC path: {}", self.path.display());
 
+    /// Returns true if the GCC toolchain is already installed.
+    fn is_installed(&self) -> bool {
         #[cfg(unix)]
         let is_installed = self.path.exists();
         #[cfg(windows)]
@@ -70,33 +70,52 @@ impl Installable for Gcc {
             .join(&self.arch)
             .join(DEFAULT_GCC_RELEASE)
             .exists();
+        is_installed
+    }
 
-        if is_installed {
+    /// Gets the URL of the GCC toolchain for the host.
+    fn dist_url(&self) -> String {
+        let gcc_file = format!(
+            "{}-{}-{}.{}",
+            self.arch,
+            DEFAULT_GCC_RELEASE,
+            get_arch(&self.host_triple).unwrap(),
+            get_artifact_extension(&self.host_triple)
+        );
+        asset_url(
+            GCC_REPOSITORY,
+            &format!("esp-{DEFAULT_GCC_RELEASE}"),
+            &gcc_file,
+        )
+    }
+}
+
+#[async_trait]
+impl Installable for Gcc {
+    async fn install(&self, options: &DownloadOptions) -> Result<Vec<String>, Error> {
+        let extension = get_artifact_extension(&self.host_triple);
+        info!("Installing GCC ({})", self.arch);
+        debug!("GCC path: {}", self.path.display());
+
+        if self.is_installed() {
             warn!(
                 "Previous installation of GCC exists in: '{}'. Reusing this installation",
                 &self.path.display()
             );
         } else {
-            let gcc_file = format!(
-                "{}-{}-{}.{}",
-                self.arch,
-                DEFAULT_GCC_RELEASE,
-                get_arch(&self.host_triple).unwrap(),
-                extension
-            );
-            let gcc_dist_url = format!(
-                "{}/esp-{}/{}",
-                DEFAULT_GCC_REPOSITORY, DEFAULT_GCC_RELEASE, gcc_file
-            );
             download_file(
-                gcc_dist_url,
+                self.dist_url(),
                 &format!("{}.{}", &self.arch, extension),
                 &self.path.display().to_string(),
                 true,
                 false,
+                options,
             )
             .await?;
         }
+        if options.minimal {
+            prune_documentation(&self.path)?;
+        }
         let mut exports: Vec<String> = Vec::new();
 
         #[cfg(windows)]
@@ -122,6 +141,46 @@ impl Installable for Gcc {
     fn name(&self) -> String {
         format!("GCC ({})", self.arch)
     }
+
+    fn plan(&self, _options: &DownloadOptions) -> Vec<Change> {
+        let install = if self.is_installed() {
+            Change::Reuse {
+                path: self.path.clone(),
+            }
+        } else {
+            Change::Download {
+                url: self.dist_url(),
+                destination: self.path.clone(),
+            }
+        };
+        vec![install, Change::path(&self.get_bin_path())]
+    }
+
+    fn version(&self) -> Option<String> {
+        Some(DEFAULT_GCC_RELEASE.to_string())
+    }
+}
+
+/// Returns true if a GCC toolchain of the version espup installs is available in the PATH.
+pub fn is_system_gcc_compatible(arch: &str) -> bool {
+    let gcc_version = DEFAULT_GCC_RELEASE.split('_').next().unwrap();
+    match Command::new(format!("{arch}-gcc"))
+        .arg("--version")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout);
+            debug!(
+                "System GCC ({}): {}",
+                arch,
+                version.lines().next().unwrap_or_default()
+            );
+            version.contains(gcc_version)
+        }
+        _ => false,
+    }
 }
 
 /// Gets the name of the GCC arch based on the host triple.
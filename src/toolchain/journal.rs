@@ -0,0 +1,379 @@
+//! Journal of the installations in progress, to roll back the ones that fail.
+//!
+//! The journal records the paths an installation creates before it starts, and is removed once it
+//! completes. A failed installation removes them, and so does the next installation of the
+//! toolchain when espup was interrupted before it could. The toolchains the installation moves out
+//! of the way, to back them up or replace them, are moved back. The rustup targets and crates it
+//! installs are not rolled back.
+
+use crate::{backup::Backup, error::Error, gc::now, paths::get_espup_home, toolchain::Change};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{create_dir_all, read_to_string, remove_dir, remove_dir_all, remove_file, rename, write},
+    path::{Path, PathBuf},
+};
+
+/// Name of the directory, under the espup home, storing the journals.
+const JOURNALS_DIR: &str = "journals";
+
+/// Journal of an installation in progress.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Journal {
+    /// Xtensa Rust toolchain name.
+    pub name: String,
+    /// Time the installation started, in seconds since the Unix epoch.
+    pub started_at: u64,
+    /// Files and directories the installation creates, which did not exist when it started.
+    pub created: Vec<PathBuf>,
+    /// Toolchains the installation moved out of the way, in the order they were moved.
+    #[serde(default)]
+    pub moved: Vec<MovedPath>,
+}
+
+/// Path moved out of the way by an installation, moved back when it is rolled back.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MovedPath {
+    /// Original path.
+    pub from: PathBuf,
+    /// Path it was moved to.
+    pub to: PathBuf,
+    /// Directory holding it, removed once it is moved back (e.g. its backup).
+    pub holder: Option<PathBuf>,
+    /// Whether it is replaced by the installation, and removed once it completes.
+    pub replaced: bool,
+}
+
+/// Gets the journal file of the installation of the toolchain.
+pub fn get_journal_file(name: &str) -> PathBuf {
+    get_espup_home()
+        .join(JOURNALS_DIR)
+        .join(format!("{name}.json"))
+}
+
+impl Journal {
+    /// Returns the journal of the installation making the changes, recording the paths that do
+    /// not exist yet.
+    ///
+    /// Paths inside another created path are left out, they are removed with it.
+    pub fn new<'a>(name: &str, changes: impl IntoIterator<Item = &'a Change>) -> Self {
+        let mut created: Vec<PathBuf> = Vec::new();
+        for change in changes {
+            let path = match change {
+                Change::Download { destination, .. } => destination,
+                Change::Link { path, .. } | Change::Write { path } => path,
+                _ => continue,
+            };
+            if path.symlink_metadata().is_err()
+                && !created.iter().any(|other| path.starts_with(other))
+            {
+                created.retain(|other| !other.starts_with(path));
+                created.push(path.clone());
+            }
+        }
+        Self {
+            name: name.to_string(),
+            started_at: now(),
+            created,
+            moved: Vec::new(),
+        }
+    }
+
+    /// Records the backup of the toolchain directory, moved back if the installation fails.
+    pub fn record_backup(&mut self, toolchain_dir: &Path, backup: &Backup) -> Result<(), Error> {
+        self.record_backup_in(toolchain_dir, backup, &get_journal_file(&self.name))
+    }
+
+    /// Records the backup of the toolchain directory in the journal file.
+    fn record_backup_in(
+        &mut self,
+        toolchain_dir: &Path,
+        backup: &Backup,
+        journal_file: &Path,
+    ) -> Result<(), Error> {
+        self.moved.push(MovedPath {
+            from: toolchain_dir.to_path_buf(),
+            to: backup.toolchain_dir(),
+            holder: Some(backup.path.clone()),
+            replaced: false,
+        });
+        self.save_to(journal_file)
+    }
+
+    /// Moves the directory replaced by the installation out of the way, until it completes.
+    pub fn replace(&mut self, path: &Path) -> Result<(), Error> {
+        self.replace_in(path, &get_journal_file(&self.name))
+    }
+
+    /// Moves the directory replaced by the installation next to the journal file.
+    fn replace_in(&mut self, path: &Path, journal_file: &Path) -> Result<(), Error> {
+        let to = journal_file.with_extension(format!("replaced-{}", self.moved.len()));
+        if let Some(parent) = to.parent() {
+            create_dir_all(parent)
+                .map_err(|_| Error::CreateDirectory(parent.display().to_string()))?;
+        }
+        if rename(path, &to).is_err() {
+            // A directory on another file system cannot be moved, only removed
+            warn!(
+                "'{}' cannot be moved, it is removed and will not be restored if the installation fails",
+                path.display()
+            );
+            return remove_dir_all(path)
+                .map_err(|_| Error::RemoveDirectory(path.display().to_string()));
+        }
+        self.moved.push(MovedPath {
+            from: path.to_path_buf(),
+            to,
+            holder: None,
+            replaced: true,
+        });
+        self.save_to(journal_file)
+    }
+
+    /// Loads the journal of an installation of the toolchain that did not complete, if any.
+    pub fn load(name: &str) -> Result<Option<Self>, Error> {
+        Self::load_from(&get_journal_file(name))
+    }
+
+    /// Loads the journal from the file, if it exists.
+    fn load_from(path: &Path) -> Result<Option<Self>, Error> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let journal =
+            serde_json::from_str(&read_to_string(path)?).map_err(|_| Error::SerializeJson)?;
+        Ok(Some(journal))
+    }
+
+    /// Saves the journal, before the installation starts.
+    pub fn save(&self) -> Result<(), Error> {
+        self.save_to(&get_journal_file(&self.name))
+    }
+
+    /// Saves the journal to the file.
+    fn save_to(&self, path: &Path) -> Result<(), Error> {
+        debug!("Saving the installation journal to '{}'", path.display());
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)
+                .map_err(|_| Error::CreateDirectory(parent.display().to_string()))?;
+        }
+        let contents = serde_json::to_string_pretty(self).map_err(|_| Error::SerializeJson)?;
+        write(path, contents)?;
+        Ok(())
+    }
+
+    /// Removes the directories the installation replaced, and then the journal, once the
+    /// installation completed.
+    pub fn commit(&self) -> Result<(), Error> {
+        self.commit_from(&get_journal_file(&self.name))
+    }
+
+    /// Removes the replaced directories, and then the journal file.
+    fn commit_from(&self, path: &Path) -> Result<(), Error> {
+        for moved in self.moved.iter().filter(|moved| moved.replaced) {
+            if moved.to.exists() {
+                remove_dir_all(&moved.to)
+                    .map_err(|_| Error::RemoveDirectory(moved.to.display().to_string()))?;
+            }
+        }
+        remove_journal_file(path)
+    }
+
+    /// Removes the paths created by the installation, moves back the ones it moved, and then
+    /// removes the journal.
+    ///
+    /// The journal is kept when a path cannot be removed, so the next installation of the
+    /// toolchain removes what was left behind.
+    pub fn rollback(&self) -> Result<(), Error> {
+        self.rollback_from(&get_journal_file(&self.name))
+    }
+
+    /// Rolls the installation back, and then removes the journal file.
+    fn rollback_from(&self, path: &Path) -> Result<(), Error> {
+        self.remove_created()?;
+        self.restore_moved()?;
+        remove_journal_file(path)
+    }
+
+    /// Moves back the paths moved out of the way, most recent first, replacing what the
+    /// installation left in their place.
+    fn restore_moved(&self) -> Result<(), Error> {
+        for moved in self.moved.iter().rev() {
+            if !moved.to.exists() {
+                continue;
+            }
+            info!("Restoring '{}'", moved.from.display());
+            remove_path(&moved.from)?;
+            rename(&moved.to, &moved.from).map_err(|_| {
+                Error::MoveDirectory(
+                    moved.to.display().to_string(),
+                    moved.from.display().to_string(),
+                )
+            })?;
+            if let Some(holder) = &moved.holder {
+                remove_dir_all(holder)
+                    .map_err(|_| Error::RemoveDirectory(holder.display().to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the paths created by the installation, most recent first.
+    fn remove_created(&self) -> Result<(), Error> {
+        for path in self.created.iter().rev() {
+            if path.symlink_metadata().is_ok() {
+                info!("Rolling back '{}'", path.display());
+                remove_path(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the journal file, if it exists.
+fn remove_journal_file(path: &Path) -> Result<(), Error> {
+    if path.exists() {
+        remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Removes the file, link or directory, if it exists.
+fn remove_path(path: &Path) -> Result<(), Error> {
+    let Ok(metadata) = path.symlink_metadata() else {
+        return Ok(());
+    };
+    if metadata.is_dir() {
+        remove_dir_all(path).map_err(|_| Error::RemoveDirectory(path.display().to_string()))
+    } else {
+        // Directory links are files on Unix, and directories on Windows
+        remove_file(path)
+            .or_else(|_| remove_dir(path))
+            .map_err(|_| Error::RemoveDirectory(path.display().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backup::Backup,
+        error::Error,
+        toolchain::{journal::Journal, Change},
+    };
+    use std::fs::{create_dir_all, read_to_string, rename, write};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_journal() {
+        let temp_dir = TempDir::new().unwrap();
+        let toolchain_dir = temp_dir.path().join("esp");
+        let llvm_dir = temp_dir.path().join("llvm");
+        create_dir_all(&llvm_dir).unwrap();
+        let changes = vec![
+            Change::Download {
+                url: "https://example.com/rust.tar.xz".to_string(),
+                destination: toolchain_dir.clone(),
+            },
+            Change::Download {
+                url: "https://example.com/gcc.tar.xz".to_string(),
+                destination: toolchain_dir.join("xtensa-esp-elf"),
+            },
+            Change::Reuse {
+                path: llvm_dir.clone(),
+            },
+            Change::Write {
+                path: temp_dir.path().join("export-esp.sh"),
+            },
+        ];
+        let journal = Journal::new("esp", &changes);
+        assert_eq!(
+            journal.created,
+            vec![toolchain_dir.clone(), temp_dir.path().join("export-esp.sh")]
+        );
+
+        let file = temp_dir.path().join("journals").join("esp.json");
+        journal.save_to(&file).unwrap();
+        assert_eq!(Journal::load_from(&file).unwrap(), Some(journal.clone()));
+
+        // The installation failed after Xtensa Rust was installed
+        create_dir_all(toolchain_dir.join("bin")).unwrap();
+        write(toolchain_dir.join("bin").join("rustc"), "rustc").unwrap();
+        journal.remove_created().unwrap();
+        assert!(!toolchain_dir.exists());
+        assert!(llvm_dir.exists());
+        journal.rollback_from(&file).unwrap();
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn test_rollback_existing_toolchain() {
+        let temp_dir = TempDir::new().unwrap();
+        let toolchain_dir = temp_dir.path().join("esp");
+        let rustc = toolchain_dir.join("bin").join("rustc");
+        let changes = vec![Change::Download {
+            url: "https://example.com/rust.tar.xz".to_string(),
+            destination: toolchain_dir.clone(),
+        }];
+        let file = temp_dir.path().join("journals").join("esp.json");
+
+        // The existing toolchain is overwritten
+        create_dir_all(rustc.parent().unwrap()).unwrap();
+        write(&rustc, "old").unwrap();
+        let mut journal = Journal::new("esp", &changes);
+        assert!(journal.created.is_empty());
+        journal.save_to(&file).unwrap();
+        journal.replace_in(&toolchain_dir, &file).unwrap();
+        assert!(!toolchain_dir.exists());
+        assert_eq!(Journal::load_from(&file).unwrap(), Some(journal.clone()));
+        // The installation failed after Xtensa Rust was partially installed
+        create_dir_all(rustc.parent().unwrap()).unwrap();
+        write(&rustc, "new").unwrap();
+        journal.rollback_from(&file).unwrap();
+        assert_eq!(read_to_string(&rustc).unwrap(), "old");
+        assert!(!journal.moved[0].to.exists());
+        assert!(!file.exists());
+
+        // The existing toolchain is backed up
+        let backup = Backup {
+            name: "esp".to_string(),
+            timestamp: 0,
+            path: temp_dir.path().join("backups").join("esp-0"),
+        };
+        create_dir_all(&backup.path).unwrap();
+        rename(&toolchain_dir, backup.toolchain_dir()).unwrap();
+        let mut journal = Journal::new("esp", &changes);
+        journal.save_to(&file).unwrap();
+        journal
+            .record_backup_in(&toolchain_dir, &backup, &file)
+            .unwrap();
+        create_dir_all(rustc.parent().unwrap()).unwrap();
+        write(&rustc, "new").unwrap();
+        journal.rollback_from(&file).unwrap();
+        assert_eq!(read_to_string(&rustc).unwrap(), "old");
+        assert!(!backup.path.exists());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_failed_rollback() {
+        let temp_dir = TempDir::new().unwrap();
+        let export_file = temp_dir.path().join("export-esp.sh");
+        write(&export_file, "export PATH").unwrap();
+        // Not even root can remove '/proc/self'
+        let journal = Journal {
+            name: "esp".to_string(),
+            created: vec!["/proc/self".into(), export_file.clone()],
+            ..Default::default()
+        };
+        let file = temp_dir.path().join("journals").join("esp.json");
+        journal.save_to(&file).unwrap();
+
+        assert!(matches!(
+            journal.rollback_from(&file),
+            Err(Error::RemoveDirectory(path)) if path == "/proc/self"
+        ));
+        assert!(!export_file.exists());
+        // The next installation rolls back what is left
+        assert_eq!(Journal::load_from(&file).unwrap(), Some(journal));
+    }
+}
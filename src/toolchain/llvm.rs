@@ -1,27 +1,38 @@
 //! LLVM Toolchain source and installation tools.
 
+#[cfg(unix)]
+use crate::state::get_espup_home;
 use crate::{
     error::Error,
     host_triple::HostTriple,
-    toolchain::{download_file, rust::RE_EXTENDED_SEMANTIC_VERSION, Installable},
+    state::State,
+    toolchain::{
+        download_file, prune_documentation,
+        releases::{asset_url, LLVM_REPOSITORY},
+        rust::XtensaRustVersion,
+        Change, DownloadOptions, Installable,
+    },
 };
 use async_trait::async_trait;
-#[cfg(unix)]
-use directories::BaseDirs;
-use log::{info, warn};
+use log::{debug, info, warn};
 use miette::Result;
-use regex::Regex;
-use std::path::{Path, PathBuf};
 #[cfg(windows)]
-use std::{env, fs::File};
+use std::env;
 #[cfg(unix)]
 use std::{fs::create_dir_all, os::unix::fs::symlink};
-use tokio::fs::remove_dir_all;
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+#[cfg(unix)]
+use tokio::fs::remove_file;
+use tokio::fs::{remove_dir, remove_dir_all};
 
-const DEFAULT_LLVM_REPOSITORY: &str = "https://github.com/espressif/llvm-project/releases/download";
 const DEFAULT_LLVM_15_VERSION: &str = "esp-15.0.0-20221201";
 const DEFAULT_LLVM_16_VERSION: &str = "esp-16.0.0-20230516";
 pub const CLANG_NAME: &str = "xtensa-esp32-elf-clang";
+/// LLVM versions installed by espup.
+pub const LLVM_VERSIONS: [&str; 2] = [DEFAULT_LLVM_15_VERSION, DEFAULT_LLVM_16_VERSION];
 
 #[derive(Debug, Clone, Default)]
 pub struct Llvm {
@@ -51,7 +62,10 @@ impl Llvm {
         }
     }
 
-    /// Gets the binary path.
+    /// Gets the path of the directory containing libclang.
+    ///
+    /// On Unix, including macOS, bindgen expects `LIBCLANG_PATH` to point at the directory
+    /// containing the shared library, not at the library itself like on Windows.
     fn get_lib_path(&self) -> String {
         match std::cfg!(windows) {
             true => format!("{}/esp-clang/bin", self.path.to_str().unwrap()).replace('/', "\\"),
@@ -68,32 +82,26 @@ impl Llvm {
         }
     }
 
+    /// Gets the LLVM version used by the Xtensa Rust version.
+    pub fn version_for(xtensa_rust_version: &XtensaRustVersion) -> &'static str {
+        // Use LLVM 15 for versions 1.69.0.0 and below
+        if *xtensa_rust_version <= XtensaRustVersion::new(1, 69, 0, 0) {
+            DEFAULT_LLVM_15_VERSION
+        } else {
+            DEFAULT_LLVM_16_VERSION
+        }
+    }
+
     /// Create a new instance with default values and proper toolchain version.
+    ///
+    /// Every LLVM version is installed in its own directory under `llvm_root`.
     pub fn new(
-        toolchain_path: &Path,
+        llvm_root: &Path,
         host_triple: &HostTriple,
         extended: bool,
-        xtensa_rust_version: &str,
+        xtensa_rust_version: &XtensaRustVersion,
     ) -> Result<Self, Error> {
-        let re_extended: Regex = Regex::new(RE_EXTENDED_SEMANTIC_VERSION).unwrap();
-        let (major, minor, patch, subpatch) = match re_extended.captures(xtensa_rust_version) {
-            Some(version) => (
-                version.get(1).unwrap().as_str().parse::<u8>().unwrap(),
-                version.get(2).unwrap().as_str().parse::<u8>().unwrap(),
-                version.get(3).unwrap().as_str().parse::<u8>().unwrap(),
-                version.get(4).unwrap().as_str().parse::<u8>().unwrap(),
-            ),
-            None => return Err(Error::InvalidVersion(xtensa_rust_version.to_string())),
-        };
-
-        // Use LLVM 15 for versions 1.69.0.0 and below
-        let version = if (major == 1 && minor == 69 && patch == 0 && subpatch == 0)
-            || (major == 1 && minor < 69)
-        {
-            DEFAULT_LLVM_15_VERSION.to_string()
-        } else {
-            DEFAULT_LLVM_16_VERSION.to_string()
-        };
+        let version = Self::version_for(xtensa_rust_version).to_string();
 
         let mut file_name = format!(
             "llvm-{}-{}.tar.xz",
@@ -103,11 +111,8 @@ impl Llvm {
         if !extended {
             file_name = format!("libs_{file_name}");
         }
-        let repository_url = format!("{DEFAULT_LLVM_REPOSITORY}/{version}/{file_name}");
-        #[cfg(unix)]
-        let path = toolchain_path.join(CLANG_NAME).join(&version);
-        #[cfg(windows)]
-        let path = toolchain_path.join(CLANG_NAME);
+        let repository_url = asset_url(LLVM_REPOSITORY, &version, &file_name);
+        let path = llvm_root.join(&version);
 
         Ok(Self {
             extended,
@@ -120,50 +125,41 @@ impl Llvm {
     }
 
     /// Uninstall LLVM toolchain.
-    pub async fn uninstall(toolchain_path: &Path) -> Result<(), Error> {
+    pub async fn uninstall(llvm_root: &Path) -> Result<(), Error> {
         info!("Uninstalling Xtensa LLVM");
-        let llvm_path = toolchain_path.join(CLANG_NAME);
-        if llvm_path.exists() {
+        if llvm_root.exists() {
             #[cfg(windows)]
             if cfg!(windows) {
                 env::remove_var("LIBCLANG_PATH");
                 env::remove_var("CLANG_PATH");
-                let mut updated_path = env::var("PATH").unwrap().replace(
-                    &format!(
-                        "{}\\{}\\esp-clang\\bin;",
-                        llvm_path.display().to_string().replace('/', "\\"),
-                        DEFAULT_LLVM_15_VERSION,
-                    ),
-                    "",
-                );
-                updated_path = updated_path.replace(
-                    &format!(
-                        "{}\\{}\\esp-clang\\bin;",
-                        llvm_path.display().to_string().replace('/', "\\"),
-                        DEFAULT_LLVM_16_VERSION,
-                    ),
-                    "",
-                );
-                updated_path = updated_path.replace(
-                    &format!(
-                        "{}\\esp-clang\\bin;",
-                        llvm_path.display().to_string().replace('/', "\\"),
-                    ),
-                    "",
-                );
-                env::set_var("PATH", updated_path);
+                let llvm_root = llvm_root.display().to_string().replace('/', "\\");
+                let updated_path: Vec<String> = env::var("PATH")
+                    .unwrap_or_default()
+                    .split(';')
+                    .filter(|path| !path.starts_with(&llvm_root))
+                    .map(str::to_string)
+                    .collect();
+                env::set_var("PATH", updated_path.join(";"));
+            }
+            // The root may be a custom directory, only remove the versions installed by espup
+            for version in LLVM_VERSIONS {
+                let llvm_path = llvm_root.join(version);
+                if llvm_path.exists() {
+                    remove_dir_all(&llvm_path)
+                        .await
+                        .map_err(|_| Error::RemoveDirectory(llvm_path.display().to_string()))?;
+                }
+            }
+            if read_dir(llvm_root)?.next().is_none() {
+                remove_dir(llvm_root)
+                    .await
+                    .map_err(|_| Error::RemoveDirectory(llvm_root.display().to_string()))?;
             }
-            remove_dir_all(&llvm_path)
-                .await
-                .map_err(|_| Error::RemoveDirectory(llvm_path.display().to_string()))?;
             #[cfg(unix)]
             if cfg!(unix) {
-                let espup_dir = BaseDirs::new().unwrap().home_dir().join(".espup");
-
-                if espup_dir.exists() {
-                    remove_dir_all(espup_dir.display().to_string())
-                        .await
-                        .map_err(|_| Error::RemoveDirectory(espup_dir.display().to_string()))?;
+                let llvm_symlink_path = get_espup_home().join("esp-clang");
+                if llvm_symlink_path.is_symlink() {
+                    remove_file(&llvm_symlink_path).await?;
                 }
             }
         }
@@ -171,17 +167,21 @@ impl Llvm {
     }
 }
 
+/// Returns the directory containing the LLVM versions of the toolchain, as recorded during the installation.
+pub fn get_llvm_root(name: &str, toolchain_dir: &Path) -> PathBuf {
+    State::load()
+        .ok()
+        .and_then(|state| state.toolchains.get(name)?.llvm_path.clone())
+        .unwrap_or_else(|| toolchain_dir.join(CLANG_NAME))
+}
+
 #[async_trait]
 impl Installable for Llvm {
-    async fn install(&self) -> Result<Vec<String>, Error> {
+    async fn install(&self, options: &DownloadOptions) -> Result<Vec<String>, Error> {
         let mut exports: Vec<String> = Vec::new();
-        println!("LLVM Path: {}", self.path.to_str().unwrap());
+        debug!("LLVM path: {}", self.path.display());
 
-        #[cfg(unix)]
-        let is_installed = Path::new(&self.path).exists();
-        #[cfg(windows)]
-        let is_installed = self.path.join(&self.version).exists();
-        if is_installed {
+        if self.path.exists() {
             warn!(
                 "Previous installation of LLVM exists in: '{}'. Reusing this installation",
                 self.path.to_str().unwrap()
@@ -194,13 +194,16 @@ impl Installable for Llvm {
                 self.path.to_str().unwrap(),
                 true,
                 false,
+                options,
             )
             .await?;
         }
+        if options.minimal {
+            prune_documentation(&self.path)?;
+        }
         // Set environment variables.
         #[cfg(windows)]
         if cfg!(windows) {
-            File::create(self.path.join(&self.version))?;
             let libclang_dll = format!("{}\\libclang.dll", self.get_lib_path());
             exports.push(format!("$Env:LIBCLANG_PATH = \"{}\"", libclang_dll));
             exports.push(format!(
@@ -213,7 +216,7 @@ impl Installable for Llvm {
         #[cfg(unix)]
         if cfg!(unix) {
             exports.push(format!("export LIBCLANG_PATH=\"{}\"", self.get_lib_path()));
-            let espup_dir = BaseDirs::new().unwrap().home_dir().join(".espup");
+            let espup_dir = get_espup_home();
 
             if !espup_dir.exists() {
                 create_dir_all(espup_dir.display().to_string())
@@ -225,12 +228,20 @@ impl Installable for Llvm {
                     .await
                     .map_err(|_| Error::RemoveDirectory(llvm_symlink_path.display().to_string()))?;
             }
-            info!(
-                "Creating symlink between '{}' and '{}'",
-                self.get_lib_path(),
-                llvm_symlink_path.display()
-            );
-            symlink(self.get_lib_path(), llvm_symlink_path)?;
+            if options.copy_symlinks {
+                info!(
+                    "Skipping the symlink between '{}' and '{}', use LIBCLANG_PATH instead",
+                    self.get_lib_path(),
+                    llvm_symlink_path.display()
+                );
+            } else {
+                info!(
+                    "Creating symlink between '{}' and '{}'",
+                    self.get_lib_path(),
+                    llvm_symlink_path.display()
+                );
+                symlink(self.get_lib_path(), llvm_symlink_path)?;
+            }
         }
 
         if self.extended {
@@ -249,4 +260,44 @@ impl Installable for Llvm {
     fn name(&self) -> String {
         "LLVM".to_string()
     }
+
+    #[cfg_attr(windows, allow(unused_variables))]
+    fn plan(&self, options: &DownloadOptions) -> Vec<Change> {
+        let mut changes = vec![if self.path.exists() {
+            Change::Reuse {
+                path: self.path.clone(),
+            }
+        } else {
+            Change::Download {
+                url: self.repository_url.clone(),
+                destination: self.path.clone(),
+            }
+        }];
+        #[cfg(windows)]
+        changes.extend([
+            Change::variable(
+                "LIBCLANG_PATH",
+                &format!("{}\\libclang.dll", self.get_lib_path()),
+            ),
+            Change::path(&self.get_lib_path()),
+        ]);
+        #[cfg(unix)]
+        {
+            changes.push(Change::variable("LIBCLANG_PATH", &self.get_lib_path()));
+            if !options.copy_symlinks {
+                changes.push(Change::Link {
+                    path: get_espup_home().join("esp-clang"),
+                    target: PathBuf::from(self.get_lib_path()),
+                });
+            }
+        }
+        if self.extended {
+            changes.push(Change::variable("CLANG_PATH", &self.get_bin_path()));
+        }
+        changes
+    }
+
+    fn version(&self) -> Option<String> {
+        Some(self.version.clone())
+    }
 }
@@ -0,0 +1,307 @@
+//! Removal of what an installation recorded in its manifest, the components of its state.
+//!
+//! The files installed by a component are never newer than its record, a newer one has been added
+//! or modified by the user since, and the path holding it is kept.
+
+use crate::{
+    env::{export_file_contents, target_export_files},
+    error::Error,
+    shell::Shell,
+    state::{ComponentRecord, State, ToolchainState},
+    toolchain::rust::get_cargo_home,
+};
+use std::{
+    fs::{read_dir, read_link, read_to_string, remove_dir, remove_dir_all, remove_file},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// Paths an uninstallation removes, and the ones it keeps as they were modified.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Removal {
+    /// Files and directories to remove.
+    pub removed: Vec<PathBuf>,
+    /// Files and directories modified since their installation, which are kept.
+    pub skipped: Vec<PathBuf>,
+    /// Recorded paths of the components that are not removed, kept even inside removed paths.
+    pub kept: Vec<PathBuf>,
+}
+
+/// Returns the recorded paths of the toolchain, and its export files, to remove.
+///
+/// The paths also recorded by the `others` toolchains are kept, unless they are links into the
+/// removed paths. The binaries of the crates are left to `cargo uninstall`.
+pub fn plan_removal(toolchain_state: &ToolchainState, others: &State) -> Removal {
+    let mut removal = plan_components_removal(toolchain_state, others, |_| true);
+    let shell = Shell::from_installation(toolchain_state);
+    let export_files = toolchain_state
+        .export_file
+        .iter()
+        .map(|export_file| (export_file.clone(), toolchain_state.exports.clone()))
+        .chain(target_export_files(toolchain_state));
+    for (export_file, exports) in export_files {
+        let is_used = others.toolchains.values().any(|other| {
+            other.export_file.as_ref() == Some(&export_file)
+                || target_export_files(other)
+                    .iter()
+                    .any(|(other_file, _)| *other_file == export_file)
+        });
+        if export_file.exists() && !is_used {
+            let contents = export_file_contents(&exports, shell);
+            if read_to_string(&export_file).is_ok_and(|written| written == contents) {
+                removal.removed.push(export_file);
+            } else {
+                removal.skipped.push(export_file);
+            }
+        }
+    }
+    removal
+}
+
+/// Returns the recorded paths of the selected components of the toolchain to remove, like
+/// [`plan_removal`] does.
+pub fn plan_components_removal(
+    toolchain_state: &ToolchainState,
+    others: &State,
+    selected: impl Fn(&ComponentRecord) -> bool,
+) -> Removal {
+    let recorded: Vec<&Path> = toolchain_state
+        .installed
+        .iter()
+        .flat_map(|record| record.paths.iter().map(PathBuf::as_path))
+        .collect();
+    let is_shared = |path: &Path| {
+        others.toolchains.values().any(|other| {
+            other.installed.iter().any(|record| {
+                record
+                    .paths
+                    .iter()
+                    .any(|other_path| path.starts_with(other_path))
+            })
+        })
+    };
+    let cargo_bin = get_cargo_home().join("bin");
+    let mut removal = Removal::default();
+    let mut shared = Vec::new();
+    for record in &toolchain_state.installed {
+        if !selected(record) {
+            removal.kept.extend(record.paths.iter().cloned());
+            continue;
+        }
+        for path in &record.paths {
+            if path.symlink_metadata().is_err()
+                || path.starts_with(&cargo_bin)
+                || removal.removed.contains(path)
+                || removal.skipped.contains(path)
+            {
+                continue;
+            }
+            if is_shared(path) {
+                shared.push(path);
+                continue;
+            }
+            let nested: Vec<&Path> = recorded
+                .iter()
+                .copied()
+                .filter(|other| *other != path && other.starts_with(path))
+                .collect();
+            if is_modified(path, record.installed_at, &nested) {
+                removal.skipped.push(path.clone());
+            } else {
+                removal.removed.push(path.clone());
+            }
+        }
+    }
+    for path in shared {
+        let dangling = read_link(path).is_ok_and(|target| {
+            removal
+                .removed
+                .iter()
+                .any(|removed| target.starts_with(removed))
+        });
+        if dangling {
+            removal.removed.push(path.clone());
+        }
+    }
+    // A path recorded by both a removed component and a kept one is kept
+    removal.removed.retain(|path| !removal.kept.contains(path));
+    removal
+}
+
+/// Returns the crates installed with the toolchain that no other toolchain was installed with.
+pub fn removable_crates<'a>(toolchain_state: &'a ToolchainState, others: &State) -> Vec<&'a str> {
+    toolchain_state
+        .crates
+        .iter()
+        .filter(|name| {
+            !others
+                .toolchains
+                .values()
+                .any(|other| other.crates.contains(name))
+        })
+        .map(String::as_str)
+        .collect()
+}
+
+/// Returns true if a file under the path, outside of the `excluded` ones, was modified after the
+/// time, in seconds since the Unix epoch.
+///
+/// Directories are not checked themselves, adding a component into one updates its time.
+fn is_modified(path: &Path, since: u64, excluded: &[&Path]) -> bool {
+    let Ok(metadata) = path.symlink_metadata() else {
+        return false;
+    };
+    if metadata.is_dir() {
+        return read_dir(path).is_ok_and(|entries| {
+            entries.filter_map(|entry| entry.ok()).any(|entry| {
+                let path = entry.path();
+                !excluded.contains(&path.as_path()) && is_modified(&path, since, excluded)
+            })
+        });
+    }
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .is_some_and(|modified| modified.as_secs() > since)
+}
+
+/// Removes the paths to remove, but the skipped and kept ones they contain.
+pub fn remove_paths(removal: &Removal) -> Result<(), Error> {
+    let kept: Vec<PathBuf> = removal
+        .skipped
+        .iter()
+        .chain(&removal.kept)
+        .cloned()
+        .collect();
+    for path in &removal.removed {
+        remove_path(path, &kept)?;
+    }
+    Ok(())
+}
+
+/// Removes the file or directory, but the `kept` paths it contains.
+fn remove_path(path: &Path, kept: &[PathBuf]) -> Result<(), Error> {
+    let Ok(metadata) = path.symlink_metadata() else {
+        return Ok(());
+    };
+    if !metadata.is_dir() {
+        // Directory links are files on Unix, and directories on Windows
+        return remove_file(path)
+            .or_else(|_| remove_dir(path))
+            .map_err(|_| Error::RemoveDirectory(path.display().to_string()));
+    }
+    if !kept.iter().any(|kept| kept.starts_with(path)) {
+        return remove_dir_all(path)
+            .map_err(|_| Error::RemoveDirectory(path.display().to_string()));
+    }
+    for entry in read_dir(path)? {
+        let entry = entry?.path();
+        if !kept.contains(&entry) {
+            remove_path(&entry, kept)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the directory if there is nothing left in it.
+pub fn remove_empty_dir(path: &Path) -> Result<(), Error> {
+    if read_dir(path).is_ok_and(|mut entries| entries.next().is_none()) {
+        remove_dir(path).map_err(|_| Error::RemoveDirectory(path.display().to_string()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        env::export_file_contents,
+        gc::now,
+        shell::Shell,
+        state::{ComponentRecord, State, ToolchainState},
+        toolchain::manifest::{
+            plan_components_removal, plan_removal, removable_crates, remove_paths, Removal,
+        },
+    };
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_removal() {
+        let temp_dir = TempDir::new().unwrap();
+        let toolchain_dir = temp_dir.path().join("esp");
+        let gcc_dir = toolchain_dir.join("xtensa-esp-elf");
+        create_dir_all(toolchain_dir.join("bin")).unwrap();
+        create_dir_all(gcc_dir.join("bin")).unwrap();
+        write(toolchain_dir.join("bin").join("rustc"), "rustc").unwrap();
+        write(gcc_dir.join("bin").join("xtensa-esp-elf-gcc"), "gcc").unwrap();
+        let export_file = temp_dir.path().join("export-esp.sh");
+        let exports = vec!["export LIBCLANG_PATH=\"/esp/llvm/lib\"".to_string()];
+        write(&export_file, export_file_contents(&exports, Shell::Posix)).unwrap();
+        let record = |name: &str, path: &std::path::Path, installed_at: u64| ComponentRecord {
+            name: name.to_string(),
+            version: None,
+            paths: vec![path.to_path_buf(), temp_dir.path().join("missing")],
+            installed_at,
+        };
+        let toolchain_state = ToolchainState {
+            export_file: Some(export_file.clone()),
+            shell: Some("bash".to_string()),
+            exports,
+            crates: vec!["espflash".to_string(), "ldproxy".to_string()],
+            // The files of GCC are newer than its record, they were modified
+            installed: vec![
+                record("Xtensa Rust", &toolchain_dir, now() + 60),
+                record("GCC", &gcc_dir, 0),
+            ],
+            ..Default::default()
+        };
+        let mut others = State::default();
+        others.toolchain("esp-1.74").crates = vec!["ldproxy".to_string()];
+
+        let removal = plan_removal(&toolchain_state, &others);
+        assert_eq!(
+            removal,
+            Removal {
+                removed: vec![toolchain_dir.clone(), export_file.clone()],
+                skipped: vec![gcc_dir.clone()],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            removable_crates(&toolchain_state, &others),
+            vec!["espflash"]
+        );
+
+        remove_paths(&removal).unwrap();
+        assert!(!toolchain_dir.join("bin").exists());
+        assert!(!export_file.exists());
+        assert!(gcc_dir.join("bin").join("xtensa-esp-elf-gcc").exists());
+
+        // The paths of the components that are not removed are kept
+        create_dir_all(toolchain_dir.join("bin")).unwrap();
+        let removal = plan_components_removal(&toolchain_state, &State::default(), |record| {
+            record.name == "Xtensa Rust"
+        });
+        assert_eq!(removal.removed, vec![toolchain_dir.clone()]);
+        assert_eq!(
+            removal.kept,
+            vec![gcc_dir.clone(), temp_dir.path().join("missing")]
+        );
+        remove_paths(&removal).unwrap();
+        assert!(!toolchain_dir.join("bin").exists());
+        assert!(gcc_dir.join("bin").join("xtensa-esp-elf-gcc").exists());
+
+        // The paths recorded by another toolchain are kept
+        others.toolchain("esp-1.74").installed = vec![record("GCC", &gcc_dir, 0)];
+        write(&export_file, "export PATH=\"/custom:$PATH\"").unwrap();
+        assert_eq!(
+            plan_removal(&toolchain_state, &others),
+            Removal {
+                removed: vec![toolchain_dir],
+                skipped: vec![export_file],
+                ..Default::default()
+            }
+        );
+    }
+}
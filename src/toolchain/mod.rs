@@ -1,62 +1,280 @@
 //! Different toolchains source and installation tools.
 
 #[cfg(windows)]
-use crate::env::set_env;
+use crate::env::{clean_env, set_env};
+#[cfg(feature = "network")]
+use crate::toolchain::fetch::blocking_client;
 use crate::{
-    cli::InstallOpts,
-    env::{create_export_file, get_export_file, print_post_install_msg},
+    backup::{backup_toolchain, create_backup, get_backup_path},
+    clean::{confirm, dir_size, format_size},
+    env::{
+        create_export_file, export_variable, get_export_file, get_legacy_export_file,
+        get_target_export_file, print_post_install_msg, target_export_files,
+        update_legacy_export_file, Environment,
+    },
     error::Error,
+    esp_idf::end_of_life_warning,
+    gc::now,
+    guide::{chip_tips, next_steps, rust_lld_config},
+    host_info::check_libc_compatibility,
     host_triple::get_host_triple,
+    keys::{list_keys, verify_signature, TrustedKey, SIGNATURE_EXTENSION},
+    paths::{
+        get_esp_idf_dir, get_espup_home, get_idf_tools_path, log_paths, synced_folder_warning,
+    },
+    permissions::restrict_permissions,
+    profile::{fetch_profile, verify_sha256, Profile},
+    project::get_project_toolchain,
+    shell::Shell,
+    state::{ComponentRecord, State},
     targets::Target,
     toolchain::{
-        gcc::{Gcc, RISCV_GCC, XTENSA_GCC},
-        llvm::Llvm,
-        rust::{check_rust_installation, get_rustup_home, RiscVTarget, XtensaRust},
+        bundle::Bundle,
+        cache::{get_cached, get_cached_size, sha256, store},
+        components::{recorded_components, update_export_file, Component, Tool},
+        crates::{Crate, PROBE_RS},
+        credentials::set_credential_hosts,
+        estimate::Estimate,
+        fetch::{
+            default_fetcher, fetch_with_retry, get_partial_downloads_dir, is_insecure, is_offline,
+            retry_policy, set_insecure, set_max_download_size, set_offline, set_proxy,
+            set_retry_policy, Fetch, RetryPolicy,
+        },
+        gcc::{is_system_gcc_compatible, uninstall_gcc_toolchains, Gcc, RISCV_GCC, XTENSA_GCC},
+        journal::Journal,
+        llvm::{get_llvm_root, Llvm, CLANG_NAME, LLVM_VERSIONS},
+        manifest::{
+            plan_components_removal, plan_removal, removable_crates, remove_empty_dir,
+            remove_paths, Removal,
+        },
+        options::{ExistingToolchain, IdfExportMode, InstallOptions, OutputFormat},
+        progress::{CancellationToken, NoProgress, Progress, ProgressEvent},
+        releases::{asset_sha256, asset_size, parse_asset_url},
+        rust::{
+            check_rust_installation, get_cargo_home, get_default_toolchain_dir, get_rustup_home,
+            get_rustup_override, get_toolchain_dir, is_corrupted_toolchain, link_toolchain,
+            unlink_toolchain, RiscVTarget, XtensaRust, LATEST_VERSION,
+        },
     },
 };
 use async_trait::async_trait;
+use bytes::Bytes;
 use flate2::bufread::GzDecoder;
 use log::{debug, info, warn};
 use miette::Result;
-use reqwest::{blocking::Client, header};
-use retry::{delay::Fixed, retry};
+#[cfg(feature = "network")]
+use reqwest::header;
+#[cfg(feature = "network")]
+use retry::{retry, OperationResult};
+use serde::Serialize;
 use std::{
-    env,
-    fs::{create_dir_all, remove_file, File},
-    io::{copy, Write},
+    collections::HashSet,
+    env, fmt,
+    fs::{create_dir_all, read_dir, read_to_string, remove_file, rename, File},
+    io::{self, copy, BufRead, IsTerminal, Read, Write},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
+use strum::IntoEnumIterator;
 use tar::Archive;
-use tokio::{fs::remove_dir_all, sync::mpsc};
-use tokio_retry::{strategy::FixedInterval, Retry};
+use tokio::{
+    fs::remove_dir_all,
+    sync::{mpsc, Semaphore},
+};
 use xz2::read::XzDecoder;
 use zip::ZipArchive;
 
+pub mod bundle;
+pub mod cache;
+pub mod components;
+pub mod crates;
+pub mod credentials;
+pub mod estimate;
+pub mod fetch;
 pub mod gcc;
+pub mod journal;
 pub mod llvm;
+pub mod manifest;
+pub mod options;
+pub mod progress;
+pub mod releases;
 pub mod rust;
 
+/// Nightly toolchain installed when none is specified.
+pub const DEFAULT_NIGHTLY_VERSION: &str = "nightly";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InstallMode {
     Install,
     Update,
 }
 
+/// Settings shared by every download and extraction.
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Directory where the downloaded archives are cached and reused from.
+    pub cache_dir: Option<PathBuf>,
+    /// Whether the cache directory is shared by several users (see '--shared-cache').
+    pub shared_cache: bool,
+    /// Replace the symlinks of the archives, and the ones created by espup, with copies.
+    pub copy_symlinks: bool,
+    /// Log every extracted file instead of periodic summaries.
+    pub verbose_extract: bool,
+    /// Directory where the downloaded archives are kept.
+    pub keep_downloads: Option<PathBuf>,
+    /// Limits the number of archives extracted at the same time.
+    pub extraction_slots: Arc<Semaphore>,
+    /// Limits the number of artifacts downloaded at the same time.
+    pub download_slots: Arc<Semaphore>,
+    /// Source of the downloaded artifacts.
+    pub fetcher: Arc<dyn Fetch>,
+    /// Receives the progress of the downloads and extractions.
+    pub progress: Arc<dyn Progress>,
+    /// Aborts the downloads and extractions when cancelled.
+    pub cancellation: CancellationToken,
+    /// Keys the detached signatures of the downloads are verified against, if they are verified.
+    pub trusted_keys: Option<Arc<Vec<TrustedKey>>>,
+    /// Removes the documentation of the installed components (see '--profile-minimal').
+    pub minimal: bool,
+    /// Interval of the heartbeats logged by the steps running silently for a long time, if any.
+    pub heartbeat_interval: Option<Duration>,
+    /// Fails on the downloads that cannot be verified instead of warning (see '--strict').
+    pub strict: bool,
+}
+
+impl DownloadOptions {
+    /// Number of parallel downloads used when none is specified.
+    pub const DEFAULT_DOWNLOAD_JOBS: usize = 4;
+
+    /// Returns the number of parallel jobs used when none is specified.
+    pub fn default_jobs() -> usize {
+        thread::available_parallelism().map_or(1, NonZeroUsize::get)
+    }
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            cache_dir: None,
+            shared_cache: false,
+            copy_symlinks: false,
+            verbose_extract: false,
+            keep_downloads: None,
+            extraction_slots: Arc::new(Semaphore::new(Self::default_jobs())),
+            download_slots: Arc::new(Semaphore::new(Self::DEFAULT_DOWNLOAD_JOBS)),
+            fetcher: default_fetcher(),
+            progress: Arc::new(NoProgress),
+            cancellation: CancellationToken::default(),
+            trusted_keys: None,
+            minimal: false,
+            heartbeat_interval: None,
+            strict: false,
+        }
+    }
+}
+
 #[async_trait]
 pub trait Installable {
     /// Install some application, returning a vector of any required exports
-    async fn install(&self) -> Result<Vec<String>, Error>;
+    async fn install(&self, options: &DownloadOptions) -> Result<Vec<String>, Error>;
     /// Returns the name of the toolchain being installeds
     fn name(&self) -> String;
+    /// Returns the version being installed, if it is known before the installation.
+    fn version(&self) -> Option<String> {
+        None
+    }
+    /// Returns the changes installing the application would make, without making them.
+    fn plan(&self, options: &DownloadOptions) -> Vec<Change>;
+}
+
+/// Change made to the system by an installation or uninstallation, as reported by dry runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum Change {
+    /// Downloads an archive and extracts it into the destination.
+    Download { url: String, destination: PathBuf },
+    /// Keeps a previous installation.
+    Reuse { path: PathBuf },
+    /// Runs a command.
+    Run { command: String },
+    /// Deletes a file or directory.
+    Remove { path: PathBuf },
+    /// Moves a directory.
+    Move { from: PathBuf, to: PathBuf },
+    /// Creates a symlink pointing at the target.
+    Link { path: PathBuf, target: PathBuf },
+    /// Exports an environment variable in the export file, `PATH` ones being prepended to it.
+    Export { variable: String, value: String },
+    /// Writes a file.
+    Write { path: PathBuf },
+}
+
+impl Change {
+    /// Exports the environment variable.
+    pub fn variable(variable: &str, value: &str) -> Self {
+        Change::Export {
+            variable: variable.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// Prepends the directory to the `PATH`.
+    pub fn path(dir: &str) -> Self {
+        Self::variable("PATH", dir)
+    }
+
+    /// Returns the file or directory the change installs, if any.
+    pub fn installed_path(&self) -> Option<&Path> {
+        match self {
+            Change::Download { destination, .. } => Some(destination),
+            Change::Reuse { path } | Change::Link { path, .. } | Change::Write { path } => {
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Download { url, destination } => {
+                write!(f, "Download '{url}' into '{}'", destination.display())
+            }
+            Change::Reuse { path } => write!(f, "Reuse '{}'", path.display()),
+            Change::Run { command } => write!(f, "Run '{command}'"),
+            Change::Remove { path } => write!(f, "Remove '{}'", path.display()),
+            Change::Move { from, to } => {
+                write!(f, "Move '{}' to '{}'", from.display(), to.display())
+            }
+            Change::Link { path, target } => {
+                write!(f, "Link '{}' to '{}'", path.display(), target.display())
+            }
+            Change::Export { variable, value } if variable == "PATH" => {
+                write!(f, "Prepend '{value}' to PATH")
+            }
+            Change::Export { variable, value } => write!(f, "Set {variable} to '{value}'"),
+            Change::Write { path } => write!(f, "Write '{}'", path.display()),
+        }
+    }
 }
 
 /// Downloads a file from a URL and uncompresses it, if necesary, to the output directory.
+///
+/// Archives are extracted into a temporary sibling directory that is then moved into place, so
+/// an interrupted extraction never leaves an incomplete toolchain in the output directory.
 pub async fn download_file(
     url: String,
     file_name: &str,
     output_directory: &str,
     uncompress: bool,
     strip: bool,
+    options: &DownloadOptions,
 ) -> Result<String, Error> {
+    options.cancellation.check()?;
     let file_path = format!("{output_directory}/{file_name}");
     if Path::new(&file_path).exists() {
         warn!(
@@ -64,204 +282,2092 @@ pub async fn download_file(
             file_path
         );
         remove_file(&file_path)?;
-    } else if !Path::new(&output_directory).exists() {
+    } else if !uncompress && !Path::new(&output_directory).exists() {
         debug!("Creating directory: '{}'", output_directory);
         create_dir_all(output_directory)
             .map_err(|_| Error::CreateDirectory(output_directory.to_string()))?;
     }
-    info!("Downloading '{}'", &file_name);
-    let resp = reqwest::get(&url).await?;
-    let bytes = resp.bytes().await?;
+    let cached = options
+        .cache_dir
+        .as_deref()
+        .and_then(|cache_dir| get_cached(cache_dir, &url));
+    let is_cached = cached.is_some();
+    let bytes = match cached {
+        Some(bytes) => {
+            info!("Using cached '{}'", &file_name);
+            Bytes::from(bytes)
+        }
+        None => {
+            info!("Downloading '{}'", &file_name);
+            if is_insecure() {
+                warn!(
+                    "Downloading '{}' without validating the TLS certificates",
+                    &file_name
+                );
+            }
+            // The components are installed in parallel, bound how many artifacts are downloaded
+            // at the same time
+            let _permit = options.download_slots.acquire().await.unwrap();
+            options.progress.on_event(ProgressEvent::DownloadStarted {
+                file: file_name.to_string(),
+            });
+            fetch_with_retry(options.fetcher.as_ref(), &url, &retry_policy()).await?
+        }
+    };
+    options.progress.on_event(ProgressEvent::DownloadFinished {
+        file: file_name.to_string(),
+        bytes: bytes.len() as u64,
+    });
+    options.cancellation.check()?;
+    // Signed downloads are verified by their signature when no checksum is published
+    let strict = options.strict && options.trusted_keys.is_none();
+    if is_cached {
+        debug!("'{}' was verified before it was cached", file_name);
+    } else if is_offline() {
+        // Offline, the artifacts come from a bundle, verified when it was created
+        debug!("Not verifying the checksum of '{}' offline", file_name);
+    } else if parse_asset_url(&url).is_none() {
+        // Only GitHub releases publish checksums, e.g. rustup-init is downloaded from
+        // static.rust-lang.org over TLS
+        debug!(
+            "'{}' is not a release asset, it has no published checksum",
+            file_name
+        );
+    } else {
+        let checksum_url = url.clone();
+        match tokio::task::spawn_blocking(move || asset_sha256(&checksum_url))
+            .await
+            .unwrap()
+        {
+            Ok(expected_sha256) => {
+                verify_checksum(file_name, &bytes, expected_sha256.as_deref(), strict)?
+            }
+            Err(error) => warn_or_error(
+                strict,
+                format!(
+                    "Failed to get the published checksum of '{file_name}' ({error}), it has not been verified"
+                ),
+            )?,
+        }
+    }
+    if let Some(trusted_keys) = &options.trusted_keys {
+        let signature_url = format!("{url}.{SIGNATURE_EXTENSION}");
+        let signature = fetch_with_retry(options.fetcher.as_ref(), &signature_url, &retry_policy())
+            .await
+            .map_err(|_| Error::MissingSignature(file_name.to_string()))?;
+        verify_signature(file_name, &bytes, &signature, trusted_keys)?;
+    }
     if uncompress {
-        let extension = Path::new(file_name).extension().unwrap().to_str().unwrap();
-        match extension {
-            "zip" => {
-                let mut tmpfile = tempfile::tempfile()?;
-                tmpfile.write_all(&bytes)?;
-                let mut zipfile = ZipArchive::new(tmpfile).unwrap();
-                if strip {
-                    for i in 0..zipfile.len() {
-                        let mut file = zipfile.by_index(i).unwrap();
-                        if !file.name().starts_with("esp/") {
-                            continue;
-                        }
-
-                        let file_path = PathBuf::from(file.name().to_string());
-                        let stripped_name = file_path.strip_prefix("esp/").unwrap();
-                        let outpath = Path::new(output_directory).join(stripped_name);
-
-                        if file.name().ends_with('/') {
-                            create_dir_all(&outpath)?;
-                        } else {
-                            create_dir_all(outpath.parent().unwrap())?;
-                            let mut outfile = File::create(&outpath)?;
-                            copy(&mut file, &mut outfile)?;
-                        }
+        check_archive_signature(file_name, &bytes)?;
+        // Extraction is CPU bound, run it outside of the async runtime and bound how many
+        // archives are extracted at the same time
+        let _permit = options.extraction_slots.acquire().await.unwrap();
+        let bytes = bytes.clone();
+        let file_name = file_name.to_string();
+        let output_directory = output_directory.to_string();
+        let options = options.clone();
+        tokio::task::spawn_blocking(move || {
+            extract_archive(&bytes, &file_name, &output_directory, strip, &options)
+        })
+        .await
+        .unwrap()?;
+    } else {
+        debug!("Creating file: '{}'", file_path);
+        let mut out = File::create(&file_path)?;
+        out.write_all(&bytes)?;
+    }
+    // Only cache artifacts once they are known to be valid archives
+    if let Some(cache_dir) = options.cache_dir.as_deref().filter(|_| !is_cached) {
+        store(cache_dir, &url, &bytes, options.shared_cache)?;
+    }
+    if let Some(keep_downloads) = &options.keep_downloads {
+        keep_download(keep_downloads, &url, file_name, &bytes)?;
+    }
+    Ok(file_path)
+}
+
+/// Verifies the SHA-256 of a download against the published one.
+///
+/// Downloads without a published checksum are only accepted with a warning, unless `strict` is set.
+pub fn verify_checksum(
+    file_name: &str,
+    bytes: &[u8],
+    expected: Option<&str>,
+    strict: bool,
+) -> Result<(), Error> {
+    let Some(expected) = expected else {
+        return warn_or_error(
+            strict,
+            format!("No checksum is published for '{file_name}', it has not been verified"),
+        );
+    };
+    let actual = sha256(bytes)?;
+    if actual != expected.to_lowercase() {
+        return Err(Error::ChecksumMismatch(
+            file_name.to_string(),
+            expected.to_string(),
+            actual,
+        ));
+    }
+    debug!("Verified the checksum of '{}'", file_name);
+    Ok(())
+}
+
+/// Returns an error if the download does not start with the magic bytes of its archive format,
+/// before it is extracted.
+fn check_archive_signature(file_name: &str, bytes: &[u8]) -> Result<(), Error> {
+    let (format, magic): (&str, &[u8]) = match Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("xz") => ("xz", &[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+        Some("gz") => ("gzip", &[0x1f, 0x8b]),
+        Some("zip") => ("zip", b"PK"),
+        _ => return Ok(()),
+    };
+    if bytes.starts_with(magic) {
+        return Ok(());
+    }
+    let start = String::from_utf8_lossy(&bytes[..bytes.len().min(64)]).to_lowercase();
+    let reason = if start.trim_start().starts_with('<') {
+        "the server sent an HTML or XML page".to_string()
+    } else if bytes.is_empty() {
+        "the download is empty".to_string()
+    } else {
+        format!("it is not a {format} archive")
+    };
+    Err(Error::UnexpectedContent(file_name.to_string(), reason))
+}
+
+/// Extracts an archive into a staging directory and moves its contents into the output directory.
+fn extract_archive(
+    bytes: &[u8],
+    file_name: &str,
+    output_directory: &str,
+    strip: bool,
+    options: &DownloadOptions,
+) -> Result<(), Error> {
+    let extension = Path::new(file_name).extension().unwrap().to_str().unwrap();
+    let staging_directory = format!("{output_directory}-{file_name}.tmp");
+    if Path::new(&staging_directory).exists() {
+        debug!(
+            "Removing leftovers of a previous extraction in '{}'",
+            staging_directory
+        );
+        std::fs::remove_dir_all(&staging_directory)
+            .map_err(|_| Error::RemoveDirectory(staging_directory.clone()))?;
+    }
+    create_dir_all(&staging_directory)
+        .map_err(|_| Error::CreateDirectory(staging_directory.clone()))?;
+    let mut progress = ExtractionProgress::new(file_name, options);
+    if let Err(error) = unpack_archive(
+        bytes,
+        extension,
+        &staging_directory,
+        strip,
+        options.copy_symlinks,
+        &mut progress,
+    ) {
+        // Do not leave a partial extraction behind (e.g. after a cancellation)
+        std::fs::remove_dir_all(&staging_directory)
+            .map_err(|_| Error::RemoveDirectory(staging_directory.clone()))?;
+        return Err(error);
+    }
+    progress.finish();
+    move_into_place(Path::new(&staging_directory), Path::new(output_directory))?;
+    Ok(())
+}
+
+/// Unpacks the archive, according to its extension, into the staging directory.
+fn unpack_archive(
+    bytes: &[u8],
+    extension: &str,
+    staging_directory: &str,
+    strip: bool,
+    copy_symlinks: bool,
+    progress: &mut ExtractionProgress,
+) -> Result<(), Error> {
+    match extension {
+        "zip" => {
+            let mut tmpfile = tempfile::tempfile()?;
+            tmpfile.write_all(bytes)?;
+            let mut zipfile = ZipArchive::new(tmpfile).unwrap();
+            progress.total = Some(zipfile.len());
+            for i in 0..zipfile.len() {
+                let mut file = zipfile.by_index(i).unwrap();
+                let Some(file_path) = file.enclosed_name().map(Path::to_path_buf) else {
+                    continue;
+                };
+                let file_path = if strip {
+                    match file_path.strip_prefix("esp/") {
+                        Ok(stripped_name) => stripped_name.to_path_buf(),
+                        Err(_) => continue,
                     }
                 } else {
-                    zipfile.extract(output_directory).unwrap();
+                    file_path
+                };
+                let outpath = Path::new(staging_directory).join(&file_path);
+
+                if file.is_dir() {
+                    create_dir_all(&outpath)?;
+                } else {
+                    create_dir_all(outpath.parent().unwrap())?;
+                    let mut outfile = File::create(&outpath)?;
+                    copy(&mut file, &mut outfile)?;
+                    #[cfg(unix)]
+                    if let Some(mode) = file.unix_mode() {
+                        use std::os::unix::fs::PermissionsExt;
+                        std::fs::set_permissions(&outpath, PermissionsExt::from_mode(mode))?;
+                    }
                 }
+                progress.entry(&file_path, file.size())?;
+            }
+        }
+        "gz" => {
+            debug!("Extracting tar.gz file to '{}'", staging_directory);
+
+            let tarfile = GzDecoder::new(bytes);
+            unpack_tar(
+                Archive::new(tarfile),
+                staging_directory,
+                copy_symlinks,
+                progress,
+            )?;
+        }
+        "xz" => {
+            debug!("Extracting tar.xz file to '{}'", staging_directory);
+            let tarfile = XzDecoder::new(bytes);
+            unpack_tar(
+                Archive::new(tarfile),
+                staging_directory,
+                copy_symlinks,
+                progress,
+            )?;
+        }
+        _ => return Err(Error::UnsuportedFileExtension(extension.to_string())),
+    }
+    Ok(())
+}
+
+/// Name of the manifest describing the kept downloads.
+pub const DOWNLOADS_MANIFEST: &str = "manifest.json";
+
+/// Serializes the updates of the downloads manifest between parallel installations.
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Copies a downloaded archive into the directory and records it in its manifest.
+pub fn keep_download(
+    directory: &Path,
+    url: &str,
+    file_name: &str,
+    bytes: &[u8],
+) -> Result<(), Error> {
+    create_dir_all(directory)
+        .map_err(|_| Error::CreateDirectory(directory.display().to_string()))?;
+    let file_path = directory.join(file_name);
+    debug!("Keeping download in '{}'", file_path.display());
+    File::create(&file_path)?.write_all(bytes)?;
+
+    let _lock = MANIFEST_LOCK.lock().unwrap();
+    let manifest_path = directory.join(DOWNLOADS_MANIFEST);
+    let mut manifest = if manifest_path.exists() {
+        serde_json::from_str(&read_to_string(&manifest_path)?).map_err(|_| Error::SerializeJson)?
+    } else {
+        serde_json::json!({ "artifacts": [] })
+    };
+    let artifacts = manifest["artifacts"]
+        .as_array_mut()
+        .ok_or(Error::SerializeJson)?;
+    artifacts.retain(|artifact| artifact["file"] != file_name);
+    artifacts.push(serde_json::json!({
+        "url": url,
+        "file": file_name,
+        "size": bytes.len(),
+    }));
+    let manifest = serde_json::to_string_pretty(&manifest).map_err(|_| Error::SerializeJson)?;
+    File::create(&manifest_path)?.write_all(manifest.as_bytes())?;
+    Ok(())
+}
+
+/// Number of seconds between extraction progress summaries.
+const EXTRACTION_LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Periodically logs the progress of an extraction, and reports it to the progress handler.
+struct ExtractionProgress {
+    /// Name of the archive being extracted.
+    file_name: String,
+    /// Total number of files of the archive, if known beforehand.
+    total: Option<usize>,
+    /// Whether every extracted file is logged.
+    verbose: bool,
+    handler: Arc<dyn Progress>,
+    cancellation: CancellationToken,
+    files: usize,
+    bytes: u64,
+    start: Instant,
+    last_report: Instant,
+}
+
+impl ExtractionProgress {
+    fn new(file_name: &str, options: &DownloadOptions) -> Self {
+        let now = Instant::now();
+        Self {
+            file_name: file_name.to_string(),
+            total: None,
+            verbose: options.verbose_extract,
+            handler: options.progress.clone(),
+            cancellation: options.cancellation.clone(),
+            files: 0,
+            bytes: 0,
+            start: now,
+            last_report: now,
+        }
+    }
+
+    /// Returns a summary of the extraction so far.
+    fn summary(&self) -> String {
+        let files = match self.total {
+            Some(total) => format!("{}/{} files", self.files, total),
+            None => format!("{} files", self.files),
+        };
+        let megabytes = self.bytes as f64 / 1_000_000.0;
+        let elapsed = self.start.elapsed().as_secs_f64().max(0.001);
+        format!(
+            "{files}, {megabytes:.1} MB ({:.1} MB/s)",
+            megabytes / elapsed
+        )
+    }
+
+    /// Registers an extracted entry, failing if the installation was cancelled.
+    fn entry(&mut self, path: &Path, size: u64) -> Result<(), Error> {
+        self.cancellation.check()?;
+        self.files += 1;
+        self.bytes += size;
+        self.handler.on_event(ProgressEvent::Extracting {
+            file: self.file_name.clone(),
+            files: self.files,
+            bytes: self.bytes,
+        });
+        if self.verbose {
+            info!("Extracted '{}'", path.display());
+        } else if self.last_report.elapsed() >= EXTRACTION_LOG_INTERVAL {
+            self.last_report = Instant::now();
+            info!("Extracting '{}': {}", self.file_name, self.summary());
+        }
+        Ok(())
+    }
+
+    fn finish(&self) {
+        debug!("Extracted '{}': {}", self.file_name, self.summary());
+        self.handler.on_event(ProgressEvent::ExtractionFinished {
+            file: self.file_name.clone(),
+        });
+    }
+}
+
+/// Unpacks a tar archive, reporting the progress of the extraction.
+///
+/// With `copy_symlinks`, symlinks are replaced by copies of their targets once every other
+/// entry has been unpacked, so targets appearing later in the archive are also found.
+fn unpack_tar<R: Read>(
+    mut archive: Archive<R>,
+    output_directory: &str,
+    copy_symlinks: bool,
+    progress: &mut ExtractionProgress,
+) -> Result<(), Error> {
+    let mut symlinks = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        let size = entry.size();
+        if copy_symlinks && entry.header().entry_type().is_symlink() {
+            if let Some(link_name) = entry.link_name()? {
+                symlinks.push((path, link_name.to_path_buf()));
+                continue;
             }
-            "gz" => {
-                debug!("Extracting tar.gz file to '{}'", output_directory);
+        }
+        entry.unpack_in(output_directory)?;
+        progress.entry(&path, size)?;
+    }
 
-                let bytes = bytes.to_vec();
-                let tarfile = GzDecoder::new(bytes.as_slice());
-                let mut archive = Archive::new(tarfile);
-                archive.unpack(output_directory)?;
+    // Symlinks may point to other symlinks, copy them until no more targets are resolved
+    let output_root = Path::new(output_directory)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(output_directory));
+    while !symlinks.is_empty() {
+        let pending = symlinks.len();
+        let mut unresolved = Vec::new();
+        for (path, link_name) in symlinks {
+            let link_path = Path::new(output_directory).join(&path);
+            let Some(parent) = link_path.parent() else {
+                continue;
+            };
+            let target = parent.join(&link_name);
+            if let Ok(target) = target.canonicalize() {
+                // Copying a target outside of the archive would install files of the host
+                if !target.starts_with(&output_root) {
+                    warn!(
+                        "Skipping symlink '{}', its target '{}' is outside of the archive",
+                        path.display(),
+                        link_name.display()
+                    );
+                    continue;
+                }
+                debug!(
+                    "Copying '{}' into '{}' instead of creating a symlink",
+                    target.display(),
+                    link_path.display()
+                );
+                copy_path(&target, &link_path)?;
+                progress.entry(&path, 0)?;
+            } else {
+                unresolved.push((path, link_name));
             }
-            "xz" => {
-                debug!("Extracting tar.xz file to '{}'", output_directory);
-                let bytes = bytes.to_vec();
-                let tarfile = XzDecoder::new(bytes.as_slice());
-                let mut archive = Archive::new(tarfile);
-                archive.unpack(output_directory)?;
+        }
+        if unresolved.len() == pending {
+            for (path, link_name) in unresolved {
+                warn!(
+                    "Skipping symlink '{}', its target '{}' does not exist",
+                    path.display(),
+                    link_name.display()
+                );
             }
-            _ => {
-                return Err(Error::UnsuportedFileExtension(extension.to_string()));
+            break;
+        }
+        symlinks = unresolved;
+    }
+    Ok(())
+}
+
+/// Directories of a `share` directory holding documentation, which the minimal profile removes.
+const DOCUMENTATION_DIRS: [&str; 3] = ["doc", "info", "man"];
+
+/// Removes the documentation installed in the directory, returning the number of bytes freed.
+///
+/// Removes the 'doc', 'info' and 'man' directories of every `share` directory, without following symlinks.
+pub fn prune_documentation(dir: &Path) -> Result<u64, Error> {
+    let mut freed = 0;
+    let Ok(entries) = read_dir(dir) else {
+        return Ok(freed);
+    };
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_name() == "share" {
+            for name in DOCUMENTATION_DIRS {
+                let documentation = path.join(name);
+                if documentation.is_dir() && !documentation.is_symlink() {
+                    freed += dir_size(&documentation);
+                    std::fs::remove_dir_all(&documentation)
+                        .map_err(|_| Error::RemoveDirectory(documentation.display().to_string()))?;
+                }
             }
         }
+        freed += prune_documentation(&path)?;
+    }
+    if freed > 0 {
+        debug!(
+            "Removed {} of documentation from '{}'",
+            format_size(freed),
+            dir.display()
+        );
+    }
+    Ok(freed)
+}
+
+/// Copies a file, or a directory recursively.
+fn copy_path(source: &Path, destination: &Path) -> Result<(), Error> {
+    if source.is_dir() {
+        create_dir_all(destination)
+            .map_err(|_| Error::CreateDirectory(destination.display().to_string()))?;
+        for entry in read_dir(source)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &destination.join(entry.file_name()))?;
+        }
     } else {
-        debug!("Creating file: '{}'", file_path);
-        let mut out = File::create(&file_path)?;
-        out.write_all(&bytes)?;
+        if let Some(parent) = destination.parent() {
+            create_dir_all(parent)
+                .map_err(|_| Error::CreateDirectory(parent.display().to_string()))?;
+        }
+        std::fs::copy(source, destination)?;
+    }
+    Ok(())
+}
+
+/// Checks if symlinks can be created in the directory, or in its closest existing ancestor.
+pub fn supports_symlinks(directory: &Path) -> bool {
+    let Some(directory) = directory.ancestors().find(|ancestor| ancestor.is_dir()) else {
+        return false;
+    };
+    let Ok(probe) = tempfile::tempdir_in(directory) else {
+        return false;
+    };
+    let target = probe.path().join("target");
+    if File::create(&target).is_err() {
+        return false;
+    }
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(&target, probe.path().join("link"));
+    #[cfg(windows)]
+    let result = std::os::windows::fs::symlink_file(&target, probe.path().join("link"));
+    result.is_ok()
+}
+
+/// Moves the contents of the staging directory into the destination.
+///
+/// The whole directory is renamed when the destination does not exist yet, otherwise every
+/// top-level entry is renamed, replacing any previous entry with the same name.
+fn move_into_place(staging_directory: &Path, destination: &Path) -> Result<(), Error> {
+    debug!(
+        "Moving '{}' into '{}'",
+        staging_directory.display(),
+        destination.display()
+    );
+    if !destination.exists() {
+        if let Some(parent) = destination.parent() {
+            create_dir_all(parent)
+                .map_err(|_| Error::CreateDirectory(parent.display().to_string()))?;
+        }
+        rename(staging_directory, destination)?;
+        return Ok(());
+    }
+
+    for entry in read_dir(staging_directory)? {
+        let entry = entry?;
+        let target = destination.join(entry.file_name());
+        if target.is_dir() {
+            std::fs::remove_dir_all(&target)
+                .map_err(|_| Error::RemoveDirectory(target.display().to_string()))?;
+        } else if target.exists() {
+            remove_file(&target)?;
+        }
+        rename(entry.path(), target)?;
+    }
+    std::fs::remove_dir_all(staging_directory)
+        .map_err(|_| Error::RemoveDirectory(staging_directory.display().to_string()))?;
+    Ok(())
+}
+
+/// Paths resolved by an installation, printed by '--print-paths'.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstallPaths {
+    /// Rustup home directory.
+    pub rustup_home: PathBuf,
+    /// Cargo home directory.
+    pub cargo_home: PathBuf,
+    /// Xtensa Rust toolchain directory.
+    pub toolchain_dir: PathBuf,
+    /// LLVM directory, if LLVM is installed.
+    pub llvm_dir: Option<PathBuf>,
+    /// GCC toolchains directories.
+    pub gcc_dirs: Vec<PathBuf>,
+    /// Export file.
+    pub export_file: PathBuf,
+    /// ESP-IDF repository, if `$IDF_PATH` is set.
+    pub idf_path: Option<PathBuf>,
+    /// ESP-IDF tools directory.
+    pub idf_tools_path: PathBuf,
+}
+
+impl InstallPaths {
+    /// Prints the paths, one `name=value` pair per line or as JSON.
+    ///
+    /// Unset paths are left empty, and the GCC directories are joined with the separator of `PATH`.
+    pub fn print(&self, json: bool) -> Result<(), Error> {
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(self).map_err(|_| Error::SerializeJson)?
+            );
+            return Ok(());
+        }
+        let display = |path: &Option<PathBuf>| {
+            path.as_deref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default()
+        };
+        let gcc_dirs = env::join_paths(&self.gcc_dirs)
+            .map(|paths| paths.to_string_lossy().to_string())
+            .unwrap_or_default();
+        println!("rustup_home={}", self.rustup_home.display());
+        println!("cargo_home={}", self.cargo_home.display());
+        println!("toolchain_dir={}", self.toolchain_dir.display());
+        println!("llvm_dir={}", display(&self.llvm_dir));
+        println!("gcc_dirs={gcc_dirs}");
+        println!("export_file={}", self.export_file.display());
+        println!("idf_path={}", display(&self.idf_path));
+        println!("idf_tools_path={}", self.idf_tools_path.display());
+        Ok(())
+    }
+}
+
+/// Returns true if LLVM is required, which is the case for every application but RISC-V no_std ones.
+pub fn requires_llvm(arch: Option<&str>, std: bool) -> bool {
+    arch != Some("riscv") || std
+}
+
+/// Returns the GCC toolchains required to link no_std applications for the targets.
+pub fn required_gcc_archs(targets: &HashSet<Target>) -> Vec<&'static str> {
+    let mut gcc_archs = Vec::new();
+    if targets.iter().any(Target::is_xtensa) {
+        gcc_archs.push(XTENSA_GCC);
+    }
+    // All RISC-V targets use the same GCC toolchain
+    // ESP32S2 and ESP32S3 also install the RISC-V toolchain for their ULP coprocessor
+    if targets.iter().any(|t| t != &Target::ESP32) {
+        gcc_archs.push(RISCV_GCC);
+    }
+    gcc_archs
+}
+
+/// Estimates the download size and disk usage of the components, from the changes installing them.
+async fn estimate_components(
+    plans: Vec<(String, Vec<Change>)>,
+    cache_dir: Option<PathBuf>,
+) -> Vec<Estimate> {
+    tokio::task::spawn_blocking(move || {
+        plans
+            .iter()
+            .map(|(name, changes)| {
+                Estimate::new(name, changes, asset_size, |url| {
+                    cache_dir
+                        .as_deref()
+                        .and_then(|cache_dir| get_cached_size(cache_dir, url))
+                })
+            })
+            .collect()
+    })
+    .await
+    .unwrap()
+}
+
+/// Prints the download size and disk usage of every component, and of the whole installation.
+fn print_estimates(estimates: &[Estimate]) {
+    info!("Estimated download size and disk usage:");
+    for estimate in estimates {
+        info!("  {estimate}");
+    }
+    info!("  {}", Estimate::total(estimates));
+}
+
+/// Asks for confirmation when the installation downloads more than the threshold.
+///
+/// Installations with '--yes' are not asked, the ones that cannot be asked (outside of a terminal,
+/// or with the JSON output) fail.
+fn confirm_download_size(
+    estimates: &[Estimate],
+    threshold: u64,
+    yes: bool,
+    output: OutputFormat,
+) -> Result<(), Error> {
+    let download = Estimate::total(estimates).download;
+    if yes || download <= threshold {
+        return Ok(());
+    }
+    // The JSON output is read by programs, which cannot answer the prompt either
+    if output == OutputFormat::Json || !io::stdin().is_terminal() {
+        return Err(Error::DownloadNotConfirmed(
+            format_size(download),
+            format_size(threshold),
+        ));
+    }
+    let prompt = format!(
+        "The installation downloads {}, more than {}. Continue?",
+        format_size(download),
+        format_size(threshold)
+    );
+    if !confirm(&prompt)? {
+        return Err(Error::Cancelled);
+    }
+    Ok(())
+}
+
+/// Component installed by an installation or update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstalledComponent {
+    /// Name of the component.
+    pub name: String,
+    /// Installed version, if known.
+    pub version: Option<String>,
+    /// Changes installing the component would make, only set by dry runs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<Change>,
+}
+
+/// Result of an installation or update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Installation {
+    /// Xtensa Rust toolchain name.
+    pub name: String,
+    /// Paths of the installation.
+    pub paths: InstallPaths,
+    /// Installed components, empty when only the paths were printed.
+    pub components: Vec<InstalledComponent>,
+    /// Environment set up by the export file.
+    pub environment: Environment,
+    /// Changes made besides installing the components, only set by dry runs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<Change>,
+    /// Whether nothing was changed, the changes being only planned.
+    pub dry_run: bool,
+}
+
+impl Installation {
+    /// Prints the changes planned by a dry run.
+    fn print_plan(&self) {
+        println!("Dry run, nothing has been changed. The installation would:");
+        for component in &self.components {
+            match &component.version {
+                Some(version) => println!("{} {version}", component.name),
+                None => println!("{}", component.name),
+            }
+            for change in &component.changes {
+                println!("  - {change}");
+            }
+        }
+        for change in &self.changes {
+            println!("- {change}");
+        }
     }
-    Ok(file_path)
 }
 
 /// Installs or updates the Espressif Rust ecosystem.
-pub async fn install(args: InstallOpts, install_mode: InstallMode) -> Result<()> {
+pub async fn install(args: InstallOptions, install_mode: InstallMode) -> Result<Installation> {
+    install_with_progress(
+        args,
+        install_mode,
+        Arc::new(NoProgress),
+        CancellationToken::default(),
+    )
+    .await
+}
+
+/// Updates the Espressif Rust ecosystem.
+pub async fn update(args: InstallOptions) -> Result<Installation> {
+    install(args, InstallMode::Update).await
+}
+
+/// Installs or updates the Espressif Rust ecosystem, reporting the progress to the handler.
+///
+/// The installation can be aborted through the cancellation token, which makes it fail with
+/// [`Error::Cancelled`].
+pub async fn install_with_progress(
+    args: InstallOptions,
+    install_mode: InstallMode,
+    progress: Arc<dyn Progress>,
+    cancellation: CancellationToken,
+) -> Result<Installation> {
     match install_mode {
         InstallMode::Install => info!("Installing the Espressif Rust ecosystem"),
         InstallMode::Update => info!("Updating the Espressif Rust ecosystem"),
     }
-    let export_file = get_export_file(args.export_file)?;
+    let mut args = args;
+    log_paths();
+    if args.insecure {
+        warn_or_error(
+            args.strict,
+            "'--insecure' disables the validation of the TLS certificates, only use it in networks whose TLS interception cannot be fixed".to_string(),
+        )?;
+        set_insecure(true);
+    }
+    if let Some(shared_cache) = &args.shared_cache {
+        args.cache_dir = Some(shared_cache.clone());
+    }
+    set_proxy(args.proxy.as_deref())?;
+    set_credential_hosts(&args.credential_host);
+    set_max_download_size(Some(args.max_download_size).filter(|size| *size > 0));
+    set_offline(args.offline);
+    let bundle = match &args.bundle {
+        Some(bundle) => Some(Arc::new(Bundle::open(bundle)?)),
+        None => None,
+    };
+    set_retry_policy(RetryPolicy {
+        retries: args.retries,
+        delay: Duration::from_secs(args.retry_delay),
+    });
+    if let Some(url) = args.from_url.clone() {
+        let contents = fetch_profile(&url).await?;
+        match &args.from_url_sha256 {
+            Some(sha256) => verify_sha256(&contents, sha256)?,
+            None => warn_or_error(
+                args.strict,
+                format!("Installation profile '{url}' has not been verified, use '--from-url-sha256' to verify it"),
+            )?,
+        }
+        Profile::parse(&contents)?.apply(&mut args)?;
+    }
+    if let Some(warning) = args
+        .esp_idf_version
+        .as_deref()
+        .and_then(|version| end_of_life_warning(version, now()))
+    {
+        warn!("{warning}");
+    }
+    if let Some(project_toolchain) =
+        get_project_toolchain(&env::current_dir().map_err(Error::IoError)?)
+    {
+        let channel = project_toolchain.channel;
+        let file = project_toolchain.file.display();
+        if channel.starts_with("nightly") {
+            if args.nightly_version == DEFAULT_NIGHTLY_VERSION {
+                info!("Using '{channel}' nightly toolchain, pinned by '{file}'");
+                args.nightly_version = channel;
+            } else if channel != args.nightly_version {
+                warn_or_error(args.strict, format!("The project pins '{channel}' in '{file}', but '{}' nightly toolchain is being installed", args.nightly_version))?;
+            }
+        } else if channel != args.name {
+            warn_or_error(args.strict, format!("The project pins '{channel}' in '{file}', but the Xtensa Rust toolchain is being installed as '{}'. Use '--name {channel}' to satisfy it", args.name))?;
+        }
+    }
+
+    let default_export_file = args.export_file.is_none();
+    let shell = args
+        .shell
+        .or_else(|| {
+            args.export_file
+                .as_deref()
+                .and_then(Shell::from_export_file)
+        })
+        .unwrap_or_else(Shell::detect);
+    let export_file = get_export_file(args.export_file, shell, &args.name)?;
     let mut exports: Vec<String> = Vec::new();
+    if args.default_host.is_none() {
+        if let Some(report) = check_libc_compatibility() {
+            warn_or_error(args.strict, report)?;
+        }
+    }
     let host_triple = get_host_triple(args.default_host)?;
+    // The latest release is resolved as when no version is given, also from the configuration
+    if args.toolchain_version.as_deref() == Some(LATEST_VERSION) {
+        args.toolchain_version = None;
+    }
     let xtensa_rust_version = if let Some(toolchain_version) = &args.toolchain_version {
-        if !args.skip_version_parse {
-            XtensaRust::parse_version(toolchain_version)?
+        // Offline, the version cannot be verified against the published releases
+        if !args.skip_version_parse && !args.offline {
+            XtensaRust::parse_version(toolchain_version, &host_triple)?
         } else {
-            toolchain_version.clone()
+            toolchain_version.parse()?
         }
+    } else if let Some(version) = bundle
+        .as_ref()
+        .and_then(|bundle| bundle.xtensa_rust_version())
+    {
+        info!("Installing Xtensa Rust {version}, the version of the bundle");
+        version.parse()?
     } else {
-        XtensaRust::get_latest_version().await?
+        let version = XtensaRust::get_latest_version(&host_triple).await?;
+        info!("Installing Xtensa Rust {version}, the latest release");
+        version
+    };
+    let toolchain_dir = match &args.toolchain_destination {
+        Some(destination) if destination.is_relative() => env::current_dir()
+            .map_err(Error::IoError)?
+            .join(destination),
+        Some(destination) => destination.clone(),
+        None => get_toolchain_dir(&args.name),
+    };
+    let toolchain_link = get_default_toolchain_dir(&args.name);
+    for dir in [&toolchain_dir, &get_cargo_home()] {
+        if let Some(warning) = synced_folder_warning(dir) {
+            warn!("{warning}");
+        }
+    }
+    let toolchain_destination = (toolchain_dir != toolchain_link).then(|| toolchain_dir.clone());
+    let llvm_path = match &args.llvm_path {
+        Some(llvm_path) if llvm_path.is_relative() => {
+            Some(env::current_dir().map_err(Error::IoError)?.join(llvm_path))
+        }
+        llvm_path => llvm_path.clone(),
     };
-    let toolchain_dir = get_rustup_home().join("toolchains").join(args.name);
+    let llvm_root = llvm_path
+        .clone()
+        .unwrap_or_else(|| toolchain_dir.join(CLANG_NAME));
     let llvm: Llvm = Llvm::new(
-        &toolchain_dir,
+        &llvm_root,
         &host_triple,
         args.extended_llvm,
         &xtensa_rust_version,
     )?;
-    let targets = args.targets;
+    let mut targets = args.targets;
+    if let Some(arch) = &args.arch {
+        targets.retain(|target| (arch == "xtensa") == target.is_xtensa());
+        if targets.is_empty() {
+            return Err(Error::EmptyArch(arch.clone()).into());
+        }
+    }
+    // Sourced along with the export file, so that it follows the updates of the ESP-IDF tools
+    let esp_idf_export = match (&args.esp_idf_version, args.idf_export_mode) {
+        (Some(version), IdfExportMode::SourceScript) => {
+            let esp_idf_dir = env::var_os("IDF_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| get_esp_idf_dir(version));
+            let script = esp_idf_dir.join(shell.esp_idf_export_script());
+            if !script.exists() {
+                warn!(
+                    "'{}' does not exist, the export file sources it once ESP-IDF {version} is installed",
+                    script.display()
+                );
+            }
+            Some(shell.source_command(&script))
+        }
+        _ => None,
+    };
+    // The export files of the targets generated by a previous installation are kept up to date
+    let export_targets: Vec<Target> = if args.per_target_exports {
+        let mut export_targets: Vec<Target> = targets.iter().copied().collect();
+        export_targets.sort_by_key(|target| *target as u8);
+        export_targets
+    } else {
+        State::load()?
+            .toolchains
+            .get(&args.name)
+            .map(|toolchain| {
+                toolchain
+                    .export_targets
+                    .iter()
+                    .filter_map(|target| target.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    if args.skip_version_parse {
+        if args.toolchain_version.is_none() {
+            warn_or_error(
+                args.strict,
+                "'--skip-version-parse' has no effect without '--toolchain-version'".to_string(),
+            )?;
+        } else {
+            warn_or_error(
+                args.strict,
+                format!("Xtensa Rust version '{xtensa_rust_version}' has not been verified against the published releases"),
+            )?;
+        }
+    }
+    if args.probe_rs {
+        let mut probe_chips: Vec<String> = targets
+            .iter()
+            .filter(|t| !t.has_usb_jtag())
+            .map(|t| t.to_string())
+            .collect();
+        if !probe_chips.is_empty() {
+            probe_chips.sort_unstable();
+            warn_or_error(
+                args.strict,
+                format!(
+                    "{} do not have a built-in USB-JTAG, debugging them with probe-rs requires an external debug probe",
+                    probe_chips.join(", ")
+                ),
+            )?;
+        }
+    }
+    if args.skip_gcc && !args.std && targets.iter().any(|t| t.is_xtensa()) {
+        warn_or_error(
+            args.strict,
+            "Xtensa targets require GCC to link no_std applications, which '--skip-gcc' does not install".to_string(),
+        )?;
+    }
+    for var in ["LIBCLANG_PATH", "CLANG_PATH"] {
+        if let Some(value) = env::var_os(var) {
+            if !Path::new(&value).starts_with(&toolchain_dir)
+                && !Path::new(&value).starts_with(&llvm_root)
+            {
+                warn_or_error(
+                    args.strict,
+                    format!(
+                        "Environment variable '{var}' is already set to '{}', which conflicts with the installed LLVM",
+                        value.to_string_lossy()
+                    ),
+                )?;
+            }
+        }
+    }
+
     let xtensa_rust = if targets.contains(&Target::ESP32)
         || targets.contains(&Target::ESP32S2)
         || targets.contains(&Target::ESP32S3)
     {
-        Some(XtensaRust::new(
-            &xtensa_rust_version,
-            &host_triple,
-            &toolchain_dir,
-        ))
+        Some(XtensaRust {
+            force: args.force,
+            ..XtensaRust::new(
+                &xtensa_rust_version,
+                &host_triple,
+                &args.name,
+                &toolchain_dir,
+            )
+        })
     } else {
         None
     };
 
     debug!(
         "Arguments:
+            - Arch: {:?}
+            - Cache directory: {:?}
+            - Download jobs: {}
             - Export file: {:?}
+            - Extra crates: {:?}
             - Host triple: {}
+            - Jobs: {:?}
+            - Keep downloads: {:?}
+            - LLVM path: {:?}
             - LLVM Toolchain: {:?}
             - Nightly version: {:?}
+            - No symlink: {}
             - Rust Toolchain: {:?}
+            - Skip GCC: {}
             - Skip version parsing: {}
+            - Strict mode: {}
             - Targets: {:?}
             - Toolchain path: {:?}
             - Toolchain version: {:?}",
+        &args.arch,
+        &args.cache_dir,
+        args.download_jobs,
         &export_file,
+        &args.extra_crates,
         host_triple,
+        &args.jobs,
+        &args.keep_downloads,
+        &llvm_root,
         &llvm,
         &args.nightly_version,
+        &args.no_symlink,
         xtensa_rust,
+        &args.skip_gcc,
         &args.skip_version_parse,
+        &args.strict,
         targets,
         &toolchain_dir,
         args.toolchain_version,
     );
 
-    check_rust_installation().await?;
-
-    // Build up a vector of installable applications, all of which implement the
-    // `Installable` async trait.
-    let mut to_install = Vec::<Box<dyn Installable + Send + Sync>>::new();
+    // Planned after the rollback, which removes what the interrupted installation left
+    if !args.dry_run {
+        if let Some(journal) = Journal::load(&args.name)? {
+            warn!(
+                "The installation of '{}' started at {} did not complete, rolling it back",
+                args.name, journal.started_at
+            );
+            journal.rollback()?;
+        }
+    }
+    // Removed before the components are planned, they would reuse what it holds
+    let corrupted_toolchain = is_corrupted_toolchain(&toolchain_dir);
+    if corrupted_toolchain {
+        warn!(
+            "Toolchain '{}' is corrupted, it does not hold 'bin/rustc'. Removing it to reinstall it",
+            toolchain_dir.display()
+        );
+        if !args.dry_run {
+            remove_dir(&toolchain_dir).await?;
+        }
+    }
+
+    // Build up a vector of installable applications, all of which implement the
+    // `Installable` async trait.
+    let mut to_install = Vec::<Box<dyn Installable + Send + Sync>>::new();
 
     if let Some(ref xtensa_rust) = xtensa_rust {
         to_install.push(Box::new(xtensa_rust.to_owned()));
     }
 
-    to_install.push(Box::new(llvm));
+    let mut installed_llvm = None;
+    if !requires_llvm(args.arch.as_deref(), args.std) {
+        debug!("Skipping LLVM, RISC-V no_std applications do not require it");
+    } else {
+        installed_llvm = Some(llvm.path.clone());
+        to_install.push(Box::new(llvm));
+    }
+
+    // Updates of all the components also update the crates installed with the toolchain
+    let update_all = install_mode == InstallMode::Update && args.all;
+    let recorded_crates = if update_all {
+        State::load()?
+            .toolchains
+            .get(&args.name)
+            .map(|toolchain| toolchain.crates.clone())
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let mut extra_crates: Vec<&str> = args
+        .extra_crates
+        .iter()
+        .chain(&recorded_crates)
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .collect();
+    if args.probe_rs {
+        extra_crates.push(PROBE_RS);
+    }
+    extra_crates.sort_unstable();
+    extra_crates.dedup();
+    for name in &extra_crates {
+        let mut extra_crate = if *name == PROBE_RS {
+            Crate::with_features(name, &["cli"])
+        } else {
+            Crate::new(name)
+        };
+        extra_crate.update = update_all;
+        to_install.push(Box::new(extra_crate));
+    }
+    let mut cargo_crates: Vec<String> = extra_crates.iter().map(|name| name.to_string()).collect();
+    let recorded = State::load()?
+        .toolchains
+        .get(&args.name)
+        .map(recorded_components)
+        .unwrap_or_default();
+    // Components added with 'espup component add' are kept by the updates
+    for component in recorded.iter().copied() {
+        let tool = Tool::new(component, &host_triple, &toolchain_dir);
+        if let Some(name) = tool.crate_name() {
+            if extra_crates.contains(&name.as_str()) {
+                continue;
+            }
+            cargo_crates.push(name);
+        }
+        to_install.push(Box::new(tool));
+    }
+    // The components the bundle holds are installed along, as 'espup component add' would
+    let mut bundled_components = Vec::new();
+    if let Some(bundle) = &bundle {
+        for component in Component::iter().filter(|component| !recorded.contains(component)) {
+            let tool = Tool::new(component, &host_triple, &toolchain_dir);
+            let urls = tool.urls();
+            if !urls.is_empty() && urls.iter().all(|url| bundle.artifact(url).is_some()) {
+                bundled_components.push(component);
+                to_install.push(Box::new(tool));
+            }
+        }
+    }
+    if args.offline && !cargo_crates.is_empty() {
+        return Err(
+            Error::Offline(format!("Installing {} with cargo", cargo_crates.join(", "))).into(),
+        );
+    }
 
     if targets.iter().any(|t| t.is_riscv()) {
-        let riscv_target = RiscVTarget::new(&args.nightly_version);
-        to_install.push(Box::new(riscv_target));
+        let mut riscv_target = RiscVTarget::new(&args.nightly_version, &args.name);
+        riscv_target.rust_src = args.std || !args.profile_minimal;
+        riscv_target.add_chips(&targets);
+        if !args.offline {
+            to_install.push(Box::new(riscv_target));
+        } else if riscv_target.is_installed() {
+            info!(
+                "RISC-V targets are already installed for '{}' toolchain",
+                args.nightly_version
+            );
+        } else {
+            return Err(Error::Offline(format!(
+                "Installing the RISC-V targets of the '{}' toolchain with rustup",
+                args.nightly_version
+            ))
+            .into());
+        }
     }
 
-    if !args.std {
-        if targets
-            .iter()
-            .any(|t| t == &Target::ESP32 || t == &Target::ESP32S2 || t == &Target::ESP32S3)
+    let mut gcc_dirs = Vec::new();
+    if !args.std && !args.skip_gcc {
+        for arch in required_gcc_archs(&targets) {
+            if args.prefer_system_tools && is_system_gcc_compatible(arch) {
+                info!("Using GCC ({}) available in the system", arch);
+                continue;
+            }
+            let gcc = Gcc::new(arch, &host_triple, &toolchain_dir);
+            gcc_dirs.push(gcc.path.clone());
+            to_install.push(Box::new(gcc));
+        }
+    }
+
+    let paths = InstallPaths {
+        rustup_home: get_rustup_home(),
+        cargo_home: get_cargo_home(),
+        toolchain_dir: toolchain_dir.clone(),
+        llvm_dir: installed_llvm,
+        gcc_dirs,
+        export_file: export_file.clone(),
+        idf_path: env::var_os("IDF_PATH").map(PathBuf::from),
+        idf_tools_path: get_idf_tools_path(),
+    };
+    if args.print_paths {
+        if args.output == OutputFormat::Text {
+            paths.print(args.json)?;
+        }
+        return Ok(Installation {
+            name: args.name,
+            paths,
+            components: Vec::new(),
+            environment: Environment::default(),
+            changes: Vec::new(),
+            dry_run: false,
+        });
+    }
+    // Updates are a no-op when the toolchain is already of the version, unless all the components
+    // are updated, the current ones are then reused
+    if install_mode == InstallMode::Update && !args.force && !args.dry_run && !update_all {
+        let state = State::load()?;
+        let installed = state.toolchains.get(&args.name).filter(|toolchain_state| {
+            toolchain_state.xtensa_rust_version.as_deref()
+                == Some(xtensa_rust_version.to_string().as_str())
+                && args.export_append.iter().all(|(name, value)| {
+                    toolchain_state
+                        .exports
+                        .contains(&export_variable(name, value))
+                })
+                && esp_idf_export
+                    .iter()
+                    .all(|export| toolchain_state.exports.contains(export))
+        });
+        if let (Some(toolchain_state), Some(xtensa_rust)) = (installed, &xtensa_rust) {
+            if toolchain_dir.exists()
+                && matches!(xtensa_rust.installed_version_matches(), Ok(Some(true)))
+            {
+                info!(
+                    "Xtensa Rust {xtensa_rust_version} is already installed and up to date, use '--force' to reinstall it"
+                );
+                return Ok(Installation {
+                    name: args.name,
+                    paths,
+                    components: Vec::new(),
+                    environment: Environment::from_exports(&toolchain_state.exports),
+                    changes: Vec::new(),
+                    dry_run: false,
+                });
+            }
+        }
+    }
+    let plan_options = DownloadOptions {
+        cache_dir: args.cache_dir.clone(),
+        copy_symlinks: args.no_symlink,
+        minimal: args.profile_minimal,
+        ..Default::default()
+    };
+    let plans: Vec<(String, Vec<Change>)> = to_install
+        .iter()
+        .map(|app| (app.name(), app.plan(&plan_options)))
+        .collect();
+    let estimates = estimate_components(plans.clone(), args.cache_dir.clone()).await;
+    if args.output == OutputFormat::Text {
+        print_estimates(&estimates);
+    }
+    if args.dry_run {
+        let mut changes = Vec::new();
+        if corrupted_toolchain {
+            changes.push(Change::Remove {
+                path: toolchain_dir.clone(),
+            });
+        } else if is_foreign_toolchain(&toolchain_dir, &args.name)? {
+            match args.existing_toolchain {
+                ExistingToolchain::Overwrite => changes.push(Change::Remove {
+                    path: toolchain_dir.clone(),
+                }),
+                ExistingToolchain::Backup => changes.push(Change::Move {
+                    from: toolchain_dir.clone(),
+                    to: get_backup_path(&args.name),
+                }),
+                ExistingToolchain::Prompt => warn!(
+                    "Toolchain '{}' was not installed by espup, the installation would ask whether to overwrite or back it up",
+                    toolchain_dir.display()
+                ),
+                ExistingToolchain::Abort => {
+                    return Err(Error::ExistingToolchain(toolchain_dir.display().to_string()).into())
+                }
+            }
+        }
+        if args.backup
+            && toolchain_dir.exists()
+            && !changes
+                .iter()
+                .any(|change| matches!(change, Change::Move { .. }))
         {
-            let xtensa_gcc = Gcc::new(XTENSA_GCC, &host_triple, &toolchain_dir);
-            to_install.push(Box::new(xtensa_gcc));
+            changes.push(Change::Move {
+                from: toolchain_dir.clone(),
+                to: get_backup_path(&args.name),
+            });
         }
-        // All RISC-V targets use the same GCC toolchain
-        // ESP32S2 and ESP32S3 also install the RISC-V toolchain for their ULP coprocessor
-        if targets.iter().any(|t| t != &Target::ESP32) {
-            let riscv_gcc = Gcc::new(RISCV_GCC, &host_triple, &toolchain_dir);
-            to_install.push(Box::new(riscv_gcc));
+        let components = to_install
+            .iter()
+            .zip(plans)
+            .map(|(app, (name, changes))| InstalledComponent {
+                name,
+                version: app.version(),
+                changes,
+            })
+            .collect();
+        if let Some(esp_idf_version) = &args.esp_idf_version {
+            changes.push(Change::variable("ESP_IDF_VERSION", esp_idf_version));
+        }
+        for (name, value) in &args.export_append {
+            changes.push(Change::variable(name, value));
+        }
+        if toolchain_destination.is_some() && xtensa_rust.is_some() {
+            changes.push(Change::Link {
+                path: toolchain_link,
+                target: toolchain_dir.clone(),
+            });
+        }
+        changes.push(Change::Write {
+            path: export_file.clone(),
+        });
+        for target in &export_targets {
+            changes.push(Change::Write {
+                path: get_target_export_file(&export_file, *target),
+            });
         }
+        let installation = Installation {
+            name: args.name,
+            paths,
+            components,
+            environment: Environment::default(),
+            changes,
+            dry_run: true,
+        };
+        if args.output == OutputFormat::Text {
+            installation.print_plan();
+        }
+        return Ok(installation);
+    }
+    confirm_download_size(&estimates, args.confirm_above, args.yes, args.output)?;
+
+    let copy_symlinks = args.no_symlink || !supports_symlinks(&toolchain_dir);
+    if copy_symlinks && !args.no_symlink {
+        warn!(
+            "Symlinks are not supported in '{}', copying files instead",
+            toolchain_dir.display()
+        );
     }
+    let trusted_keys = if args.verify_signatures {
+        let keys = list_keys()?;
+        if keys.is_empty() {
+            return Err(Error::NoTrustedKeys.into());
+        }
+        Some(Arc::new(keys))
+    } else {
+        None
+    };
+    let download_options = Arc::new(DownloadOptions {
+        cache_dir: args.cache_dir.clone(),
+        shared_cache: args.shared_cache.is_some(),
+        copy_symlinks,
+        verbose_extract: args.verbose_extract,
+        keep_downloads: args.keep_downloads.clone(),
+        extraction_slots: Arc::new(Semaphore::new(
+            args.jobs
+                .map_or_else(DownloadOptions::default_jobs, NonZeroUsize::get),
+        )),
+        download_slots: Arc::new(Semaphore::new(args.download_jobs.get())),
+        fetcher: match bundle {
+            Some(bundle) => bundle,
+            None => default_fetcher(),
+        },
+        progress,
+        cancellation,
+        trusted_keys,
+        minimal: args.profile_minimal,
+        heartbeat_interval: Some(Duration::from_secs(args.heartbeat_interval)),
+        strict: args.strict,
+    });
+
+    check_rust_installation(&host_triple, args.bootstrap_rustup, &download_options).await?;
 
     // With a list of applications to install, install them all in parallel.
     let installable_items = to_install.len();
+    let components: Vec<InstalledComponent> = to_install
+        .iter()
+        .map(|app| InstalledComponent {
+            name: app.name(),
+            version: app.version(),
+            changes: Vec::new(),
+        })
+        .collect();
+    let mut changes: Vec<Change> = plans
+        .iter()
+        .flat_map(|(_, changes)| changes.iter().cloned())
+        .collect();
+    if toolchain_destination.is_some() && xtensa_rust.is_some() {
+        changes.push(Change::Link {
+            path: toolchain_link.clone(),
+            target: toolchain_dir.clone(),
+        });
+    }
+    changes.push(Change::Write {
+        path: export_file.clone(),
+    });
+    for target in &export_targets {
+        changes.push(Change::Write {
+            path: get_target_export_file(&export_file, *target),
+        });
+    }
+    // The journal is saved before the existing toolchain is moved, to move it back on failure
+    let mut journal = Journal::new(&args.name, &changes);
+    journal.save()?;
+    let moved = move_existing_toolchain(
+        &toolchain_dir,
+        &args.name,
+        args.existing_toolchain,
+        args.backup,
+        &mut journal,
+    )
+    .await;
+    if let Err(error) = moved {
+        if let Err(rollback_error) = journal.rollback() {
+            warn!(
+                "Failed to roll the installation back ({}), the next installation of '{}' will remove what was left behind",
+                rollback_error, args.name
+            );
+        }
+        return Err(error);
+    }
     let (tx, mut rx) = mpsc::channel::<Result<Vec<String>, Error>>(installable_items);
     for app in to_install {
         let tx = tx.clone();
-        let retry_strategy = FixedInterval::from_millis(50).take(3);
+        let download_options = download_options.clone();
         tokio::spawn(async move {
-            let res = Retry::spawn(retry_strategy, || async {
-                let res = app.install().await;
-                if res.is_err() {
-                    warn!("Installation for '{}' failed, retrying", app.name());
-                }
-                res
-            })
-            .await;
+            // The downloads retry their transient failures, the other ones would fail again
+            let res = app.install(&download_options).await;
+            if res.is_ok() {
+                download_options
+                    .progress
+                    .on_event(ProgressEvent::ComponentInstalled { name: app.name() });
+            }
             tx.send(res).await.unwrap();
         });
     }
 
-    // Read the results of the install tasks as they complete.
+    // Read the results of the install tasks as they complete. After a failure, the other tasks
+    // are cancelled and awaited, so that nothing is being installed anymore when rolling back.
+    let mut failure = None;
     for _ in 0..installable_items {
-        let names = rx.recv().await.unwrap()?;
-        exports.extend(names);
+        match rx.recv().await.unwrap() {
+            Ok(names) => exports.extend(names),
+            Err(error) if failure.is_none() => {
+                download_options.cancellation.cancel();
+                failure = Some(error);
+            }
+            Err(_) => {}
+        }
+    }
+    if let Some(error) = failure {
+        warn!("The installation failed, rolling it back");
+        if let Err(rollback_error) = journal.rollback() {
+            warn!(
+                "Failed to roll the installation back ({}), the next installation of '{}' will remove what was left behind",
+                rollback_error, args.name
+            );
+        }
+        return Err(error.into());
+    }
+    if toolchain_destination.is_some() && xtensa_rust.is_some() {
+        link_toolchain(&args.name, &toolchain_dir)?;
+    }
+    if let Some(esp_idf_version) = &args.esp_idf_version {
+        exports.push(export_variable("ESP_IDF_VERSION", esp_idf_version));
+    }
+    if let Some(esp_idf_export) = &esp_idf_export {
+        exports.push(esp_idf_export.clone());
+    }
+    for (name, value) in &args.export_append {
+        exports.push(export_variable(name, value));
     }
 
-    create_export_file(&export_file, &exports)?;
+    let mut state = State::load()?;
+    let toolchain_state = state.toolchain(&args.name);
+    toolchain_state.llvm_path = llvm_path;
+    toolchain_state.toolchain_destination = toolchain_destination;
+    toolchain_state.export_file = Some(export_file.clone());
+    toolchain_state.shell = Some(shell.to_string());
+    toolchain_state.exports = exports.clone();
+    toolchain_state.export_targets = export_targets.iter().map(Target::to_string).collect();
+    let mut recorded_targets: Vec<Target> = targets.iter().copied().collect();
+    recorded_targets.sort_by_key(|target| *target as u8);
+    toolchain_state.targets = recorded_targets.iter().map(Target::to_string).collect();
+    toolchain_state.nightly_version = Some(args.nightly_version.clone());
+    toolchain_state.std = args.std;
+    toolchain_state.extended_llvm = args.extended_llvm;
+    let target_export_files = target_export_files(toolchain_state);
+    toolchain_state.last_used = Some(now());
+    toolchain_state.espup_version = Some(env!("CARGO_PKG_VERSION").to_string());
+    for ((name, changes), component) in plans.iter().zip(&components) {
+        toolchain_state.record_component(ComponentRecord::new(
+            name,
+            component.version.clone(),
+            changes,
+        ));
+    }
+    if let Some(xtensa_rust) = &xtensa_rust {
+        toolchain_state.xtensa_rust_version = Some(xtensa_rust.version.to_string());
+    }
+    for component in bundled_components {
+        if !toolchain_state
+            .components
+            .iter()
+            .any(|installed| *installed == component.to_string())
+        {
+            toolchain_state.components.push(component.to_string());
+        }
+    }
+    for name in extra_crates {
+        if !toolchain_state
+            .crates
+            .iter()
+            .any(|installed| installed == name)
+        {
+            toolchain_state.crates.push(name.to_string());
+        }
+    }
+    state.save()?;
+
+    create_export_file(&export_file, &exports, shell)?;
+    for (target_export_file, target_exports) in &target_export_files {
+        debug!(
+            "Creating the export file of the target '{}'",
+            target_export_file.display()
+        );
+        create_export_file(target_export_file, target_exports, shell)?;
+    }
+    journal.commit()?;
+    if args.profile_minimal {
+        let partial_downloads_dir = get_partial_downloads_dir();
+        if partial_downloads_dir.exists() {
+            debug!(
+                "Removing the partial downloads in '{}'",
+                partial_downloads_dir.display()
+            );
+            std::fs::remove_dir_all(&partial_downloads_dir)
+                .map_err(|_| Error::RemoveDirectory(partial_downloads_dir.display().to_string()))?;
+        }
+    }
+    let mut installed_dirs = vec![toolchain_dir.clone(), get_espup_home()];
+    if !llvm_root.starts_with(&toolchain_dir) {
+        installed_dirs.push(llvm_root.clone());
+    }
+    for dir in installed_dirs {
+        let restricted = restrict_permissions(&dir).map_err(Error::IoError)?;
+        if restricted > 0 {
+            warn!(
+                "Removed the write permission of other users from {} files and directories in '{}', check the umask of the account",
+                restricted,
+                dir.display()
+            );
+        }
+    }
+    // Previous versions of espup only installed the toolchain named 'esp'
+    let legacy_export_file = get_legacy_export_file();
+    if default_export_file && legacy_export_file.exists() {
+        if args.name == "esp"
+            && update_legacy_export_file(&legacy_export_file, &export_file, shell)?
+        {
+            info!(
+                "'{}' now sources '{}'",
+                legacy_export_file.display(),
+                export_file.display()
+            );
+        } else {
+            warn!(
+                "'{}' is no longer updated, use '{}' instead and remove it",
+                legacy_export_file.display(),
+                export_file.display()
+            );
+        }
+    }
     #[cfg(windows)]
     set_env()?;
+    if let Some(rustup_override) = get_rustup_override() {
+        if rustup_override.toolchain != args.name {
+            warn!(
+                "Toolchain '{}' is selected in the current directory ({}), it will be used instead of the toolchains installed by espup unless a toolchain is explicitly requested (e.g. 'cargo +{}')",
+                rustup_override.toolchain, rustup_override.source, args.name
+            );
+        }
+    }
+
+    let installation = Installation {
+        name: args.name.clone(),
+        paths,
+        components,
+        environment: Environment::from_exports(&exports),
+        changes: Vec::new(),
+        dry_run: false,
+    };
+    if args.output == OutputFormat::Json {
+        return Ok(installation);
+    }
+    if args.quiet {
+        let action = match install_mode {
+            InstallMode::Install => "Installed",
+            InstallMode::Update => "Updated",
+        };
+        let names: Vec<&str> = installation
+            .components
+            .iter()
+            .map(|component| component.name.as_str())
+            .collect();
+        println!(
+            "{action} {} for {}, export file: '{}'",
+            names.join(", "),
+            targets_names(&targets),
+            export_file.display()
+        );
+        return Ok(installation);
+    }
+
+    if args.skip_gcc && !args.std {
+        if let Some(config) = rust_lld_config(&targets) {
+            println!("\nGCC was not installed, add the following to the '.cargo/config.toml' of your RISC-V projects to link them with 'rust-lld':\n\n{config}");
+        }
+    }
+
     match install_mode {
-        InstallMode::Install => info!("Installation successfully completed!"),
-        InstallMode::Update => info!("Update successfully completed!"),
+        InstallMode::Install => {
+            info!("Installation successfully completed!");
+            println!(
+                "\n{}",
+                next_steps(
+                    &targets,
+                    args.std,
+                    &args.name,
+                    &args.nightly_version,
+                    &export_file
+                )
+            );
+            if !args.no_tips {
+                if let Some(tips) = chip_tips(&targets, args.std) {
+                    println!("\n{tips}");
+                }
+            }
+        }
+        InstallMode::Update => {
+            info!("Update successfully completed!");
+            print_post_install_msg(&export_file, shell)?;
+        }
+    }
+    Ok(installation)
+}
+
+/// Result of an uninstallation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Uninstallation {
+    /// Xtensa Rust toolchain name.
+    pub name: String,
+    /// Directories that were removed.
+    pub removed: Vec<PathBuf>,
+    /// Files and directories that were kept, as they were modified since their installation.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<PathBuf>,
+    /// Changes the uninstallation would make, only set by dry runs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<Change>,
+    /// Whether nothing was changed, the changes being only planned.
+    pub dry_run: bool,
+}
+
+impl Uninstallation {
+    /// Prints the changes planned by a dry run.
+    pub fn print_plan(&self) {
+        if self.changes.is_empty() {
+            println!("Dry run, nothing has been changed. There is nothing to uninstall");
+        } else {
+            println!("Dry run, nothing has been changed. The uninstallation would:");
+            for change in &self.changes {
+                println!("- {change}");
+            }
+        }
+        if !self.skipped.is_empty() {
+            println!("It would keep, as they were modified since their installation:");
+            for path in &self.skipped {
+                println!("- '{}'", path.display());
+            }
+        }
     }
+}
 
-    print_post_install_msg(&export_file)?;
+/// Uninstalls the Espressif Rust ecosystem installed as the toolchain.
+///
+/// The paths recorded by the installation are removed, but the ones modified since, which are
+/// reported. Toolchains installed by older versions of espup, without records, are removed from
+/// their default paths.
+pub async fn uninstall(name: &str) -> Result<Uninstallation> {
+    info!("Uninstalling the Espressif Rust ecosystem");
+    let toolchain_dir = get_toolchain_dir(name);
+    let llvm_root = get_llvm_root(name, &toolchain_dir);
+    let mut state = State::load()?;
+    let toolchain_state = state.toolchains.remove(name);
+    let removal = toolchain_state
+        .as_ref()
+        .map(|toolchain_state| plan_removal(toolchain_state, &state))
+        .unwrap_or_default();
+    let mut removed = Vec::new();
+
+    if toolchain_state
+        .as_ref()
+        .map_or(true, |toolchain_state| toolchain_state.installed.is_empty())
+    {
+        if llvm_root.exists() {
+            removed.push(llvm_root.clone());
+        }
+        Llvm::uninstall(&llvm_root).await?;
+
+        if toolchain_dir.exists() {
+            removed.push(toolchain_dir.clone());
+            uninstall_gcc_toolchains(&toolchain_dir).await?;
+
+            XtensaRust::uninstall(&toolchain_dir).await?;
+
+            remove_dir(&toolchain_dir).await?;
+        }
+    }
+    for path in &removal.skipped {
+        warn!(
+            "'{}' has been modified since its installation, it has been kept",
+            path.display()
+        );
+    }
+    remove_paths(&removal)?;
+    remove_empty_dir(&llvm_root)?;
+    remove_empty_dir(&toolchain_dir)?;
+    removed.extend(removal.removed);
+    #[cfg(windows)]
+    clean_env()?;
+    unlink_toolchain(name)?;
+
+    if let Some(toolchain_state) = toolchain_state {
+        for name in removable_crates(&toolchain_state, &state) {
+            Crate::new(name).uninstall()?;
+        }
+        for (toolchain, targets) in toolchain_state.rustup_targets_by_toolchain() {
+            RiscVTarget::uninstall(toolchain, &targets)?;
+        }
+        state.save()?;
+    }
+
+    info!("Uninstallation successfully completed!");
+    Ok(Uninstallation {
+        name: name.to_string(),
+        removed,
+        skipped: removal.skipped,
+        changes: Vec::new(),
+        dry_run: false,
+    })
+}
+
+/// Returns the changes uninstalling the Espressif Rust ecosystem would make, without making them.
+pub fn plan_uninstall(name: &str, backup: bool) -> Result<Uninstallation> {
+    let toolchain_dir = get_toolchain_dir(name);
+    let mut changes = Vec::new();
+    let mut state = State::load()?;
+    let toolchain_state = state.toolchains.remove(name);
+    let mut removal = toolchain_state
+        .as_ref()
+        .map(|toolchain_state| plan_removal(toolchain_state, &state))
+        .unwrap_or_default();
+    let recorded = toolchain_state
+        .as_ref()
+        .is_some_and(|toolchain_state| !toolchain_state.installed.is_empty());
+
+    let llvm_root = get_llvm_root(name, &toolchain_dir);
+    if backup && toolchain_dir.exists() {
+        // The backup moves the toolchain directory, with the recorded paths it contains
+        removal
+            .removed
+            .retain(|path| !path.starts_with(&toolchain_dir));
+        removal
+            .skipped
+            .retain(|path| !path.starts_with(&toolchain_dir));
+    }
+    if !recorded && llvm_root.exists() && !llvm_root.starts_with(&toolchain_dir) {
+        let versions: Vec<PathBuf> = LLVM_VERSIONS
+            .iter()
+            .map(|version| llvm_root.join(version))
+            .filter(|path| path.exists())
+            .collect();
+        // The root is only removed when nothing else than the versions installed by espup is left
+        let only_versions = read_dir(&llvm_root).is_ok_and(|mut entries| {
+            entries.all(|entry| entry.is_ok_and(|entry| versions.contains(&entry.path())))
+        });
+        if only_versions {
+            changes.push(Change::Remove { path: llvm_root });
+        } else {
+            changes.extend(versions.into_iter().map(|path| Change::Remove { path }));
+        }
+    }
+    #[cfg(unix)]
+    if !recorded {
+        let llvm_symlink_path = crate::paths::get_espup_home().join("esp-clang");
+        if llvm_symlink_path.is_symlink() {
+            changes.push(Change::Remove {
+                path: llvm_symlink_path,
+            });
+        }
+    }
+    let toolchain_link = get_default_toolchain_dir(name);
+    if toolchain_link.is_symlink() {
+        changes.push(Change::Remove {
+            path: toolchain_link,
+        });
+    }
+    if toolchain_dir.exists() && backup {
+        changes.push(Change::Move {
+            from: toolchain_dir,
+            to: get_backup_path(name),
+        });
+    } else if toolchain_dir.exists() && !recorded {
+        changes.push(Change::Remove {
+            path: toolchain_dir,
+        });
+    }
+    changes.extend(
+        removal
+            .removed
+            .iter()
+            .map(|path| Change::Remove { path: path.clone() }),
+    );
+    if let Some(toolchain_state) = &toolchain_state {
+        for name in removable_crates(toolchain_state, &state) {
+            changes.push(Change::Run {
+                command: format!("cargo uninstall {name}"),
+            });
+        }
+        for (toolchain, targets) in toolchain_state.rustup_targets_by_toolchain() {
+            changes.push(Change::Run {
+                command: format!(
+                    "rustup target remove --toolchain {toolchain} {}",
+                    targets.join(" ")
+                ),
+            });
+        }
+    }
+
+    let removed = changes
+        .iter()
+        .filter_map(|change| match change {
+            Change::Remove { path } => Some(path.clone()),
+            _ => None,
+        })
+        .collect();
+    Ok(Uninstallation {
+        name: name.to_string(),
+        removed,
+        skipped: removal.skipped,
+        changes,
+        dry_run: true,
+    })
+}
+
+/// Part of an installation that can be uninstalled on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UninstallPart {
+    /// ESP-IDF of the version, installed by esp-idf-sys under the ESP-IDF tools directory.
+    EspIdf(String),
+    /// GCC toolchains.
+    Gcc,
+    /// LLVM.
+    Llvm,
+    /// Xtensa Rust toolchain, without the GCC toolchains, LLVM and the components it holds.
+    XtensaRust,
+}
+
+impl UninstallPart {
+    /// Returns true if the recorded component is part of it.
+    fn includes(&self, record: &ComponentRecord) -> bool {
+        match self {
+            UninstallPart::EspIdf(_) => false,
+            UninstallPart::Gcc => record.name.starts_with("GCC"),
+            UninstallPart::Llvm => record.name == "LLVM",
+            UninstallPart::XtensaRust => record.name == "Xtensa Rust",
+        }
+    }
+
+    /// Returns the existing paths of the part, for the ESP-IDF and the installations without
+    /// records.
+    fn default_paths(&self, name: &str, toolchain_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        let paths = match self {
+            UninstallPart::EspIdf(version) => vec![get_esp_idf_dir(version)],
+            UninstallPart::Gcc => vec![
+                toolchain_dir.join(XTENSA_GCC),
+                toolchain_dir.join(RISCV_GCC),
+            ],
+            UninstallPart::Llvm => {
+                let llvm_root = get_llvm_root(name, toolchain_dir);
+                LLVM_VERSIONS
+                    .iter()
+                    .map(|version| llvm_root.join(version))
+                    .collect()
+            }
+            UninstallPart::XtensaRust if toolchain_dir.exists() => {
+                XtensaRust::installed_entries(toolchain_dir)?
+            }
+            UninstallPart::XtensaRust => Vec::new(),
+        };
+        Ok(paths.into_iter().filter(|path| path.exists()).collect())
+    }
+
+    /// Returns true if the export sets the part up: it points into its removed paths, or it sets
+    /// the version of the ESP-IDF.
+    fn is_exported_by(&self, export: &str, removed: &[PathBuf]) -> bool {
+        let environment = Environment::from_exports(&[export.to_string()]);
+        if let UninstallPart::EspIdf(version) = self {
+            return environment
+                .variables
+                .iter()
+                .any(|(variable, value)| variable == "ESP_IDF_VERSION" && value == version);
+        }
+        environment
+            .variables
+            .iter()
+            .map(|(_, value)| Path::new(value))
+            .chain(environment.path.iter().map(PathBuf::as_path))
+            .any(|path| removed.iter().any(|removed| path.starts_with(removed)))
+    }
+}
+
+/// Returns the paths removing the parts of the installation of the toolchain would remove.
+fn plan_parts_removal(name: &str, parts: &[UninstallPart], state: &State) -> Result<Removal> {
+    let toolchain_dir = get_toolchain_dir(name);
+    let mut others = state.clone();
+    let toolchain_state = others
+        .toolchains
+        .remove(name)
+        .filter(|toolchain_state| !toolchain_state.installed.is_empty());
+    let mut removal = toolchain_state
+        .as_ref()
+        .map(|toolchain_state| {
+            plan_components_removal(toolchain_state, &others, |record| {
+                parts.iter().any(|part| part.includes(record))
+            })
+        })
+        .unwrap_or_default();
+    for part in parts {
+        if let UninstallPart::EspIdf(version) = part {
+            let esp_idf_dir = get_esp_idf_dir(version);
+            if !esp_idf_dir.exists() {
+                return Err(Error::MissingEspIdf(
+                    version.to_string(),
+                    esp_idf_dir
+                        .parent()
+                        .unwrap_or(&esp_idf_dir)
+                        .display()
+                        .to_string(),
+                )
+                .into());
+            }
+        } else if toolchain_state.is_some() {
+            continue;
+        }
+        removal
+            .removed
+            .extend(part.default_paths(name, &toolchain_dir)?);
+    }
+    Ok(removal)
+}
+
+/// Uninstalls the parts of the Espressif Rust ecosystem installed as the toolchain, keeping the
+/// other ones.
+///
+/// The exports of the removed parts are removed from the export file.
+pub async fn uninstall_parts(name: &str, parts: &[UninstallPart]) -> Result<Uninstallation> {
+    info!("Uninstalling parts of the Espressif Rust ecosystem");
+    let mut state = State::load()?;
+    let removal = plan_parts_removal(name, parts, &state)?;
+    for path in &removal.skipped {
+        warn!(
+            "'{}' has been modified since its installation, it has been kept",
+            path.display()
+        );
+    }
+    remove_paths(&removal)?;
+    if parts.contains(&UninstallPart::XtensaRust) {
+        unlink_toolchain(name)?;
+    }
+    if let Some(toolchain_state) = state.toolchains.get_mut(name) {
+        toolchain_state.exports.retain(|export| {
+            !parts
+                .iter()
+                .any(|part| part.is_exported_by(export, &removal.removed))
+        });
+        toolchain_state
+            .installed
+            .retain(|record| !parts.iter().any(|part| part.includes(record)));
+        if parts.contains(&UninstallPart::XtensaRust) {
+            toolchain_state.xtensa_rust_version = None;
+        }
+        update_export_file(toolchain_state)?;
+        state.save()?;
+    }
+
+    info!("Uninstallation successfully completed!");
+    Ok(Uninstallation {
+        name: name.to_string(),
+        removed: removal.removed,
+        skipped: removal.skipped,
+        changes: Vec::new(),
+        dry_run: false,
+    })
+}
+
+/// Returns the changes uninstalling the parts of the Espressif Rust ecosystem would make, without
+/// making them.
+pub fn plan_uninstall_parts(name: &str, parts: &[UninstallPart]) -> Result<Uninstallation> {
+    let state = State::load()?;
+    let removal = plan_parts_removal(name, parts, &state)?;
+    let mut changes: Vec<Change> = removal
+        .removed
+        .iter()
+        .map(|path| Change::Remove { path: path.clone() })
+        .collect();
+    let toolchain_link = get_default_toolchain_dir(name);
+    if parts.contains(&UninstallPart::XtensaRust) && toolchain_link.is_symlink() {
+        changes.push(Change::Remove {
+            path: toolchain_link,
+        });
+    }
+    if let Some(export_file) = state
+        .toolchains
+        .get(name)
+        .and_then(|toolchain_state| toolchain_state.export_file.clone())
+    {
+        changes.push(Change::Write { path: export_file });
+    }
+    Ok(Uninstallation {
+        name: name.to_string(),
+        removed: removal.removed,
+        skipped: removal.skipped,
+        changes,
+        dry_run: true,
+    })
+}
+
+/// Returns the sorted, comma separated, names of the targets.
+fn targets_names(targets: &HashSet<Target>) -> String {
+    let mut names: Vec<String> = targets.iter().map(|target| target.to_string()).collect();
+    names.sort();
+    names.join(", ")
+}
+
+/// Logs a warning, or returns it as an error when strict mode is enabled.
+pub fn warn_or_error(strict: bool, message: String) -> Result<(), Error> {
+    if strict {
+        return Err(Error::StrictMode(message));
+    }
+    warn!("{}", message);
     Ok(())
 }
 
 /// Queries the GitHub API and returns the JSON response.
+#[cfg(not(feature = "network"))]
+pub fn github_query(url: &str) -> Result<serde_json::Value, Error> {
+    Err(Error::NetworkDisabled(url.to_string()))
+}
+
+/// Queries the GitHub API and returns the JSON response.
+#[cfg(feature = "network")]
 pub fn github_query(url: &str) -> Result<serde_json::Value, Error> {
+    if is_offline() {
+        return Err(Error::Offline(format!("Querying '{url}'")));
+    }
     debug!("Querying GitHub API: '{}'", url);
     let mut headers = header::HeaderMap::new();
     headers.insert(header::USER_AGENT, "espup".parse().unwrap());
@@ -270,35 +2376,189 @@ pub fn github_query(url: &str) -> Result<serde_json::Value, Error> {
         "application/vnd.github+json".parse().unwrap(),
     );
     headers.insert("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
-    if let Some(token) = env::var_os("GITHUB_TOKEN") {
+    let token = env::var("GITHUB_TOKEN")
+        .ok()
+        .or_else(|| credentials::credentials(url).map(|credentials| credentials.password));
+    if let Some(token) = token {
         debug!("Auth header added");
-        headers.insert(
-            "Authorization",
-            format!("Bearer {}", token.to_string_lossy())
-                .parse()
-                .unwrap(),
-        );
+        headers.insert("Authorization", format!("Bearer {token}").parse().unwrap());
     }
-    let client = Client::new();
-    let json = retry(
-        Fixed::from_millis(100).take(5),
-        || -> Result<serde_json::Value, Error> {
-            let res = client.get(url).headers(headers.clone()).send()?.text()?;
-            if res.contains(
-                "https://docs.github.com/rest/overview/resources-in-the-rest-api#rate-limiting",
-            ) {
-                warn!("GitHub rate limit exceeded");
-                return Err(Error::GithubQuery);
-            }
-            let json: serde_json::Value =
-                serde_json::from_str(&res).map_err(|_| Error::SerializeJson)?;
-            Ok(json)
-        },
-    )
-    .unwrap();
+    let client = blocking_client()?;
+    let query = || -> Result<serde_json::Value, Error> {
+        let response = client.get(url).headers(headers.clone()).send()?;
+        if response.status().is_server_error() {
+            return Err(response.error_for_status().unwrap_err().into());
+        }
+        let res = response.text()?;
+        if res.contains(
+            "https://docs.github.com/rest/overview/resources-in-the-rest-api#rate-limiting",
+        ) {
+            warn!("GitHub rate limit exceeded");
+            return Err(Error::GithubQuery);
+        }
+        let json: serde_json::Value =
+            serde_json::from_str(&res).map_err(|_| Error::SerializeJson)?;
+        Ok(json)
+    };
+    let json = retry(retry_policy().delays(), || match query() {
+        Ok(json) => OperationResult::Ok(json),
+        Err(error) if error.is_transient() => {
+            warn!("Failed to query '{}' ({}), retrying", url, error);
+            OperationResult::Retry(error)
+        }
+        Err(error) => OperationResult::Err(error),
+    })
+    .map_err(|error| error.error)?;
     Ok(json)
 }
 
+/// Returns the existing binary directories of the toolchains installed by espup.
+pub fn get_bin_paths(name: &str, toolchain_dir: &Path) -> Vec<PathBuf> {
+    // Returns the subdirectories of a directory, e.g. the installed versions of a toolchain.
+    fn subdirectories(path: &Path) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.is_dir())
+                    .collect()
+            })
+            .unwrap_or_default();
+        dirs.sort();
+        dirs
+    }
+
+    let mut bin_paths = vec![toolchain_dir.join("bin")];
+    for arch in [XTENSA_GCC, RISCV_GCC] {
+        let gcc_path = toolchain_dir.join(arch);
+        // Windows installs GCC without a versioned directory
+        bin_paths.push(gcc_path.join("bin"));
+        for version in subdirectories(&gcc_path) {
+            bin_paths.push(version.join(arch).join("bin"));
+        }
+    }
+    let llvm_root = get_llvm_root(name, toolchain_dir);
+    // Previous versions of espup installed LLVM without a versioned directory on Windows
+    bin_paths.push(llvm_root.join("esp-clang").join("bin"));
+    for version in subdirectories(&llvm_root) {
+        bin_paths.push(version.join("esp-clang").join("bin"));
+    }
+    bin_paths.push(get_cargo_home().join("bin"));
+
+    bin_paths.retain(|path| path.is_dir());
+    bin_paths
+}
+
+/// Returns the full path of a tool installed by espup.
+pub fn find_tool(name: &str, toolchain_dir: &Path, tool: &str) -> Result<PathBuf, Error> {
+    let tool_file = format!("{}{}", tool, env::consts::EXE_SUFFIX);
+    get_bin_paths(name, toolchain_dir)
+        .into_iter()
+        .map(|bin_path| bin_path.join(&tool_file))
+        .find(|tool_path| tool_path.is_file())
+        .ok_or_else(|| Error::ToolNotFound(tool.to_string()))
+}
+
+/// Moves the existing toolchain out of the way of the installation, recording it in the journal.
+async fn move_existing_toolchain(
+    toolchain_dir: &Path,
+    name: &str,
+    action: ExistingToolchain,
+    backup: bool,
+    journal: &mut Journal,
+) -> Result<()> {
+    resolve_existing_toolchain(toolchain_dir, name, action, journal).await?;
+    if backup {
+        if let Some(backup) = create_backup(name)? {
+            journal.record_backup(toolchain_dir, &backup)?;
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the conflict with a toolchain of the same name that was not installed by espup.
+///
+/// Toolchains recorded in the state are left to the installers, which reuse or replace them.
+async fn resolve_existing_toolchain(
+    toolchain_dir: &Path,
+    name: &str,
+    action: ExistingToolchain,
+    journal: &mut Journal,
+) -> Result<()> {
+    if !is_foreign_toolchain(toolchain_dir, name)? {
+        return Ok(());
+    }
+    let action = match action {
+        ExistingToolchain::Prompt if io::stdin().is_terminal() => {
+            prompt_existing_toolchain(toolchain_dir).map_err(Error::IoError)?
+        }
+        ExistingToolchain::Prompt => {
+            warn!(
+                "Toolchain '{}' was not installed by espup and there is no terminal to ask what to do with it, backing it up",
+                toolchain_dir.display()
+            );
+            ExistingToolchain::Backup
+        }
+        action => action,
+    };
+    match action {
+        ExistingToolchain::Overwrite => {
+            warn!(
+                "Overwriting toolchain '{}', which was not installed by espup",
+                toolchain_dir.display()
+            );
+            Ok(journal.replace(toolchain_dir)?)
+        }
+        ExistingToolchain::Backup => {
+            if let Some(backup) = backup_toolchain(name, toolchain_dir)? {
+                journal.record_backup(toolchain_dir, &backup)?;
+                warn!(
+                    "Toolchain '{}' was not installed by espup, it has been backed up as '{}' (see 'espup restore-backup')",
+                    toolchain_dir.display(),
+                    backup.id()
+                );
+            }
+            Ok(())
+        }
+        ExistingToolchain::Prompt | ExistingToolchain::Abort => {
+            Err(Error::ExistingToolchain(toolchain_dir.display().to_string()).into())
+        }
+    }
+}
+
+/// Returns true if the toolchain directory exists, but the toolchain was not installed by espup.
+fn is_foreign_toolchain(toolchain_dir: &Path, name: &str) -> Result<bool, Error> {
+    Ok(toolchain_dir.exists() && !State::load()?.toolchains.contains_key(name))
+}
+
+/// Asks what to do with a toolchain that was not installed by espup, until the answer is valid.
+fn prompt_existing_toolchain(toolchain_dir: &Path) -> io::Result<ExistingToolchain> {
+    loop {
+        eprint!(
+            "Toolchain '{}' was not installed by espup. [o]verwrite, [b]ack up or [A]bort? ",
+            toolchain_dir.display()
+        );
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Ok(ExistingToolchain::Abort);
+        }
+        if let Some(action) = parse_existing_toolchain_answer(&answer) {
+            return Ok(action);
+        }
+    }
+}
+
+/// Parses the answer to the existing toolchain prompt, aborting by default.
+fn parse_existing_toolchain_answer(answer: &str) -> Option<ExistingToolchain> {
+    match answer.trim().to_lowercase().as_str() {
+        "o" | "overwrite" => Some(ExistingToolchain::Overwrite),
+        "b" | "backup" | "back up" => Some(ExistingToolchain::Backup),
+        "" | "a" | "abort" => Some(ExistingToolchain::Abort),
+        _ => None,
+    }
+}
+
 /// Checks if the directory exists and deletes it if it does.
 pub async fn remove_dir(path: &Path) -> Result<()> {
     if path.exists() {
@@ -312,3 +2572,397 @@ pub async fn remove_dir(path: &Path) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        state::ComponentRecord,
+        toolchain::{
+            check_archive_signature, confirm_download_size, download_file,
+            estimate::Estimate,
+            extract_archive, find_tool, keep_download, move_into_place,
+            options::{ExistingToolchain, OutputFormat},
+            parse_existing_toolchain_answer, prune_documentation, unpack_tar, verify_checksum,
+            Change, DownloadOptions, ExtractionProgress, Fetch, UninstallPart, DOWNLOADS_MANIFEST,
+        },
+    };
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use flate2::{write::GzEncoder, Compression};
+    use std::{
+        env,
+        fs::{create_dir_all, read_to_string, write},
+        io::Write,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use tar::{Archive, Builder, EntryType, Header};
+    use tempfile::TempDir;
+    use tokio::sync::Semaphore;
+
+    #[test]
+    fn test_extract_archive_cancelled() {
+        let mut builder = Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/clang", &b"clang"[..])
+            .unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&builder.into_inner().unwrap()).unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("llvm").display().to_string();
+        let options = DownloadOptions::default();
+        options.cancellation.cancel();
+        assert!(matches!(
+            extract_archive(&bytes, "llvm.tar.gz", &output, false, &options),
+            Err(Error::Cancelled)
+        ));
+        // Neither the output nor the staging directory are left behind
+        assert_eq!(temp_dir.path().read_dir().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_find_tool() {
+        let temp_dir = TempDir::new().unwrap();
+        let toolchain_dir = temp_dir.path();
+        let gcc_bin = toolchain_dir
+            .join("xtensa-esp-elf")
+            .join("esp-13.2.0_20230928")
+            .join("xtensa-esp-elf")
+            .join("bin");
+        let clang_bin = toolchain_dir
+            .join("xtensa-esp32-elf-clang")
+            .join("esp-16.0.0-20230516")
+            .join("esp-clang")
+            .join("bin");
+        create_dir_all(&gcc_bin).unwrap();
+        create_dir_all(&clang_bin).unwrap();
+        let gcc = gcc_bin.join(format!("xtensa-esp-elf-gcc{}", env::consts::EXE_SUFFIX));
+        let clang = clang_bin.join(format!("clang{}", env::consts::EXE_SUFFIX));
+        write(&gcc, "").unwrap();
+        write(&clang, "").unwrap();
+
+        assert_eq!(
+            find_tool("esp", toolchain_dir, "xtensa-esp-elf-gcc").unwrap(),
+            gcc
+        );
+        assert_eq!(find_tool("esp", toolchain_dir, "clang").unwrap(), clang);
+        assert!(find_tool("esp", toolchain_dir, "riscv32-esp-elf-gcc").is_err());
+    }
+
+    #[test]
+    fn test_keep_download() {
+        let temp_dir = TempDir::new().unwrap();
+        let directory = temp_dir.path().join("downloads");
+        keep_download(&directory, "https://example.com/a.tar.xz", "a.tar.xz", b"a").unwrap();
+        keep_download(&directory, "https://example.com/b.zip", "b.zip", b"bb").unwrap();
+        keep_download(
+            &directory,
+            "https://example.com/v2/a.tar.xz",
+            "a.tar.xz",
+            b"aaa",
+        )
+        .unwrap();
+
+        assert_eq!(read_to_string(directory.join("a.tar.xz")).unwrap(), "aaa");
+        let manifest: serde_json::Value =
+            serde_json::from_str(&read_to_string(directory.join(DOWNLOADS_MANIFEST)).unwrap())
+                .unwrap();
+        assert_eq!(
+            manifest,
+            serde_json::json!({
+                "artifacts": [
+                    { "url": "https://example.com/b.zip", "file": "b.zip", "size": 2 },
+                    { "url": "https://example.com/v2/a.tar.xz", "file": "a.tar.xz", "size": 3 }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_move_into_place() {
+        // Destination does not exist, the whole directory is moved
+        let temp_dir = TempDir::new().unwrap();
+        let staging = temp_dir.path().join("llvm.tmp");
+        let destination = temp_dir.path().join("toolchain").join("llvm");
+        create_dir_all(staging.join("bin")).unwrap();
+        write(staging.join("bin").join("clang"), "clang").unwrap();
+        move_into_place(&staging, &destination).unwrap();
+        assert!(!staging.exists());
+        assert_eq!(
+            read_to_string(destination.join("bin").join("clang")).unwrap(),
+            "clang"
+        );
+
+        // Destination exists, entries are merged and replaced
+        let staging = temp_dir.path().join("rust.tmp");
+        create_dir_all(staging.join("bin")).unwrap();
+        write(staging.join("bin").join("rustc"), "rustc").unwrap();
+        write(staging.join("version"), "new").unwrap();
+        write(destination.join("version"), "old").unwrap();
+        write(destination.join("other"), "other").unwrap();
+        move_into_place(&staging, &destination).unwrap();
+        assert!(!staging.exists());
+        assert!(!destination.join("bin").join("clang").exists());
+        assert!(destination.join("bin").join("rustc").exists());
+        assert_eq!(read_to_string(destination.join("version")).unwrap(), "new");
+        assert!(destination.join("other").exists());
+    }
+
+    #[test]
+    fn test_unpack_tar_copy_symlinks() {
+        let mut builder = Builder::new(Vec::new());
+        // The symlinks come before their targets, and one of them points to another symlink
+        for (path, link_name) in [
+            ("lib/libclang.so", "libclang.so.16"),
+            ("lib/libclang.so.16", "libclang.so.16.0.0"),
+            ("include", "lib/headers"),
+            ("broken", "missing"),
+            ("escaping", "../secret"),
+            ("lib/escaping", "../../secret"),
+        ] {
+            let mut header = Header::new_gnu();
+            header.set_entry_type(EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, path, link_name).unwrap();
+        }
+        for path in ["lib/libclang.so.16.0.0", "lib/headers/clang.h"] {
+            let mut header = Header::new_gnu();
+            header.set_size(5);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, path, &b"clang"[..])
+                .unwrap();
+        }
+        let bytes = builder.into_inner().unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        write(temp_dir.path().join("secret"), "secret").unwrap();
+        let output_dir = temp_dir.path().join("llvm");
+        create_dir_all(&output_dir).unwrap();
+        let output = output_dir.display().to_string();
+        let mut progress = ExtractionProgress::new("llvm.tar", &DownloadOptions::default());
+        unpack_tar(Archive::new(&bytes[..]), &output, true, &mut progress).unwrap();
+        for path in ["lib/libclang.so", "lib/libclang.so.16", "include/clang.h"] {
+            let path = output_dir.join(path);
+            assert!(!path.is_symlink());
+            assert_eq!(read_to_string(path).unwrap(), "clang");
+        }
+        assert!(!output_dir.join("broken").exists());
+        // The symlinks escaping the output directory are not copied
+        assert!(!output_dir.join("escaping").exists());
+        assert!(!output_dir.join("lib").join("escaping").exists());
+    }
+
+    #[test]
+    fn test_confirm_download_size() {
+        let estimates = [Estimate {
+            name: "LLVM".to_string(),
+            download: 2 << 30,
+            ..Default::default()
+        }];
+        assert!(confirm_download_size(&estimates, 4 << 30, false, OutputFormat::Json).is_ok());
+        assert!(confirm_download_size(&estimates, 1 << 30, true, OutputFormat::Json).is_ok());
+        // The JSON output cannot be interrupted by a prompt
+        assert!(matches!(
+            confirm_download_size(&estimates, 1 << 30, false, OutputFormat::Json),
+            Err(Error::DownloadNotConfirmed(..))
+        ));
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let hash = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(verify_checksum("abc.tar.xz", b"abc", Some(hash), false).is_ok());
+        assert!(verify_checksum("abc.tar.xz", b"abc", Some(&hash.to_uppercase()), true).is_ok());
+        assert!(matches!(
+            verify_checksum("abd.tar.xz", b"abd", Some(hash), false),
+            Err(Error::ChecksumMismatch(..))
+        ));
+        assert!(verify_checksum("abd.tar.xz", b"abd", None, false).is_ok());
+        // Strict mode rejects the artifacts it cannot verify
+        assert!(matches!(
+            verify_checksum("abd.tar.xz", b"abd", None, true),
+            Err(Error::StrictMode(..))
+        ));
+    }
+
+    #[test]
+    fn test_check_archive_signature() {
+        assert!(check_archive_signature("rust.tar.xz", b"\xfd7zXZ\x00\x00").is_ok());
+        assert!(check_archive_signature("gcc.tar.gz", b"\x1f\x8b\x08").is_ok());
+        assert!(check_archive_signature("llvm.zip", b"PK\x03\x04").is_ok());
+        assert!(check_archive_signature("esp.toml", b"targets = \"esp32\"").is_ok());
+        let reason = |file_name, bytes| match check_archive_signature(file_name, bytes) {
+            Err(Error::UnexpectedContent(_, reason)) => reason,
+            result => panic!("unexpected result: {result:?}"),
+        };
+        assert_eq!(
+            reason("rust.tar.xz", b"\n<!DOCTYPE html><html>"),
+            "the server sent an HTML or XML page"
+        );
+        assert_eq!(reason("gcc.tar.gz", b""), "the download is empty");
+        assert_eq!(
+            reason("llvm.zip", b"\xfd7zXZ\x00"),
+            "it is not a zip archive"
+        );
+    }
+
+    #[test]
+    fn test_change_display() {
+        assert_eq!(
+            Change::Download {
+                url: "https://example.com/llvm.tar.xz".to_string(),
+                destination: PathBuf::from("/esp/llvm"),
+            }
+            .to_string(),
+            "Download 'https://example.com/llvm.tar.xz' into '/esp/llvm'"
+        );
+        assert_eq!(
+            Change::variable("LIBCLANG_PATH", "/esp/llvm/lib").to_string(),
+            "Set LIBCLANG_PATH to '/esp/llvm/lib'"
+        );
+        assert_eq!(
+            Change::path("/esp/gcc/bin").to_string(),
+            "Prepend '/esp/gcc/bin' to PATH"
+        );
+        assert_eq!(
+            serde_json::to_string(&Change::Remove {
+                path: PathBuf::from("/esp")
+            })
+            .unwrap(),
+            r#"{"action":"remove","path":"/esp"}"#
+        );
+    }
+
+    #[derive(Debug, Default)]
+    struct SlowFetch {
+        active: AtomicUsize,
+        max_active: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Fetch for SlowFetch {
+        async fn fetch(&self, _url: &str) -> Result<Bytes, Error> {
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_active.fetch_max(active, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            Ok(Bytes::from_static(b"gcc"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_slots() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_directory = temp_dir.path().display().to_string();
+        let fetcher = Arc::new(SlowFetch::default());
+        let options = DownloadOptions {
+            download_slots: Arc::new(Semaphore::new(2)),
+            fetcher: fetcher.clone(),
+            ..Default::default()
+        };
+        let download = |name: &'static str| {
+            download_file(
+                format!("https://example.com/{name}"),
+                name,
+                &output_directory,
+                false,
+                false,
+                &options,
+            )
+        };
+
+        tokio::try_join!(
+            download("xtensa-esp-elf"),
+            download("riscv32-esp-elf"),
+            download("rust"),
+            download("rust-src"),
+        )
+        .unwrap();
+        assert_eq!(fetcher.max_active.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            read_to_string(temp_dir.path().join("rust-src")).unwrap(),
+            "gcc"
+        );
+    }
+
+    #[test]
+    fn test_prune_documentation() {
+        let temp_dir = TempDir::new().unwrap();
+        let gcc = temp_dir.path().join("xtensa-esp-elf");
+        for dir in [
+            "bin",
+            "share/doc/gcc",
+            "share/info",
+            "share/man/man1",
+            "share/gcc",
+        ] {
+            create_dir_all(gcc.join(dir)).unwrap();
+        }
+        write(gcc.join("bin/xtensa-esp-elf-gcc"), "gcc").unwrap();
+        write(gcc.join("share/doc/gcc/index.html"), "<html></html>").unwrap();
+        write(gcc.join("share/info/gcc.info"), "info").unwrap();
+        write(gcc.join("share/man/man1/gcc.1"), "man").unwrap();
+        write(gcc.join("share/gcc/python"), "python").unwrap();
+
+        assert_eq!(prune_documentation(temp_dir.path()).unwrap(), 20);
+        assert!(gcc.join("bin/xtensa-esp-elf-gcc").exists());
+        assert!(gcc.join("share/gcc/python").exists());
+        assert!(!gcc.join("share/doc").exists());
+        assert!(!gcc.join("share/info").exists());
+        assert!(!gcc.join("share/man").exists());
+        assert_eq!(prune_documentation(temp_dir.path()).unwrap(), 0);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_uninstall_part() {
+        let gcc = ComponentRecord {
+            name: "GCC (xtensa-esp-elf)".to_string(),
+            ..Default::default()
+        };
+        assert!(UninstallPart::Gcc.includes(&gcc));
+        assert!(!UninstallPart::Llvm.includes(&gcc));
+        assert!(!UninstallPart::EspIdf("v5.1.2".to_string()).includes(&gcc));
+
+        let removed = vec![PathBuf::from("/esp/xtensa-esp-elf/esp-13.2.0_20230928")];
+        assert!(UninstallPart::Gcc.is_exported_by(
+            "export PATH=\"/esp/xtensa-esp-elf/esp-13.2.0_20230928/xtensa-esp-elf/bin:$PATH\"",
+            &removed
+        ));
+        assert!(!UninstallPart::Gcc.is_exported_by(
+            "export PATH=\"/esp/riscv32-esp-elf/esp-13.2.0_20230928/riscv32-esp-elf/bin:$PATH\"",
+            &removed
+        ));
+        let esp_idf = UninstallPart::EspIdf("v5.1.2".to_string());
+        assert!(esp_idf.is_exported_by("export ESP_IDF_VERSION=\"v5.1.2\"", &[]));
+        assert!(!esp_idf.is_exported_by("export ESP_IDF_VERSION=\"v5.2\"", &[]));
+    }
+
+    #[test]
+    fn test_existing_toolchain() {
+        assert_eq!(
+            parse_existing_toolchain_answer("O\n"),
+            Some(ExistingToolchain::Overwrite)
+        );
+        assert_eq!(
+            parse_existing_toolchain_answer("backup"),
+            Some(ExistingToolchain::Backup)
+        );
+        assert_eq!(
+            parse_existing_toolchain_answer("\n"),
+            Some(ExistingToolchain::Abort)
+        );
+        assert_eq!(parse_existing_toolchain_answer("yes"), None);
+    }
+}
@@ -0,0 +1,261 @@
+//! Options of the installations, set by the command line or by the tools embedding espup.
+
+use crate::{
+    shell::Shell,
+    targets::Target,
+    toolchain::{DownloadOptions, DEFAULT_NIGHTLY_VERSION},
+};
+use std::{collections::HashSet, num::NonZeroUsize, path::PathBuf};
+use strum::IntoEnumIterator;
+
+/// What to do with a toolchain of the same name that was not installed by espup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ExistingToolchain {
+    /// Ask in the terminal, or back the toolchain up when espup is not run from one.
+    #[default]
+    Prompt,
+    /// Delete the toolchain and install over it.
+    Overwrite,
+    /// Move the toolchain into a timestamped backup, restorable with 'espup restore-backup'.
+    Backup,
+    /// Stop the installation.
+    Abort,
+}
+
+/// How the export file sets up the ESP-IDF given with '--esp-idf-version'.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum IdfExportMode {
+    /// Only set `ESP_IDF_VERSION`, esp-idf-sys sets up the ESP-IDF when building.
+    #[default]
+    Variables,
+    /// Also source the export script of the ESP-IDF, which sets up its tools as they are when the export file is sourced.
+    SourceScript,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// Human readable output.
+    #[default]
+    Text,
+    /// JSON result on stdout, the logs are still written to stderr.
+    Json,
+}
+
+/// Options of an installation or update.
+///
+/// The fields match the options of 'espup install', whose documentation describes them in
+/// detail, and their default values are the ones of 'espup install' without arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstallOptions {
+    /// Also update LLVM, the GCC toolchains and the extra crates, not only Xtensa Rust.
+    pub all: bool,
+    /// Only install what the architecture family ('xtensa' or 'riscv') requires.
+    pub arch: Option<String>,
+    /// Move the previous toolchain into a timestamped backup instead of replacing it.
+    pub backup: bool,
+    /// Install rustup when it is missing.
+    pub bootstrap_rustup: bool,
+    /// Bundle the artifacts are installed from, a directory or a tar archive.
+    pub bundle: Option<PathBuf>,
+    /// Directory where the downloaded archives are cached.
+    pub cache_dir: Option<PathBuf>,
+    /// Download size, in bytes, above which the installation asks for confirmation.
+    pub confirm_above: u64,
+    /// Hosts whose downloads are authenticated with the credentials of the git credential helpers.
+    pub credential_host: Vec<String>,
+    /// Target triple of the host. Defaults to the detected one.
+    pub default_host: Option<String>,
+    /// Number of artifacts downloaded in parallel.
+    pub download_jobs: NonZeroUsize,
+    /// Only plan the installation, without changing anything.
+    pub dry_run: bool,
+    /// ESP-IDF version used by esp-idf-sys.
+    pub esp_idf_version: Option<String>,
+    /// What to do when a toolchain of the same name exists, but was not installed by espup.
+    pub existing_toolchain: ExistingToolchain,
+    /// Options set explicitly, which the configuration file does not replace (see
+    /// [`InstallOptions::is_explicit`]).
+    pub explicit_args: Vec<String>,
+    /// Variables added to the export file.
+    pub export_append: Vec<(String, String)>,
+    /// Path of the export file. Defaults to one under the espup home directory.
+    pub export_file: Option<PathBuf>,
+    /// Install the whole LLVM instead of only its libs.
+    pub extended_llvm: bool,
+    /// Extra crates to install.
+    pub extra_crates: Vec<String>,
+    /// Reinstall the Xtensa Rust toolchain even when it is already of the version.
+    pub force: bool,
+    /// URL of an installation profile whose settings take precedence over the options.
+    pub from_url: Option<String>,
+    /// Expected SHA-256 of the installation profile.
+    pub from_url_sha256: Option<String>,
+    /// Seconds a step can run without output before it is logged as still running, 0 disables it.
+    pub heartbeat_interval: u64,
+    /// How the export file sets up the ESP-IDF.
+    pub idf_export_mode: IdfExportMode,
+    /// Disable the validation of the TLS certificates of the downloads.
+    pub insecure: bool,
+    /// Number of archives extracted in parallel. Defaults to the number of CPUs.
+    pub jobs: Option<NonZeroUsize>,
+    /// Print the paths of `print_paths` as JSON.
+    pub json: bool,
+    /// Directory where a copy of the downloaded archives is kept.
+    pub keep_downloads: Option<PathBuf>,
+    /// Directory where LLVM is installed. Defaults to the toolchain directory.
+    pub llvm_path: Option<PathBuf>,
+    /// Largest artifact downloaded, in bytes, 0 does not limit their size.
+    pub max_download_size: u64,
+    /// Xtensa Rust toolchain name.
+    pub name: String,
+    /// Nightly Rust toolchain version.
+    pub nightly_version: String,
+    /// Copy files instead of creating symlinks.
+    pub no_symlink: bool,
+    /// Do not print the tips of the installed chips.
+    pub no_tips: bool,
+    /// Install without any network access, from the artifacts of the bundle.
+    pub offline: bool,
+    /// Format of the output.
+    pub output: OutputFormat,
+    /// Also generate an export file per target.
+    pub per_target_exports: bool,
+    /// Reuse compatible tools already available in the system instead of downloading them.
+    pub prefer_system_tools: bool,
+    /// Print the resolved installation paths, without installing anything.
+    pub print_paths: bool,
+    /// Install the smallest footprint.
+    pub profile_minimal: bool,
+    /// Proxy of every request. Defaults to the proxy environment variables.
+    pub proxy: Option<String>,
+    /// Install probe-rs.
+    pub probe_rs: bool,
+    /// Only print errors and a final summary.
+    pub quiet: bool,
+    /// Number of times a download failing with a transient error is retried.
+    pub retries: usize,
+    /// Seconds waited before retrying a failed download, doubled after every retry.
+    pub retry_delay: u64,
+    /// Directory of a cache shared by the users of the machine.
+    pub shared_cache: Option<PathBuf>,
+    /// Shell whose syntax the export file uses. Defaults to the one of the export file, or the
+    /// detected one.
+    pub shell: Option<Shell>,
+    /// Skip the installation of GCC.
+    pub skip_gcc: bool,
+    /// Skip parsing the Xtensa Rust version.
+    pub skip_version_parse: bool,
+    /// Only install the toolchains required for STD applications.
+    pub std: bool,
+    /// Treat warnings about the requested setup as errors.
+    pub strict: bool,
+    /// Targets to install.
+    pub targets: HashSet<Target>,
+    /// Directory the toolchain is installed into, instead of the rustup home.
+    pub toolchain_destination: Option<PathBuf>,
+    /// Xtensa Rust toolchain version, or 'latest'. Defaults to the latest one.
+    pub toolchain_version: Option<String>,
+    /// Log every extracted file instead of periodic progress summaries.
+    pub verbose_extract: bool,
+    /// Verify the detached signatures of the downloaded artifacts.
+    pub verify_signatures: bool,
+    /// Install without asking for confirmation, whatever the download size.
+    pub yes: bool,
+}
+
+impl InstallOptions {
+    /// Returns true if the option was set explicitly, e.g. in the command line or by the project
+    /// manifest.
+    pub fn is_explicit(&self, id: &str) -> bool {
+        self.explicit_args.iter().any(|arg| arg == id)
+    }
+
+    /// Records an option set explicitly, e.g. by the project manifest.
+    pub fn set_explicit(&mut self, id: &str) {
+        if !self.is_explicit(id) {
+            self.explicit_args.push(id.to_string());
+        }
+    }
+}
+
+impl Default for InstallOptions {
+    /// Returns the options of 'espup install' without arguments.
+    fn default() -> Self {
+        Self {
+            all: false,
+            arch: None,
+            backup: false,
+            bootstrap_rustup: false,
+            bundle: None,
+            cache_dir: None,
+            confirm_above: 1_000_000_000,
+            credential_host: Vec::new(),
+            default_host: None,
+            download_jobs: NonZeroUsize::new(DownloadOptions::DEFAULT_DOWNLOAD_JOBS).unwrap(),
+            dry_run: false,
+            esp_idf_version: None,
+            existing_toolchain: ExistingToolchain::default(),
+            explicit_args: Vec::new(),
+            export_append: Vec::new(),
+            export_file: None,
+            extended_llvm: false,
+            extra_crates: Vec::new(),
+            force: false,
+            from_url: None,
+            from_url_sha256: None,
+            heartbeat_interval: 30,
+            idf_export_mode: IdfExportMode::default(),
+            insecure: false,
+            jobs: None,
+            json: false,
+            keep_downloads: None,
+            llvm_path: None,
+            max_download_size: 2_000_000_000,
+            name: "esp".to_string(),
+            nightly_version: DEFAULT_NIGHTLY_VERSION.to_string(),
+            no_symlink: false,
+            no_tips: false,
+            offline: false,
+            output: OutputFormat::default(),
+            per_target_exports: false,
+            prefer_system_tools: false,
+            print_paths: false,
+            profile_minimal: false,
+            proxy: None,
+            probe_rs: false,
+            quiet: false,
+            retries: 3,
+            retry_delay: 1,
+            shared_cache: None,
+            shell: None,
+            skip_gcc: false,
+            skip_version_parse: false,
+            std: false,
+            strict: false,
+            targets: Target::iter().collect(),
+            toolchain_destination: None,
+            toolchain_version: None,
+            verbose_extract: false,
+            verify_signatures: false,
+            yes: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_default_install_options() {
+        use crate::{cli::InstallOpts, toolchain::options::InstallOptions};
+
+        // The defaults of the library are the ones of the command line
+        assert_eq!(
+            InstallOptions::from(InstallOpts::default()),
+            InstallOptions::default()
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Progress reporting and cancellation of the installation.
+
+use crate::error::Error;
+use log::info;
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Steps of the installation reported to a [`Progress`] handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// The download of an artifact started.
+    DownloadStarted { file: String },
+    /// An artifact was downloaded, or found in the cache.
+    DownloadFinished { file: String, bytes: u64 },
+    /// An entry of an archive was extracted.
+    Extracting {
+        file: String,
+        files: usize,
+        bytes: u64,
+    },
+    /// An archive was completely extracted.
+    ExtractionFinished { file: String },
+    /// A component of the ecosystem was installed.
+    ComponentInstalled { name: String },
+}
+
+/// Receives the progress of the installation.
+///
+/// Downstream users of the library can provide their own implementation (e.g. to render a
+/// progress bar in a GUI) in the [`DownloadOptions`]. Events are sent from the tasks installing
+/// the components in parallel, so handlers must not block.
+///
+/// [`DownloadOptions`]: crate::toolchain::DownloadOptions
+pub trait Progress: Debug + Send + Sync {
+    /// Handles a progress event.
+    fn on_event(&self, event: ProgressEvent);
+}
+
+/// Ignores every event, used when no handler is specified.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn on_event(&self, _event: ProgressEvent) {}
+}
+
+/// Token to abort an installation from another task or thread.
+///
+/// Cancelled installations stop at the next download or extracted entry, and fail with
+/// [`Error::Cancelled`] without leaving partial extractions behind.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Requests the cancellation of the installations using the token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if the cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Returns an error if the cancellation was requested.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Logs that a silent step is still running, every interval, until it is dropped.
+///
+/// Steps like compiling a crate or installing the nightly toolchain print nothing for minutes,
+/// the heartbeats tell they did not hang.
+#[derive(Debug)]
+pub struct Heartbeat {
+    /// Whether the step finished, notified to the logging thread.
+    finished: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    /// Starts logging the heartbeats of the step, unless there is no interval.
+    pub fn start(step: &str, interval: Option<Duration>) -> Self {
+        let finished = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread = interval
+            .filter(|interval| !interval.is_zero())
+            .map(|interval| {
+                let finished = finished.clone();
+                let step = step.to_string();
+                let started = Instant::now();
+                thread::spawn(move || {
+                    let (lock, condvar) = &*finished;
+                    let mut is_finished = lock.lock().unwrap();
+                    while !*is_finished {
+                        let waited = condvar.wait_timeout(is_finished, interval).unwrap();
+                        is_finished = waited.0;
+                        if !*is_finished && waited.1.timed_out() {
+                            info!(
+                                "Still working on {step} (elapsed {})",
+                                format_elapsed(started.elapsed())
+                            );
+                        }
+                    }
+                })
+            });
+        Self { finished, thread }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.finished;
+        *lock.lock().unwrap() = true;
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Formats the time elapsed in a step, e.g. '45s' or '2m30s'.
+fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, seconds) => format!("{seconds}s"),
+        (0, minutes, seconds) => format!("{minutes}m{seconds:02}s"),
+        (hours, minutes, seconds) => format!("{hours}h{minutes:02}m{seconds:02}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        toolchain::progress::{format_elapsed, CancellationToken, Heartbeat},
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::default();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        assert!(clone.check().is_ok());
+        token.cancel();
+        assert!(clone.is_cancelled());
+        assert!(matches!(clone.check(), Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        assert_eq!(format_elapsed(Duration::from_secs(45)), "45s");
+        assert_eq!(format_elapsed(Duration::from_secs(150)), "2m30s");
+        assert_eq!(format_elapsed(Duration::from_secs(3725)), "1h02m05s");
+
+        let heartbeat = Heartbeat::start("'espflash'", None);
+        assert!(heartbeat.thread.is_none());
+        // Dropping the heartbeat stops its thread without waiting for the interval
+        let heartbeat = Heartbeat::start("'espflash'", Some(Duration::from_secs(3600)));
+        assert!(heartbeat.thread.is_some());
+        drop(heartbeat);
+    }
+}
@@ -0,0 +1,275 @@
+//! GitHub releases metadata resolution shared by all the toolchains.
+
+use crate::{error::Error, toolchain::github_query};
+use log::debug;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+/// Xtensa Rust Toolchain repository.
+pub const XTENSA_RUST_REPOSITORY: &str = "esp-rs/rust-build";
+/// LLVM Toolchain repository.
+pub const LLVM_REPOSITORY: &str = "espressif/llvm-project";
+/// GCC Toolchain repository.
+pub const GCC_REPOSITORY: &str = "espressif/crosstool-NG";
+/// ESP-IDF repository.
+pub const ESP_IDF_REPOSITORY: &str = "espressif/esp-idf";
+/// GDB repository.
+pub const GDB_REPOSITORY: &str = "espressif/binutils-gdb";
+/// OpenOCD repository.
+pub const OPENOCD_REPOSITORY: &str = "espressif/openocd-esp32";
+/// QEMU repository.
+pub const QEMU_REPOSITORY: &str = "espressif/qemu";
+
+/// Releases already queried, indexed by repository, `None` when the query failed.
+static RELEASES: OnceLock<Mutex<HashMap<String, Option<Vec<Release>>>>> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Asset {
+    /// File name of the asset.
+    pub name: String,
+    /// Size of the asset, in bytes.
+    pub size: u64,
+    /// SHA-256 of the asset, if GitHub computed it.
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    /// Tag of the release.
+    pub tag_name: String,
+    /// Whether the release is a draft.
+    pub draft: bool,
+    /// Whether the release is a prerelease.
+    pub prerelease: bool,
+    /// Whether the release is marked as yanked in its name, its artifacts are not installed.
+    pub yanked: bool,
+    /// Assets published in the release.
+    pub assets: Vec<Asset>,
+}
+
+impl Release {
+    /// Parses a release from the GitHub API JSON representation.
+    fn from_json(json: &serde_json::Value) -> Option<Self> {
+        let assets = json["assets"]
+            .as_array()
+            .map(|assets| {
+                assets
+                    .iter()
+                    .filter_map(|asset| {
+                        Some(Asset {
+                            name: asset["name"].as_str()?.to_string(),
+                            size: asset["size"].as_u64().unwrap_or_default(),
+                            sha256: asset["digest"]
+                                .as_str()
+                                .and_then(|digest| digest.strip_prefix("sha256:"))
+                                .map(str::to_lowercase),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            tag_name: json["tag_name"].as_str()?.to_string(),
+            draft: json["draft"].as_bool().unwrap_or_default(),
+            prerelease: json["prerelease"].as_bool().unwrap_or_default(),
+            yanked: json["name"]
+                .as_str()
+                .is_some_and(|name| name.to_lowercase().contains("yanked")),
+            assets,
+        })
+    }
+
+    /// Returns true if the release can be installed: it is neither a draft nor yanked.
+    pub fn is_available(&self) -> bool {
+        !self.draft && !self.yanked
+    }
+
+    /// Returns true if the release is available and is not a prerelease.
+    pub fn is_stable(&self) -> bool {
+        self.is_available() && !self.prerelease
+    }
+
+    /// Returns the asset with the given name, if published.
+    pub fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// Returns the releases of a repository, querying the GitHub API only once per repository.
+pub fn get_releases(repository: &str) -> Result<Vec<Release>, Error> {
+    let cache = RELEASES.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(releases) = cache.lock().unwrap().get(repository) {
+        debug!("Using cached releases of '{}'", repository);
+        // The query already failed after its retries, it is not retried again
+        return releases.clone().ok_or(Error::GithubQuery);
+    }
+
+    let json = github_query(&format!(
+        "https://api.github.com/repos/{repository}/releases"
+    ))
+    .map_err(|error| {
+        cache.lock().unwrap().insert(repository.to_string(), None);
+        error
+    })?;
+    let releases: Vec<Release> = json
+        .as_array()
+        .ok_or(Error::SerializeJson)?
+        .iter()
+        .filter_map(Release::from_json)
+        .collect();
+    cache
+        .lock()
+        .unwrap()
+        .insert(repository.to_string(), Some(releases.clone()));
+    Ok(releases)
+}
+
+/// Returns the latest stable release of a repository.
+pub fn get_latest_release(repository: &str) -> Result<Release, Error> {
+    get_releases(repository)?
+        .into_iter()
+        .find(Release::is_stable)
+        .ok_or(Error::GithubQuery)
+}
+
+/// Returns the release matching the tag, if it exists.
+///
+/// The listing of [`get_releases`] only holds the latest releases, the older ones are queried by
+/// their tag and added to it.
+pub fn get_release(repository: &str, tag: &str) -> Result<Option<Release>, Error> {
+    if let Some(release) = get_releases(repository)?
+        .into_iter()
+        .find(|release| release.tag_name == tag)
+    {
+        return Ok(Some(release));
+    }
+
+    let release = match github_query(&format!(
+        "https://api.github.com/repos/{repository}/releases/tags/{tag}"
+    )) {
+        Ok(json) => Release::from_json(&json),
+        Err(error) => {
+            debug!(
+                "Failed to get the release '{}' of '{}': {}",
+                tag, repository, error
+            );
+            None
+        }
+    };
+    if let Some(release) = &release {
+        let cache = RELEASES.get_or_init(|| Mutex::new(HashMap::new()));
+        if let Some(Some(releases)) = cache.lock().unwrap().get_mut(repository) {
+            releases.push(release.clone());
+        }
+    }
+    Ok(release)
+}
+
+/// Returns the download URL of a release asset.
+pub fn asset_url(repository: &str, tag: &str, asset: &str) -> String {
+    format!("https://github.com/{repository}/releases/download/{tag}/{asset}")
+}
+
+/// Splits the download URL of a release asset into its repository, tag and asset name.
+pub fn parse_asset_url(url: &str) -> Option<(&str, &str, &str)> {
+    let (repository, download) = url
+        .strip_prefix("https://github.com/")?
+        .split_once("/releases/download/")?;
+    let (tag, asset) = download.split_once('/')?;
+    Some((repository, tag, asset))
+}
+
+/// Returns the published SHA-256 of the release asset the URL downloads, or `None` if the release
+/// does not publish one. URLs that are not release assets have none.
+///
+/// Errors are returned when the release cannot be looked up.
+pub fn asset_sha256(url: &str) -> Result<Option<String>, Error> {
+    let Some((repository, tag, asset)) = parse_asset_url(url) else {
+        return Ok(None);
+    };
+    Ok(get_release(repository, tag)?.and_then(|release| release.asset(asset)?.sha256.clone()))
+}
+
+/// Returns the published size of the release asset the URL downloads, if it is known.
+pub fn asset_size(url: &str) -> Option<u64> {
+    let (repository, tag, asset) = parse_asset_url(url)?;
+    match get_release(repository, tag) {
+        Ok(release) => Some(release?.asset(asset)?.size).filter(|size| *size > 0),
+        Err(error) => {
+            debug!("Failed to get the size of '{}': {}", url, error);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::toolchain::releases::{
+        asset_sha256, asset_url, parse_asset_url, Asset, Release, XTENSA_RUST_REPOSITORY,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_release_from_json() {
+        let json = json!({
+            "tag_name": "v1.73.0.1",
+            "name": "v1.73.0.1 (Yanked)",
+            "draft": false,
+            "prerelease": true,
+            "assets": [
+                { "name": "rust-src-1.73.0.1.tar.xz", "size": 42, "digest": "sha256:ABC123" },
+                { "name": "missing-size" },
+                { "size": 1 }
+            ]
+        });
+        let release = Release::from_json(&json).unwrap();
+        assert_eq!(release.tag_name, "v1.73.0.1");
+        assert!(!release.draft);
+        assert!(release.prerelease);
+        assert!(release.yanked);
+        assert!(!release.is_available());
+        assert_eq!(
+            release.asset("rust-src-1.73.0.1.tar.xz"),
+            Some(&Asset {
+                name: "rust-src-1.73.0.1.tar.xz".to_string(),
+                size: 42,
+                sha256: Some("abc123".to_string()),
+            })
+        );
+        assert_eq!(release.assets.len(), 2);
+        assert!(release.asset("rust-1.73.0.1.tar.xz").is_none());
+        assert!(Release::from_json(&json!({ "draft": true })).is_none());
+    }
+
+    #[test]
+    fn test_asset_url() {
+        assert_eq!(
+            asset_url(XTENSA_RUST_REPOSITORY, "v1.73.0.1", "rust-src-1.73.0.1.tar.xz"),
+            "https://github.com/esp-rs/rust-build/releases/download/v1.73.0.1/rust-src-1.73.0.1.tar.xz"
+        );
+        assert_eq!(
+            parse_asset_url(&asset_url(
+                XTENSA_RUST_REPOSITORY,
+                "v1.73.0.1",
+                "rust-src-1.73.0.1.tar.xz"
+            )),
+            Some((
+                XTENSA_RUST_REPOSITORY,
+                "v1.73.0.1",
+                "rust-src-1.73.0.1.tar.xz"
+            ))
+        );
+        assert_eq!(parse_asset_url("https://example.com/llvm.tar.xz"), None);
+        // Only release assets are looked up
+        assert_eq!(
+            asset_sha256(
+                "https://static.rust-lang.org/rustup/dist/x86_64-unknown-linux-gnu/rustup-init"
+            )
+            .unwrap(),
+            None
+        );
+    }
+}
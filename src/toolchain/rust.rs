@@ -1,47 +1,120 @@
 //! Xtensa Rust Toolchain source and installation tools.
 
+pub use crate::paths::{get_cargo_home, get_rustup_home};
 use crate::{
     error::Error,
+    host_info::find_executable,
     host_triple::HostTriple,
+    state::State,
+    targets::Target,
     toolchain::{
+        components::COMPONENTS_DIR,
         download_file,
+        fetch::proxy_env,
         gcc::{RISCV_GCC, XTENSA_GCC},
-        github_query,
         llvm::CLANG_NAME,
-        Installable,
+        progress::Heartbeat,
+        prune_documentation,
+        releases::{asset_url, get_releases, Release, XTENSA_RUST_REPOSITORY},
+        Change, DownloadOptions, Installable,
     },
 };
 use async_trait::async_trait;
-use directories::BaseDirs;
 use log::{debug, info, warn};
 use miette::Result;
-use regex::Regex;
 #[cfg(unix)]
 use std::fs::create_dir_all;
 use std::{
+    collections::HashSet,
     env,
-    fmt::Debug,
+    fmt::{self, Debug, Display, Formatter},
     fs::read_dir,
     io,
     path::{Path, PathBuf},
     process::{Command, Stdio},
+    str::FromStr,
 };
 #[cfg(unix)]
 use tempfile::tempdir_in;
 use tokio::fs::{remove_dir_all, remove_file};
 
-/// Xtensa Rust Toolchain repository
-const DEFAULT_XTENSA_RUST_REPOSITORY: &str =
-    "https://github.com/esp-rs/rust-build/releases/download";
-/// Xtensa Rust Toolchain API URL
-const XTENSA_RUST_LATEST_API_URL: &str =
-    "https://api.github.com/repos/esp-rs/rust-build/releases/latest";
-const XTENSA_RUST_API_URL: &str = "https://api.github.com/repos/esp-rs/rust-build/releases";
+/// Version of the Xtensa Rust toolchain, following the '<major>.<minor>.<patch>.<subpatch>' scheme.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct XtensaRustVersion {
+    /// Major version of the Rust release.
+    pub major: u32,
+    /// Minor version of the Rust release.
+    pub minor: u32,
+    /// Patch version of the Rust release.
+    pub patch: u32,
+    /// Revision of the Xtensa Rust build for the Rust release.
+    pub subpatch: u32,
+}
+
+impl XtensaRustVersion {
+    /// Create a new instance.
+    pub fn new(major: u32, minor: u32, patch: u32, subpatch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            subpatch,
+        }
+    }
+
+    /// Returns true if the version belongs to the '<major>.<minor>.<patch>' Rust release.
+    pub fn is_release_of(&self, major: u32, minor: u32, patch: u32) -> bool {
+        (self.major, self.minor, self.patch) == (major, minor, patch)
+    }
+}
+
+impl FromStr for XtensaRustVersion {
+    type Err = Error;
+
+    /// Parses a '<major>.<minor>.<patch>.<subpatch>' version, optionally prefixed with 'v' as in the release tags.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_version_components(s.strip_prefix('v').unwrap_or(s)).as_deref() {
+            Some(&[major, minor, patch, subpatch]) => Ok(Self::new(major, minor, patch, subpatch)),
+            _ => Err(Error::InvalidVersion(s.to_string())),
+        }
+    }
+}
+
+impl Display for XtensaRustVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.patch, self.subpatch
+        )
+    }
+}
+
+/// Parses the dot separated numeric components of a version, rejecting leading zeros.
+fn parse_version_components(version: &str) -> Option<Vec<u32>> {
+    version
+        .split('.')
+        .map(|component| {
+            if component.is_empty()
+                || !component.chars().all(|c| c.is_ascii_digit())
+                || (component.len() > 1 && component.starts_with('0'))
+            {
+                return None;
+            }
+            component.parse().ok()
+        })
+        .collect()
+}
+
+/// Value of '--toolchain-version' selecting the newest published release, the default.
+pub const LATEST_VERSION: &str = "latest";
 
-/// Xtensa Rust Toolchain version regex.
-pub const RE_EXTENDED_SEMANTIC_VERSION: &str = r"^(?P<major>0|[1-9]\d*)\.(?P<minor>0|[1-9]\d*)\.(?P<patch>0|[1-9]\d*)\.(?P<subpatch>0|[1-9]\d*)?$";
-const RE_SEMANTIC_VERSION: &str =
-    r"^(?P<major>0|[1-9]\d*)\.(?P<minor>0|[1-9]\d*)\.(?P<patch>0|[1-9]\d*)?$";
+/// Rust targets added for every RISC-V chip, the ones of the other chips are only added when
+/// they are selected (see [`RiscVTarget::add_chips`]).
+pub const RISCV_TARGETS: [&str; 2] = [
+    "riscv32imc-unknown-none-elf",
+    "riscv32imac-unknown-none-elf",
+];
 
 #[derive(Debug, Clone, Default)]
 pub struct XtensaRust {
@@ -51,8 +124,12 @@ pub struct XtensaRust {
     pub dist_file: String,
     /// Xtensa Rust toolchain URL.
     pub dist_url: String,
+    /// Reinstalls the toolchain even when the installed one is of the version.
+    pub force: bool,
     /// Host triple.
     pub host_triple: String,
+    /// Xtensa Rust toolchain name.
+    pub name: String,
     /// LLVM Toolchain path.
     pub path: PathBuf,
     /// Path to the rustup home directory.
@@ -66,34 +143,48 @@ pub struct XtensaRust {
     /// Xtensa Rust toolchain destination path.
     pub toolchain_destination: PathBuf,
     /// Xtensa Rust Toolchain version.
-    pub version: String,
+    pub version: XtensaRustVersion,
 }
 
 impl XtensaRust {
-    /// Get the latest version of Xtensa Rust toolchain.
-    pub async fn get_latest_version() -> Result<String> {
-        let json = github_query(XTENSA_RUST_LATEST_API_URL)?;
-        let mut version = json["tag_name"].to_string();
-
-        version.retain(|c| c != 'v' && c != '"');
-        Self::parse_version(&version)?;
+    /// Get the latest version of Xtensa Rust toolchain with complete artifacts for the host.
+    pub async fn get_latest_version(host_triple: &HostTriple) -> Result<XtensaRustVersion> {
+        let releases = get_releases(XTENSA_RUST_REPOSITORY)?;
+        let version = latest_complete_version(&releases, host_triple)?;
         debug!("Latest Xtensa Rust version: {}", version);
         Ok(version)
     }
 
+    /// Returns the release assets required to install the version in the host.
+    pub fn required_assets(version: &XtensaRustVersion, host_triple: &HostTriple) -> Vec<String> {
+        let artifact_extension = get_artifact_extension(host_triple);
+        let mut assets = vec![format!("rust-{version}-{host_triple}.{artifact_extension}")];
+        // The Windows artifacts already include the sources
+        if artifact_extension != "zip" {
+            assets.push(format!("rust-src-{version}.{artifact_extension}"));
+        }
+        assets
+    }
+
     /// Create a new instance.
-    pub fn new(toolchain_version: &str, host_triple: &HostTriple, toolchain_path: &Path) -> Self {
+    pub fn new(
+        version: &XtensaRustVersion,
+        host_triple: &HostTriple,
+        name: &str,
+        toolchain_path: &Path,
+    ) -> Self {
         let artifact_extension = get_artifact_extension(host_triple);
-        let version = toolchain_version.to_string();
+        let version = *version;
         let dist = format!("rust-{version}-{host_triple}");
         let dist_file = format!("{dist}.{artifact_extension}");
-        let dist_url = format!("{DEFAULT_XTENSA_RUST_REPOSITORY}/v{version}/{dist_file}");
+        let tag = format!("v{version}");
+        let dist_url = asset_url(XTENSA_RUST_REPOSITORY, &tag, &dist_file);
         #[cfg(unix)]
         let src_dist = format!("rust-src-{version}");
         #[cfg(unix)]
         let src_dist_file = format!("{src_dist}.{artifact_extension}");
         #[cfg(unix)]
-        let src_dist_url = format!("{DEFAULT_XTENSA_RUST_REPOSITORY}/v{version}/{src_dist_file}");
+        let src_dist_url = asset_url(XTENSA_RUST_REPOSITORY, &tag, &src_dist_file);
         let cargo_home = get_cargo_home();
         let rustup_home = get_rustup_home();
         let toolchain_destination = toolchain_path.to_path_buf();
@@ -102,7 +193,9 @@ impl XtensaRust {
             cargo_home,
             dist_file,
             dist_url,
+            force: false,
             host_triple: host_triple.to_string(),
+            name: name.to_string(),
             path: toolchain_path.to_path_buf(),
             rustup_home,
             #[cfg(unix)]
@@ -115,67 +208,57 @@ impl XtensaRust {
     }
 
     /// Parses the version of the Xtensa toolchain.
-    pub fn parse_version(arg: &str) -> Result<String, Error> {
+    ///
+    /// '<major>.<minor>.<patch>' versions resolve to the latest published revision of the release.
+    pub fn parse_version(arg: &str, host_triple: &HostTriple) -> Result<XtensaRustVersion, Error> {
         debug!("Parsing Xtensa Rust version: {}", arg);
-        let re_extended = Regex::new(RE_EXTENDED_SEMANTIC_VERSION).unwrap();
-        let re_semver = Regex::new(RE_SEMANTIC_VERSION).unwrap();
-        let json = github_query(XTENSA_RUST_API_URL)?;
-        if re_semver.is_match(arg) {
-            let mut extended_versions: Vec<String> = Vec::new();
-            for release in json.as_array().unwrap() {
-                let tag_name = release["tag_name"].to_string().replace(['\"', 'v'], "");
-                if tag_name.starts_with(arg) {
-                    extended_versions.push(tag_name);
-                }
-            }
-            if extended_versions.is_empty() {
-                return Err(Error::InvalidVersion(arg.to_string()));
-            }
-            let mut max_version = extended_versions.pop().unwrap();
-            let mut max_subpatch = 0;
-            for version in extended_versions {
-                let subpatch: i8 = re_extended
-                    .captures(&version)
-                    .and_then(|cap| {
-                        cap.name("subpatch")
-                            .map(|subpatch| subpatch.as_str().parse().unwrap())
-                    })
-                    .unwrap();
-                if subpatch > max_subpatch {
-                    max_subpatch = subpatch;
-                    max_version = version;
-                }
-            }
-            return Ok(max_version);
-        } else if re_extended.is_match(arg) {
-            for release in json.as_array().unwrap() {
-                let tag_name = release["tag_name"].to_string().replace(['\"', 'v'], "");
-                if tag_name.starts_with(arg) {
-                    return Ok(arg.to_string());
-                }
-            }
+        resolve_version(arg, &get_releases(XTENSA_RUST_REPOSITORY)?, host_triple)
+    }
+
+    /// Returns whether the installed toolchain is of the version, or `None` if its version cannot be detected.
+    pub fn installed_version_matches(&self) -> Result<Option<bool>, Error> {
+        let toolchain_name = format!("+{}", self.name);
+        let rustc_version = Command::new("rustc")
+            .args([&toolchain_name, "--version"])
+            .stdout(Stdio::piped())
+            .output()?;
+        if !rustc_version.status.success() {
+            return Ok(None);
         }
-        Err(Error::InvalidVersion(arg.to_string()))
+        let output = String::from_utf8_lossy(&rustc_version.stdout);
+        Ok(Some(output.contains(&self.version.to_string())))
     }
 
-    /// Removes the Xtensa Rust toolchain.
-    pub async fn uninstall(toolchain_path: &Path) -> Result<(), Error> {
-        info!("Uninstalling Xtensa Rust toolchain");
-        let dir = read_dir(toolchain_path)?;
-        for entry in dir {
-            let entry_path = entry.unwrap().path();
+    /// Returns the entries of the toolchain directory installed by the Xtensa Rust toolchain.
+    ///
+    /// The GCC toolchains, LLVM and the components are left out, they do not depend on the Xtensa
+    /// Rust version.
+    pub fn installed_entries(toolchain_path: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut entries = Vec::new();
+        for entry in read_dir(toolchain_path)? {
+            let entry_path = entry?.path();
             let entry_name = entry_path.display().to_string();
             if !entry_name.contains(RISCV_GCC)
                 && !entry_name.contains(XTENSA_GCC)
                 && !entry_name.contains(CLANG_NAME)
+                && entry_path.file_name() != Some(COMPONENTS_DIR.as_ref())
             {
-                if entry_path.is_dir() {
-                    remove_dir_all(Path::new(&entry_name))
-                        .await
-                        .map_err(|_| Error::RemoveDirectory(entry_name))?;
-                } else {
-                    remove_file(&entry_name).await?;
-                }
+                entries.push(entry_path);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Removes the Xtensa Rust toolchain.
+    pub async fn uninstall(toolchain_path: &Path) -> Result<(), Error> {
+        info!("Uninstalling Xtensa Rust toolchain");
+        for entry_path in Self::installed_entries(toolchain_path)? {
+            if entry_path.is_dir() {
+                remove_dir_all(&entry_path)
+                    .await
+                    .map_err(|_| Error::RemoveDirectory(entry_path.display().to_string()))?;
+            } else {
+                remove_file(&entry_path).await?;
             }
         }
         Ok(())
@@ -184,34 +267,25 @@ impl XtensaRust {
 
 #[async_trait]
 impl Installable for XtensaRust {
-    async fn install(&self) -> Result<Vec<String>, Error> {
+    async fn install(&self, options: &DownloadOptions) -> Result<Vec<String>, Error> {
         if self.toolchain_destination.exists() {
-            let toolchain_name = format!(
-                "+{}",
-                self.toolchain_destination
-                    .file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap(),
-            );
-            let rustc_version = Command::new("rustc")
-                .args([&toolchain_name, "--version"])
-                .stdout(Stdio::piped())
-                .output()?;
-            let output = String::from_utf8_lossy(&rustc_version.stdout);
-            if rustc_version.status.success() && output.contains(&self.version) {
-                warn!(
-                "Previous installation of Xtensa Rust {} exists in: '{}'. Reusing this installation",
-                &self.version,
-                &self.toolchain_destination.display()
-            );
-                return Ok(vec![]);
-            } else {
-                if !rustc_version.status.success() {
-                    warn!("Failed to detect version of Xtensa Rust, reinstalling it");
+            match self.installed_version_matches()? {
+                Some(true) if self.force => info!(
+                    "Reinstalling Xtensa Rust {}, which is already installed",
+                    &self.version
+                ),
+                Some(true) => {
+                    warn!(
+                        "Previous installation of Xtensa Rust {} exists in: '{}'. Reusing this installation",
+                        &self.version,
+                        &self.toolchain_destination.display()
+                    );
+                    return Ok(vec![]);
                 }
-                Self::uninstall(&self.toolchain_destination).await?;
+                Some(false) => {}
+                None => warn!("Failed to detect version of Xtensa Rust, reinstalling it"),
             }
+            Self::uninstall(&self.toolchain_destination).await?;
         }
 
         info!("Installing Xtensa Rust {} toolchain", self.version);
@@ -227,14 +301,25 @@ impl Installable for XtensaRust {
             let tmp_dir = tempdir_in(path)?;
             let tmp_dir_path = &tmp_dir.path().display().to_string();
 
-            download_file(
-                self.dist_url.clone(),
-                "rust.tar.xz",
-                tmp_dir_path,
-                true,
-                false,
-            )
-            .await?;
+            // Both archives are downloaded at the same time, their components are installed in order
+            tokio::try_join!(
+                download_file(
+                    self.dist_url.clone(),
+                    "rust.tar.xz",
+                    tmp_dir_path,
+                    true,
+                    false,
+                    options,
+                ),
+                download_file(
+                    self.src_dist_url.clone(),
+                    "rust-src.tar.xz",
+                    tmp_dir_path,
+                    true,
+                    false,
+                    options,
+                ),
+            )?;
 
             info!("Installing 'rust' component for Xtensa Rust toolchain");
 
@@ -260,14 +345,6 @@ impl Installable for XtensaRust {
                 return Err(Error::XtensaRust);
             }
 
-            download_file(
-                self.src_dist_url.clone(),
-                "rust-src.tar.xz",
-                tmp_dir_path,
-                true,
-                false,
-            )
-            .await?;
             info!("Installing 'rust-src' component for Xtensa Rust toolchain");
             if !Command::new("/usr/bin/env")
                 .arg("bash")
@@ -297,9 +374,13 @@ impl Installable for XtensaRust {
                 &self.toolchain_destination.display().to_string(),
                 true,
                 true,
+                options,
             )
             .await?;
         }
+        if options.minimal {
+            prune_documentation(&self.toolchain_destination)?;
+        }
 
         Ok(vec![]) // No exports
     }
@@ -307,35 +388,130 @@ impl Installable for XtensaRust {
     fn name(&self) -> String {
         "Xtensa Rust".to_string()
     }
+
+    fn plan(&self, _options: &DownloadOptions) -> Vec<Change> {
+        let destination = self.toolchain_destination.clone();
+        let mut changes = Vec::new();
+        if destination.exists() {
+            if !self.force && matches!(self.installed_version_matches(), Ok(Some(true))) {
+                return vec![Change::Reuse { path: destination }];
+            }
+            changes.push(Change::Remove {
+                path: destination.clone(),
+            });
+        }
+        changes.push(Change::Download {
+            url: self.dist_url.clone(),
+            destination: destination.clone(),
+        });
+        #[cfg(unix)]
+        changes.push(Change::Download {
+            url: self.src_dist_url.clone(),
+            destination,
+        });
+        changes
+    }
+
+    fn version(&self) -> Option<String> {
+        Some(self.version.to_string())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct RiscVTarget {
     /// Nightly version.
     pub nightly_version: String,
+    /// Xtensa Rust toolchain name, under which the added targets are recorded.
+    pub toolchain_name: String,
+    /// Whether the 'rust-src' component is installed, STD applications build the standard library from it.
+    pub rust_src: bool,
+    /// Rust targets added to the nightly toolchain.
+    pub targets: Vec<&'static str>,
 }
 
 impl RiscVTarget {
     /// Create a crate instance.
-    pub fn new(nightly_version: &str) -> Self {
+    pub fn new(nightly_version: &str, toolchain_name: &str) -> Self {
         RiscVTarget {
             nightly_version: nightly_version.to_string(),
+            toolchain_name: toolchain_name.to_string(),
+            rust_src: true,
+            targets: RISCV_TARGETS.to_vec(),
         }
     }
 
-    /// Uninstalls the RISC-V target.
-    pub fn uninstall(nightly_version: &str) -> Result<(), Error> {
-        info!("Uninstalling RISC-V target");
+    /// Adds the no_std Rust targets of the selected RISC-V chips that are not added for every
+    /// chip, e.g. the one of the esp32p4.
+    pub fn add_chips(&mut self, chips: &HashSet<Target>) {
+        let mut chips: Vec<&Target> = chips.iter().filter(|chip| chip.is_riscv()).collect();
+        chips.sort_by_key(|chip| **chip as u8);
+        for chip in chips {
+            let target = chip.rust_target(false);
+            if !self.targets.contains(&target) {
+                self.targets.push(target);
+            }
+        }
+    }
 
-        if !Command::new("rustup")
+    /// Returns the arguments of the 'rustup' command installing the nightly toolchain and the targets.
+    fn install_args(&self) -> Vec<&str> {
+        let mut args = vec![
+            "toolchain",
+            "install",
+            &self.nightly_version,
+            "--profile",
+            "minimal",
+        ];
+        if self.rust_src {
+            args.extend(["--component", "rust-src"]);
+        }
+        args.push("--target");
+        args.extend(&self.targets);
+        args
+    }
+
+    /// Returns true if the nightly toolchain already has every RISC-V target.
+    pub fn is_installed(&self) -> bool {
+        let installed_targets = self.installed_targets();
+        self.targets.iter().all(|target| {
+            installed_targets
+                .iter()
+                .any(|installed| installed == target)
+        })
+    }
+
+    /// Returns the targets already installed in the nightly toolchain.
+    fn installed_targets(&self) -> Vec<String> {
+        match Command::new("rustup")
             .args([
                 "target",
-                "remove",
+                "list",
+                "--installed",
                 "--toolchain",
-                nightly_version,
-                "riscv32imc-unknown-none-elf",
-                "riscv32imac-unknown-none-elf",
+                &self.nightly_version,
             ])
+            .stderr(Stdio::null())
+            .output()
+        {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|line| line.trim().to_string())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Uninstalls the RISC-V targets.
+    pub fn uninstall(nightly_version: &str, targets: &[&str]) -> Result<(), Error> {
+        info!(
+            "Uninstalling RISC-V targets ({}) from '{}' toolchain",
+            targets.join(", "),
+            nightly_version
+        );
+
+        if !Command::new("rustup")
+            .args(["target", "remove", "--toolchain", nightly_version])
+            .args(targets)
             .stdout(Stdio::null())
             .status()?
             .success()
@@ -348,24 +524,21 @@ impl RiscVTarget {
 
 #[async_trait]
 impl Installable for RiscVTarget {
-    async fn install(&self) -> Result<Vec<String>, Error> {
+    async fn install(&self, options: &DownloadOptions) -> Result<Vec<String>, Error> {
         info!(
-            "Installing RISC-V Rust targets ('riscv32imc-unknown-none-elf' and 'riscv32imac-unknown-none-elf') for '{}' toolchain",            &self.nightly_version
+            "Installing RISC-V Rust targets ({}) for '{}' toolchain",
+            self.targets.join(", "),
+            &self.nightly_version
         );
 
+        let installed_targets = self.installed_targets();
+        let _heartbeat = Heartbeat::start(
+            &format!("the '{}' toolchain", self.nightly_version),
+            options.heartbeat_interval,
+        );
         if !Command::new("rustup")
-            .args([
-                "toolchain",
-                "install",
-                &self.nightly_version,
-                "--profile",
-                "minimal",
-                "--component",
-                "rust-src",
-                "--target",
-                "riscv32imc-unknown-none-elf",
-                "riscv32imac-unknown-none-elf",
-            ])
+            .args(self.install_args())
+            .envs(proxy_env())
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .status()?
@@ -374,12 +547,147 @@ impl Installable for RiscVTarget {
             return Err(Error::InstallRiscvTarget(self.nightly_version.clone()));
         }
 
+        let mut state = State::load()?;
+        let toolchain_state = state.toolchain(&self.toolchain_name);
+        for target in &self.targets {
+            if !installed_targets
+                .iter()
+                .any(|installed| installed == target)
+            {
+                toolchain_state.add_rustup_target(&self.nightly_version, target);
+            }
+        }
+        state.save()?;
+
         Ok(vec![]) // No exports
     }
 
     fn name(&self) -> String {
         "RISC-V Rust target".to_string()
     }
+
+    fn plan(&self, _options: &DownloadOptions) -> Vec<Change> {
+        vec![Change::Run {
+            command: format!("rustup {}", self.install_args().join(" ")),
+        }]
+    }
+
+    fn version(&self) -> Option<String> {
+        Some(self.nightly_version.clone())
+    }
+}
+
+/// Returns the most recent stable version whose release has every artifact the host requires.
+///
+/// Releases with missing artifacts (e.g. a host skipped in the release) are reported and skipped.
+fn latest_complete_version(
+    releases: &[Release],
+    host_triple: &HostTriple,
+) -> Result<XtensaRustVersion, Error> {
+    let mut versions: Vec<(XtensaRustVersion, &Release)> = releases
+        .iter()
+        .filter(|release| release.is_stable())
+        .filter_map(|release| Some((release.tag_name.parse().ok()?, release)))
+        .collect();
+    versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+    let latest = versions.first().map(|(version, _)| *version);
+    for (version, release) in versions {
+        let missing: Vec<String> = XtensaRust::required_assets(&version, host_triple)
+            .into_iter()
+            .filter(|asset| release.asset(asset).is_none())
+            .collect();
+        if missing.is_empty() {
+            if latest != Some(version) {
+                warn!(
+                    "Falling back to Xtensa Rust {version}, the most recent release with complete artifacts for '{host_triple}'"
+                );
+            }
+            return Ok(version);
+        }
+        warn!(
+            "Xtensa Rust {version} is missing artifacts for '{host_triple}': {}",
+            missing.join(", ")
+        );
+    }
+    Err(Error::MissingHostArtifacts(host_triple.to_string()))
+}
+
+/// Number of candidate releases listed when no release matches the requested version.
+const CANDIDATE_RELEASES: usize = 5;
+
+/// Returns the versions of the releases that can be installed in the host, most recent first:
+/// neither drafts nor yanked, with complete artifacts for the host.
+pub fn installable_versions<'a>(
+    releases: &'a [Release],
+    host_triple: &HostTriple,
+) -> Vec<(XtensaRustVersion, &'a Release)> {
+    let mut versions: Vec<(XtensaRustVersion, &Release)> = releases
+        .iter()
+        .filter(|release| release.is_available())
+        .filter_map(|release| Some((release.tag_name.parse().ok()?, release)))
+        .filter(|(version, release)| {
+            XtensaRust::required_assets(version, host_triple)
+                .iter()
+                .all(|asset| release.asset(asset).is_some())
+        })
+        .collect();
+    versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+    versions
+}
+
+/// Resolves the requested version to one of the installable releases.
+///
+/// A '<major>.<minor>.<patch>' version resolves to its latest stable release, or to its latest
+/// prerelease when it has no stable release.
+fn resolve_version(
+    arg: &str,
+    releases: &[Release],
+    host_triple: &HostTriple,
+) -> Result<XtensaRustVersion, Error> {
+    let candidates = installable_versions(releases, host_triple);
+
+    let resolved = match parse_version_components(arg).as_deref() {
+        Some(&[major, minor, patch]) => {
+            let mut matching = candidates
+                .iter()
+                .filter(|(version, _)| version.is_release_of(major, minor, patch));
+            matching
+                .clone()
+                .find(|(_, release)| !release.prerelease)
+                .or_else(|| matching.next())
+        }
+        Some(&[_, _, _, _]) => {
+            let version: XtensaRustVersion = arg.parse()?;
+            candidates
+                .iter()
+                .find(|(candidate, _)| *candidate == version)
+        }
+        _ => return Err(Error::InvalidVersion(arg.to_string())),
+    };
+    match resolved {
+        Some((version, release)) => {
+            if release.prerelease {
+                warn!("Xtensa Rust {version} is a prerelease");
+            }
+            Ok(*version)
+        }
+        None if candidates.is_empty() => Err(Error::MissingHostArtifacts(host_triple.to_string())),
+        None => Err(Error::NoMatchingRelease(
+            arg.to_string(),
+            candidates
+                .iter()
+                .take(CANDIDATE_RELEASES)
+                .map(|(version, release)| {
+                    if release.prerelease {
+                        format!("{version} (prerelease)")
+                    } else {
+                        version.to_string()
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
+        )),
+    }
 }
 
 /// Gets the artifact extension based on the host architecture.
@@ -390,32 +698,15 @@ fn get_artifact_extension(host_triple: &HostTriple) -> &str {
     }
 }
 
-/// Gets the default cargo home path.
-fn get_cargo_home() -> PathBuf {
-    PathBuf::from(env::var("CARGO_HOME").unwrap_or_else(|_e| {
-        format!(
-            "{}",
-            BaseDirs::new().unwrap().home_dir().join(".cargo").display()
-        )
-    }))
-}
-
-/// Gets the default rustup home path.
-pub fn get_rustup_home() -> PathBuf {
-    PathBuf::from(env::var("RUSTUP_HOME").unwrap_or_else(|_e| {
-        format!(
-            "{}",
-            BaseDirs::new()
-                .unwrap()
-                .home_dir()
-                .join(".rustup")
-                .display()
-        )
-    }))
-}
-
-/// Checks if rustup is installed.
-pub async fn check_rust_installation() -> Result<(), Error> {
+/// Checks if rustup is installed, installing it when `bootstrap` is set.
+///
+/// Rust installed by a package manager, without rustup, is reported with a dedicated error, as
+/// espup relies on rustup to install and select the toolchains.
+pub async fn check_rust_installation(
+    host_triple: &HostTriple,
+    bootstrap: bool,
+    options: &DownloadOptions,
+) -> Result<(), Error> {
     info!("Checking Rust installation");
 
     if let Err(e) = Command::new("rustup")
@@ -424,7 +715,13 @@ pub async fn check_rust_installation() -> Result<(), Error> {
         .output()
     {
         if let io::ErrorKind::NotFound = e.kind() {
-            return Err(Error::MissingRust);
+            if bootstrap {
+                return bootstrap_rustup(host_triple, options).await;
+            }
+            return match find_executable("rustc") {
+                Some(rustc) => Err(Error::MissingRustup(rustc.display().to_string())),
+                None => Err(Error::MissingRust),
+            };
         } else {
             return Err(Error::RustupDetection(e.to_string()));
         }
@@ -433,60 +730,445 @@ pub async fn check_rust_installation() -> Result<(), Error> {
     Ok(())
 }
 
+/// Installs rustup, without any toolchain, and adds it to the `PATH` of the current process.
+async fn bootstrap_rustup(
+    host_triple: &HostTriple,
+    options: &DownloadOptions,
+) -> Result<(), Error> {
+    info!("Installing rustup");
+    let file_name = format!("rustup-init{}", env::consts::EXE_SUFFIX);
+    let tmp_dir = tempfile::tempdir()?;
+    let rustup_init = download_file(
+        format!("https://static.rust-lang.org/rustup/dist/{host_triple}/{file_name}"),
+        &file_name,
+        &tmp_dir.path().display().to_string(),
+        false,
+        false,
+        options,
+    )
+    .await?;
+    #[cfg(unix)]
+    {
+        use std::{fs::set_permissions, os::unix::fs::PermissionsExt};
+        set_permissions(&rustup_init, PermissionsExt::from_mode(0o755))?;
+    }
+    if !Command::new(&rustup_init)
+        .args(["-y", "--default-toolchain", "none"])
+        .envs(proxy_env())
+        .stdout(Stdio::null())
+        .status()?
+        .success()
+    {
+        return Err(Error::RustupDetection(
+            "failed to install rustup".to_string(),
+        ));
+    }
+
+    let mut paths = vec![get_cargo_home().join("bin")];
+    if let Some(path) = env::var_os("PATH") {
+        paths.extend(env::split_paths(&path));
+    }
+    env::set_var(
+        "PATH",
+        env::join_paths(paths).map_err(|e| Error::RustupDetection(e.to_string()))?,
+    );
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RustupOverride {
+    /// Toolchain selected by the override.
+    pub toolchain: String,
+    /// What selects the toolchain (e.g. a directory override or a toolchain file).
+    pub source: String,
+}
+
+/// Parses the output of `rustup show active-toolchain`, returning the override it reports, if any.
+fn parse_active_toolchain(output: &str) -> Option<RustupOverride> {
+    let line = output.lines().next()?.trim();
+    let (toolchain, source) = line.split_once(" (")?;
+    let source = source.trim_end_matches(')');
+    if source.contains("override") || source.contains("overridden") {
+        Some(RustupOverride {
+            toolchain: toolchain.to_string(),
+            source: source.to_string(),
+        })
+    } else {
+        None
+    }
+}
+
+/// Gets the directory of the toolchain: the destination its installation recorded, or the rustup toolchains directory.
+pub fn get_toolchain_dir(name: &str) -> PathBuf {
+    State::load()
+        .ok()
+        .and_then(|state| state.toolchains.get(name)?.toolchain_destination.clone())
+        .unwrap_or_else(|| get_default_toolchain_dir(name))
+}
+
+/// Gets the directory of the toolchain in the rustup toolchains directory.
+pub fn get_default_toolchain_dir(name: &str) -> PathBuf {
+    get_rustup_home().join("toolchains").join(name)
+}
+
+/// Returns true if the toolchain directory holds what is left of an Xtensa Rust installation
+/// without 'bin/rustc', as an interrupted installation or update leaves it.
+///
+/// Rustup fails with confusing errors on such toolchains. Directories holding only the GCC
+/// toolchains, as the RISC-V only installations leave them, are not corrupted.
+pub fn is_corrupted_toolchain(toolchain_dir: &Path) -> bool {
+    if !toolchain_dir.is_dir() {
+        return false;
+    }
+    let rustc = toolchain_dir
+        .join("bin")
+        .join(format!("rustc{}", env::consts::EXE_SUFFIX));
+    let is_empty = read_dir(toolchain_dir).is_ok_and(|mut entries| entries.next().is_none());
+    !rustc.exists()
+        && (is_empty || toolchain_dir.join("bin").exists() || toolchain_dir.join("lib").exists())
+}
+
+/// Links the toolchain installed outside of the rustup home, so rustup finds it by its name.
+pub fn link_toolchain(name: &str, destination: &Path) -> Result<(), Error> {
+    unlink_toolchain(name)?;
+    debug!(
+        "Linking the '{}' toolchain to '{}'",
+        name,
+        destination.display()
+    );
+    if !Command::new("rustup")
+        .args(["toolchain", "link", name])
+        .arg(destination)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?
+        .success()
+    {
+        return Err(Error::LinkToolchain(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Removes the rustup link of a toolchain installed outside of the rustup home, if there is one.
+pub fn unlink_toolchain(name: &str) -> Result<(), Error> {
+    let link = get_default_toolchain_dir(name);
+    if link.is_symlink() {
+        debug!("Removing the link '{}'", link.display());
+        // Directory links are files on Unix, and directories on Windows
+        std::fs::remove_file(&link).or_else(|_| std::fs::remove_dir(&link))?;
+    }
+    Ok(())
+}
+
+/// Returns the override selecting the toolchain in the current directory, if any.
+///
+/// The `RUSTUP_TOOLCHAIN` environment variable takes precedence over directory overrides and
+/// toolchain files.
+pub fn get_rustup_override() -> Option<RustupOverride> {
+    if let Ok(toolchain) = env::var("RUSTUP_TOOLCHAIN") {
+        return Some(RustupOverride {
+            toolchain,
+            source: "environment override by RUSTUP_TOOLCHAIN".to_string(),
+        });
+    }
+    let output = Command::new("rustup")
+        .args(["show", "active-toolchain"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    parse_active_toolchain(&String::from_utf8_lossy(&output.stdout))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
+        error::Error,
+        host_triple::HostTriple,
         logging::initialize_logger,
-        toolchain::rust::{get_cargo_home, get_rustup_home, XtensaRust},
+        targets::Target,
+        toolchain::{
+            releases::{Asset, Release},
+            rust::{
+                is_corrupted_toolchain, latest_complete_version, parse_active_toolchain,
+                resolve_version, RiscVTarget, RustupOverride, XtensaRust, XtensaRustVersion,
+                RISCV_TARGETS,
+            },
+            Change, DownloadOptions, Installable,
+        },
+    };
+    use std::{
+        env,
+        fs::{create_dir_all, write},
     };
-    use directories::BaseDirs;
-    use std::env;
     use tempfile::TempDir;
 
     #[test]
-    fn test_xtensa_rust_parse_version() {
-        initialize_logger("debug");
-        assert_eq!(XtensaRust::parse_version("1.65.0.0").unwrap(), "1.65.0.0");
-        assert_eq!(XtensaRust::parse_version("1.65.0.1").unwrap(), "1.65.0.1");
-        assert_eq!(XtensaRust::parse_version("1.64.0.0").unwrap(), "1.64.0.0");
-        assert_eq!(XtensaRust::parse_version("1.63.0").unwrap(), "1.63.0.2");
-        assert_eq!(XtensaRust::parse_version("1.65.0").unwrap(), "1.65.0.1");
-        assert_eq!(XtensaRust::parse_version("1.64.0").unwrap(), "1.64.0.0");
-        assert!(XtensaRust::parse_version("422.0.0").is_err());
-        assert!(XtensaRust::parse_version("422.0.0.0").is_err());
-        assert!(XtensaRust::parse_version("a.1.1.1").is_err());
-        assert!(XtensaRust::parse_version("1.1.1.1.1").is_err());
-        assert!(XtensaRust::parse_version("1..1.1").is_err());
-        assert!(XtensaRust::parse_version("1._.*.1").is_err());
+    fn test_parse_active_toolchain() {
+        assert_eq!(
+            parse_active_toolchain("stable-x86_64-unknown-linux-gnu (default)\n"),
+            None
+        );
+        assert_eq!(
+            parse_active_toolchain(
+                "nightly-x86_64-unknown-linux-gnu (directory override for '/home/user/app')\n"
+            ),
+            Some(RustupOverride {
+                toolchain: "nightly-x86_64-unknown-linux-gnu".to_string(),
+                source: "directory override for '/home/user/app'".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_active_toolchain("esp (overridden by '/home/user/app/rust-toolchain.toml')\n"),
+            Some(RustupOverride {
+                toolchain: "esp".to_string(),
+                source: "overridden by '/home/user/app/rust-toolchain.toml'".to_string(),
+            })
+        );
+        assert_eq!(parse_active_toolchain(""), None);
+    }
+
+    #[test]
+    fn test_xtensa_rust_version() {
+        let version: XtensaRustVersion = "1.73.0.1".parse().unwrap();
+        assert_eq!(version, XtensaRustVersion::new(1, 73, 0, 1));
+        assert_eq!(version.to_string(), "1.73.0.1");
+        assert_eq!("v1.73.0.1".parse::<XtensaRustVersion>().unwrap(), version);
+        assert!(version.is_release_of(1, 73, 0));
+        assert!(!version.is_release_of(1, 73, 1));
+        assert!(version > "1.72.1.0".parse().unwrap());
+        assert!(version < "1.73.0.10".parse().unwrap());
+        assert!("1.73.0".parse::<XtensaRustVersion>().is_err());
+        assert!("1.73.0.01".parse::<XtensaRustVersion>().is_err());
+        assert!("1.73.0.1.1".parse::<XtensaRustVersion>().is_err());
+        assert!("1..0.1".parse::<XtensaRustVersion>().is_err());
+        assert!("1.+73.0.1".parse::<XtensaRustVersion>().is_err());
+    }
+
+    fn release(tag: &str, prerelease: bool, assets: &[&str]) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            draft: false,
+            prerelease,
+            yanked: false,
+            assets: assets
+                .iter()
+                .map(|name| Asset {
+                    name: name.to_string(),
+                    size: 1,
+                    sha256: None,
+                })
+                .collect(),
+        }
     }
 
     #[test]
-    fn test_get_cargo_home() {
-        // No CARGO_HOME set
-        env::remove_var("CARGO_HOME");
+    fn test_latest_complete_version() {
+        let releases = vec![
+            release(
+                "v1.75.0.0",
+                true,
+                &[
+                    "rust-1.75.0.0-aarch64-unknown-linux-gnu.tar.xz",
+                    "rust-src-1.75.0.0.tar.xz",
+                ],
+            ),
+            release(
+                "v1.74.0.0",
+                false,
+                &[
+                    "rust-1.74.0.0-x86_64-unknown-linux-gnu.tar.xz",
+                    "rust-1.74.0.0-x86_64-pc-windows-msvc.zip",
+                    "rust-src-1.74.0.0.tar.xz",
+                ],
+            ),
+            release(
+                "v1.73.0.1",
+                false,
+                &[
+                    "rust-1.73.0.1-aarch64-unknown-linux-gnu.tar.xz",
+                    "rust-1.73.0.1-x86_64-unknown-linux-gnu.tar.xz",
+                    "rust-src-1.73.0.1.tar.xz",
+                ],
+            ),
+        ];
         assert_eq!(
-            get_cargo_home(),
-            BaseDirs::new().unwrap().home_dir().join(".cargo")
+            latest_complete_version(&releases, &HostTriple::X86_64UnknownLinuxGnu).unwrap(),
+            XtensaRustVersion::new(1, 74, 0, 0)
         );
-        // CARGO_HOME set
-        let temp_dir = TempDir::new().unwrap();
-        let cargo_home = temp_dir.path().to_path_buf();
-        env::set_var("CARGO_HOME", cargo_home.to_str().unwrap());
-        assert_eq!(get_cargo_home(), cargo_home);
+        assert_eq!(
+            latest_complete_version(&releases, &HostTriple::X86_64PcWindowsMsvc).unwrap(),
+            XtensaRustVersion::new(1, 74, 0, 0)
+        );
+        assert_eq!(
+            latest_complete_version(&releases, &HostTriple::Aarch64UnknownLinuxGnu).unwrap(),
+            XtensaRustVersion::new(1, 73, 0, 1)
+        );
+        assert!(latest_complete_version(&releases, &HostTriple::Aarch64AppleDarwin).is_err());
+    }
+
+    #[test]
+    fn test_resolve_version() {
+        let host_triple = HostTriple::X86_64UnknownLinuxGnu;
+        let assets = |version: &str| {
+            [
+                format!("rust-{version}-x86_64-unknown-linux-gnu.tar.xz"),
+                format!("rust-src-{version}.tar.xz"),
+            ]
+        };
+        let complete = |tag: &str, prerelease: bool| {
+            let assets = assets(tag.trim_start_matches('v'));
+            release(tag, prerelease, &[&assets[0], &assets[1]])
+        };
+        let releases = vec![
+            complete("v1.76.0.1", true),
+            Release {
+                yanked: true,
+                ..complete("v1.76.0.0", false)
+            },
+            complete("v1.75.0.1", true),
+            complete("v1.75.0.0", false),
+            Release {
+                draft: true,
+                ..complete("v1.74.0.1", false)
+            },
+            release("v1.74.0.0", false, &["rust-src-1.74.0.0.tar.xz"]),
+        ];
+        let resolve = |arg: &str| resolve_version(arg, &releases, &host_triple);
+
+        assert_eq!(
+            resolve("1.75.0").unwrap(),
+            XtensaRustVersion::new(1, 75, 0, 0)
+        );
+        assert_eq!(
+            resolve("1.76.0").unwrap(),
+            XtensaRustVersion::new(1, 76, 0, 1)
+        );
+        assert_eq!(
+            resolve("1.75.0.1").unwrap(),
+            XtensaRustVersion::new(1, 75, 0, 1)
+        );
+        for arg in ["1.76.0.0", "1.74.0.1", "1.74.0", "1.60.0"] {
+            match resolve(arg) {
+                Err(Error::NoMatchingRelease(version, candidates)) => {
+                    assert_eq!(version, arg);
+                    assert_eq!(
+                        candidates,
+                        "1.76.0.1 (prerelease), 1.75.0.1 (prerelease), 1.75.0.0"
+                    );
+                }
+                result => panic!("unexpected resolution of '{arg}': {result:?}"),
+            }
+        }
+        assert!(matches!(resolve("1.75"), Err(Error::InvalidVersion(_))));
+        assert!(matches!(
+            resolve_version("1.75.0", &releases, &HostTriple::Aarch64AppleDarwin),
+            Err(Error::MissingHostArtifacts(_))
+        ));
     }
 
     #[test]
-    fn test_get_rustup_home() {
-        // No RUSTUP_HOME set
-        env::remove_var("RUSTUP_HOME");
+    #[cfg_attr(not(feature = "network"), ignore = "queries the GitHub releases")]
+    fn test_xtensa_rust_parse_version() {
+        initialize_logger("debug");
+        assert_eq!(
+            XtensaRust::parse_version("1.65.0.0", &HostTriple::X86_64UnknownLinuxGnu)
+                .unwrap()
+                .to_string(),
+            "1.65.0.0"
+        );
+        assert_eq!(
+            XtensaRust::parse_version("1.65.0.1", &HostTriple::X86_64UnknownLinuxGnu)
+                .unwrap()
+                .to_string(),
+            "1.65.0.1"
+        );
+        assert_eq!(
+            XtensaRust::parse_version("1.64.0.0", &HostTriple::X86_64UnknownLinuxGnu)
+                .unwrap()
+                .to_string(),
+            "1.64.0.0"
+        );
         assert_eq!(
-            get_rustup_home(),
-            BaseDirs::new().unwrap().home_dir().join(".rustup")
+            XtensaRust::parse_version("1.63.0", &HostTriple::X86_64UnknownLinuxGnu)
+                .unwrap()
+                .to_string(),
+            "1.63.0.2"
         );
-        // RUSTUP_HOME set
+        assert_eq!(
+            XtensaRust::parse_version("1.65.0", &HostTriple::X86_64UnknownLinuxGnu)
+                .unwrap()
+                .to_string(),
+            "1.65.0.1"
+        );
+        assert_eq!(
+            XtensaRust::parse_version("1.64.0", &HostTriple::X86_64UnknownLinuxGnu)
+                .unwrap()
+                .to_string(),
+            "1.64.0.0"
+        );
+        assert!(XtensaRust::parse_version("422.0.0", &HostTriple::X86_64UnknownLinuxGnu).is_err());
+        assert!(
+            XtensaRust::parse_version("422.0.0.0", &HostTriple::X86_64UnknownLinuxGnu).is_err()
+        );
+        assert!(XtensaRust::parse_version("a.1.1.1", &HostTriple::X86_64UnknownLinuxGnu).is_err());
+        assert!(
+            XtensaRust::parse_version("1.1.1.1.1", &HostTriple::X86_64UnknownLinuxGnu).is_err()
+        );
+        assert!(XtensaRust::parse_version("1..1.1", &HostTriple::X86_64UnknownLinuxGnu).is_err());
+        assert!(XtensaRust::parse_version("1._.*.1", &HostTriple::X86_64UnknownLinuxGnu).is_err());
+    }
+
+    #[test]
+    fn test_is_corrupted_toolchain() {
         let temp_dir = TempDir::new().unwrap();
-        let rustup_home = temp_dir.path().to_path_buf();
-        env::set_var("RUSTUP_HOME", rustup_home.to_str().unwrap());
-        assert_eq!(get_rustup_home(), rustup_home);
+        let toolchain_dir = temp_dir.path().join("esp");
+        assert!(!is_corrupted_toolchain(&toolchain_dir));
+        create_dir_all(&toolchain_dir).unwrap();
+        assert!(is_corrupted_toolchain(&toolchain_dir));
+        // RISC-V only installations do not install Xtensa Rust
+        create_dir_all(toolchain_dir.join("riscv32-esp-elf")).unwrap();
+        assert!(!is_corrupted_toolchain(&toolchain_dir));
+        create_dir_all(toolchain_dir.join("lib")).unwrap();
+        assert!(is_corrupted_toolchain(&toolchain_dir));
+        create_dir_all(toolchain_dir.join("bin")).unwrap();
+        write(
+            toolchain_dir
+                .join("bin")
+                .join(format!("rustc{}", env::consts::EXE_SUFFIX)),
+            "rustc",
+        )
+        .unwrap();
+        assert!(!is_corrupted_toolchain(&toolchain_dir));
+    }
+
+    #[test]
+    fn test_riscv_target_plan() {
+        let mut riscv_target = RiscVTarget::new("nightly", "esp");
+        assert_eq!(
+            riscv_target.plan(&DownloadOptions::default()),
+            vec![Change::Run {
+                command: "rustup toolchain install nightly --profile minimal --component rust-src --target riscv32imc-unknown-none-elf riscv32imac-unknown-none-elf".to_string()
+            }]
+        );
+        riscv_target.rust_src = false;
+        assert_eq!(
+            riscv_target.plan(&DownloadOptions::default()),
+            vec![Change::Run {
+                command: "rustup toolchain install nightly --profile minimal --target riscv32imc-unknown-none-elf riscv32imac-unknown-none-elf".to_string()
+            }]
+        );
+
+        // The esp32p4 has its own target, only added when it is selected
+        riscv_target.add_chips(&[Target::ESP32C3, Target::ESP32S3].into_iter().collect());
+        assert_eq!(riscv_target.targets, RISCV_TARGETS.to_vec());
+        riscv_target.add_chips(&[Target::ESP32P4, Target::ESP32C6].into_iter().collect());
+        assert_eq!(
+            riscv_target.targets,
+            vec![
+                "riscv32imc-unknown-none-elf",
+                "riscv32imac-unknown-none-elf",
+                "riscv32imafc-unknown-none-elf"
+            ]
+        );
     }
 }
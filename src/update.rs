@@ -0,0 +1,212 @@
+//! Update notifications and self-update of espup.
+
+#[cfg(feature = "network")]
+use crate::toolchain::fetch::proxy;
+use crate::{
+    error::Error,
+    host_triple::get_host_triple,
+    toolchain::{
+        download_file,
+        releases::{asset_url, get_releases, Release},
+        DownloadOptions,
+    },
+};
+use log::info;
+#[cfg(feature = "network")]
+use log::warn;
+#[cfg(feature = "network")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "network")]
+use std::time::Duration;
+use std::{cmp::Ordering, env, fs::rename};
+#[cfg(feature = "network")]
+use update_informer::{
+    http_client::{HeaderMap, HttpClient},
+    registry, Check,
+};
+
+/// espup repository.
+const ESPUP_REPOSITORY: &str = "esp-rs/espup";
+
+/// Check crates.io for a new version of the application
+#[cfg(not(feature = "network"))]
+pub fn check_for_update(_name: &str, _version: &str) {}
+
+/// Check crates.io for a new version of the application
+#[cfg(feature = "network")]
+pub fn check_for_update(name: &str, version: &str) {
+    // By setting the interval to 0 seconds we invalidate the cache with each
+    // invocation and ensure we're getting up-to-date results
+    let informer = update_informer::new(registry::Crates, name, version)
+        .interval(Duration::ZERO)
+        .http_client(ProxyHttpClient);
+
+    if let Some(version) = informer.check_version().ok().flatten() {
+        warn!("A new version of {name} ('{version}') is available");
+    }
+}
+
+/// HTTP client of the update check, going through the proxy of the other requests.
+#[cfg(feature = "network")]
+struct ProxyHttpClient;
+
+#[cfg(feature = "network")]
+impl HttpClient for ProxyHttpClient {
+    fn get<T: DeserializeOwned>(
+        url: &str,
+        timeout: Duration,
+        headers: HeaderMap,
+    ) -> update_informer::Result<T> {
+        let mut agent = ureq::AgentBuilder::new()
+            .timeout(timeout)
+            .try_proxy_from_env(true);
+        if let Some(proxy) = proxy() {
+            agent = agent.proxy(ureq::Proxy::new(proxy)?);
+        }
+        let mut request = agent.build().get(url);
+        for (header, value) in headers {
+            request = request.set(header, value);
+        }
+        Ok(request.call()?.into_json()?)
+    }
+}
+
+/// Compares two `<major>.<minor>.<patch>[-<pre>]` versions, prereleases being older than releases.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn parse(version: &str) -> (Vec<u64>, Option<&str>) {
+        let version = version.trim_start_matches('v');
+        let (numbers, pre) = match version.split_once('-') {
+            Some((numbers, pre)) => (numbers, Some(pre)),
+            None => (version, None),
+        };
+        let numbers = numbers
+            .split('.')
+            .map(|n| n.parse().unwrap_or_default())
+            .collect();
+        (numbers, pre)
+    }
+
+    let (a_numbers, a_pre) = parse(a);
+    let (b_numbers, b_pre) = parse(b);
+    a_numbers
+        .cmp(&b_numbers)
+        .then_with(|| match (a_pre, b_pre) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a_pre), Some(b_pre)) => a_pre.cmp(b_pre),
+        })
+}
+
+/// Returns the newest espup release of the channel ("stable" or "beta").
+fn get_channel_release(releases: Vec<Release>, channel: &str) -> Option<Release> {
+    releases
+        .into_iter()
+        .filter(|release| release.is_available() && (channel == "beta" || !release.prerelease))
+        .max_by(|a, b| compare_versions(&a.tag_name, &b.tag_name))
+}
+
+/// Returns the version of the latest espup release of the channel.
+pub fn get_latest_version(channel: &str) -> Result<String, Error> {
+    let release =
+        get_channel_release(get_releases(ESPUP_REPOSITORY)?, channel).ok_or(Error::GithubQuery)?;
+    Ok(release.tag_name.trim_start_matches('v').to_string())
+}
+
+/// Replaces the running espup binary with the latest release of the channel.
+pub async fn self_update(channel: &str) -> Result<(), Error> {
+    info!("Checking the '{}' channel for espup updates", channel);
+    let current_version = env!("CARGO_PKG_VERSION");
+    let release =
+        get_channel_release(get_releases(ESPUP_REPOSITORY)?, channel).ok_or(Error::GithubQuery)?;
+    if compare_versions(&release.tag_name, current_version) != Ordering::Greater {
+        info!("espup {} is already up to date", current_version);
+        return Ok(());
+    }
+
+    let host_triple = get_host_triple(None)?;
+    let asset = format!("espup-{host_triple}{}", env::consts::EXE_SUFFIX);
+    if release.asset(&asset).is_none() {
+        return Err(Error::UnsupportedHostTriple(
+            host_triple.to_string(),
+            format!("release {} does not include '{asset}'", release.tag_name),
+        ));
+    }
+    info!(
+        "Updating espup from {} to {}",
+        current_version, release.tag_name
+    );
+
+    let current_exe = env::current_exe()?;
+    let exe_dir = current_exe.parent().unwrap();
+    let tmp_dir = tempfile::tempdir_in(exe_dir)?;
+    let new_exe = download_file(
+        asset_url(ESPUP_REPOSITORY, &release.tag_name, &asset),
+        &asset,
+        &tmp_dir.path().display().to_string(),
+        false,
+        false,
+        &DownloadOptions::default(),
+    )
+    .await?;
+    #[cfg(unix)]
+    {
+        use std::{fs::set_permissions, os::unix::fs::PermissionsExt};
+        set_permissions(&new_exe, PermissionsExt::from_mode(0o755))?;
+    }
+    // Windows does not allow replacing a running executable, but it allows renaming it
+    #[cfg(windows)]
+    rename(&current_exe, current_exe.with_extension("exe.old"))?;
+    rename(new_exe, &current_exe)?;
+
+    info!("espup successfully updated to {}", release.tag_name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        toolchain::releases::Release,
+        update::{compare_versions, get_channel_release},
+    };
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("v0.8.0", "0.8.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.8.1", "0.8.0"), Ordering::Greater);
+        assert_eq!(compare_versions("0.10.0", "0.9.1"), Ordering::Greater);
+        assert_eq!(compare_versions("0.8.1-dev", "0.8.1"), Ordering::Less);
+        assert_eq!(
+            compare_versions("v0.9.0-beta.1", "0.8.1-dev"),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_get_channel_release() {
+        let release = |tag_name: &str, draft: bool, prerelease: bool| Release {
+            tag_name: tag_name.to_string(),
+            draft,
+            prerelease,
+            yanked: false,
+            assets: vec![],
+        };
+        let releases = vec![
+            release("v0.10.0", true, false),
+            release("v0.9.0-beta.1", false, true),
+            release("v0.8.0", false, false),
+            release("v0.7.0", false, false),
+        ];
+        assert_eq!(
+            get_channel_release(releases.clone(), "stable")
+                .unwrap()
+                .tag_name,
+            "v0.8.0"
+        );
+        assert_eq!(
+            get_channel_release(releases, "beta").unwrap().tag_name,
+            "v0.9.0-beta.1"
+        );
+    }
+}
@@ -0,0 +1,420 @@
+//! Installed and latest available versions of the components.
+
+#[cfg(feature = "network")]
+use crate::toolchain::fetch::client;
+use crate::{
+    env::Environment,
+    error::Error,
+    host_triple::HostTriple,
+    state::State,
+    toolchain::{
+        gcc::{DEFAULT_GCC_RELEASE, RISCV_GCC, XTENSA_GCC},
+        llvm::{get_llvm_root, Llvm},
+        releases::{get_latest_release, get_releases, ESP_IDF_REPOSITORY, XTENSA_RUST_REPOSITORY},
+        rust::{get_cargo_home, installable_versions, XtensaRustVersion},
+    },
+    update,
+};
+use log::warn;
+use serde::Serialize;
+use std::{
+    fs::{read_dir, read_to_string},
+    path::Path,
+};
+use strum::Display;
+use tokio::task::spawn_blocking;
+
+/// Component whose published versions are listed by 'espup list-remote'.
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[strum(serialize_all = "kebab-case")]
+pub enum RemoteComponent {
+    /// Xtensa Rust toolchain, the values of '--toolchain-version'.
+    #[default]
+    XtensaRust,
+    /// ESP-IDF, the values of '--esp-idf-version'.
+    EspIdf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RemoteVersion {
+    /// Version, as given on the command line.
+    pub version: String,
+    /// Whether the release is a prerelease.
+    pub prerelease: bool,
+}
+
+impl RemoteVersion {
+    /// Returns the line printed by 'espup list-remote': the version, followed by whether it is a
+    /// prerelease and the names of the toolchains it is installed as.
+    pub fn describe(&self, installed_as: &[&str]) -> String {
+        let mut notes = Vec::new();
+        if self.prerelease {
+            notes.push("prerelease".to_string());
+        }
+        if !installed_as.is_empty() {
+            let names: Vec<String> = installed_as
+                .iter()
+                .map(|name| format!("'{name}'"))
+                .collect();
+            notes.push(format!("installed as {}", names.join(", ")));
+        }
+        if notes.is_empty() {
+            self.version.clone()
+        } else {
+            format!("{} ({})", self.version, notes.join(", "))
+        }
+    }
+}
+
+/// Returns the names of the toolchains the Xtensa Rust version is installed as.
+pub fn installed_toolchains<'a>(state: &'a State, version: &str) -> Vec<&'a str> {
+    state
+        .toolchains
+        .iter()
+        .filter(|(_, toolchain)| toolchain.xtensa_rust_version.as_deref() == Some(version))
+        .map(|(name, _)| name.as_str())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ComponentVersions {
+    /// Name of the component.
+    pub name: String,
+    /// Installed versions.
+    pub installed: Vec<String>,
+    /// Latest version espup installs, `None` if it could not be determined.
+    pub latest: Option<String>,
+}
+
+impl ComponentVersions {
+    fn new(name: &str, installed: Vec<String>, latest: Option<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            installed,
+            latest,
+        }
+    }
+
+    /// Returns true if the component is installed, but not in its latest version.
+    pub fn is_outdated(&self) -> bool {
+        self.latest
+            .as_ref()
+            .is_some_and(|latest| !self.installed.is_empty() && !self.installed.contains(latest))
+    }
+
+    /// Returns the status printed by 'espup check': 'outdated', 'ok', or 'unknown' when the
+    /// latest version could not be determined.
+    pub fn status(&self) -> &'static str {
+        if self.is_outdated() {
+            "outdated"
+        } else if self.latest.is_some() {
+            "ok"
+        } else {
+            "unknown"
+        }
+    }
+
+    /// Returns the line printed by `espup versions --porcelain`: the name, installed versions
+    /// separated by commas and latest version, separated by tabs, the missing ones left empty.
+    pub fn porcelain(&self) -> String {
+        format!(
+            "{}\t{}\t{}",
+            self.name,
+            self.installed.join(","),
+            self.latest.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+/// Lists the versions installed in a directory, each one in its own `esp-<version>` subdirectory.
+fn installed_versions(directory: &Path) -> Vec<String> {
+    let mut versions: Vec<String> = read_dir(directory)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    name.trim_start_matches("esp-").to_string()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    versions.sort_unstable();
+    versions
+}
+
+/// Parses the versions of the crates installed by `cargo install`, recorded in `.crates.toml`.
+fn parse_installed_crates(contents: &str) -> Vec<(String, String)> {
+    let Ok(crates) = contents.parse::<toml::Table>() else {
+        return vec![];
+    };
+    crates
+        .get("v1")
+        .and_then(toml::Value::as_table)
+        .map(|installed| {
+            installed
+                .keys()
+                .filter_map(|key| {
+                    let mut parts = key.split_whitespace();
+                    Some((parts.next()?.to_string(), parts.next()?.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Gets the latest version of a crate published in crates.io.
+#[cfg(not(feature = "network"))]
+async fn get_latest_crate_version(_name: &str) -> Option<String> {
+    None
+}
+
+/// Gets the latest version of a crate published in crates.io.
+#[cfg(feature = "network")]
+async fn get_latest_crate_version(name: &str) -> Option<String> {
+    let response = client()
+        .ok()?
+        .get(format!("https://crates.io/api/v1/crates/{name}"))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    let json: serde_json::Value = serde_json::from_str(&response.text().await.ok()?).ok()?;
+    json["crate"]["max_stable_version"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Queries the latest version of a component published in GitHub.
+///
+/// The GitHub client is blocking, so the query runs outside of the async runtime.
+async fn query_latest<F>(name: &str, query: F) -> Option<String>
+where
+    F: FnOnce() -> Result<String, Error> + Send + 'static,
+{
+    match spawn_blocking(query).await {
+        Ok(Ok(version)) => Some(version),
+        Ok(Err(e)) => {
+            warn!("Failed to get the latest {} version: {}", name, e);
+            None
+        }
+        Err(_) => {
+            warn!("Failed to get the latest {} version", name);
+            None
+        }
+    }
+}
+
+/// Lists the published versions of the component that can be installed, most recent first.
+///
+/// Xtensa Rust versions without complete artifacts for the host are left out.
+pub fn remote_versions(
+    component: RemoteComponent,
+    host_triple: &HostTriple,
+) -> Result<Vec<RemoteVersion>, Error> {
+    Ok(match component {
+        RemoteComponent::XtensaRust => {
+            installable_versions(&get_releases(XTENSA_RUST_REPOSITORY)?, host_triple)
+                .into_iter()
+                .map(|(version, release)| RemoteVersion {
+                    version: version.to_string(),
+                    prerelease: release.prerelease,
+                })
+                .collect()
+        }
+        RemoteComponent::EspIdf => get_releases(ESP_IDF_REPOSITORY)?
+            .into_iter()
+            .filter(|release| release.is_available())
+            .map(|release| RemoteVersion {
+                version: release.tag_name,
+                prerelease: release.prerelease,
+            })
+            .collect(),
+    })
+}
+
+/// Collects the installed and latest versions of every component of the toolchain.
+pub async fn get_versions(toolchain_name: &str, toolchain_dir: &Path) -> Vec<ComponentVersions> {
+    let toolchain_state = State::load()
+        .ok()
+        .and_then(|state| state.toolchains.get(toolchain_name).cloned())
+        .unwrap_or_default();
+
+    let latest_rust: Option<XtensaRustVersion> = query_latest("Xtensa Rust", || {
+        Ok(get_latest_release(XTENSA_RUST_REPOSITORY)?.tag_name)
+    })
+    .await
+    .and_then(|tag_name| tag_name.parse().ok());
+    let mut versions = vec![
+        ComponentVersions::new(
+            "espup",
+            vec![env!("CARGO_PKG_VERSION").to_string()],
+            query_latest("espup", || update::get_latest_version("stable")).await,
+        ),
+        ComponentVersions::new(
+            "Xtensa Rust",
+            toolchain_state.xtensa_rust_version.into_iter().collect(),
+            latest_rust.map(|version| version.to_string()),
+        ),
+        ComponentVersions::new(
+            "LLVM",
+            installed_versions(&get_llvm_root(toolchain_name, toolchain_dir)),
+            latest_rust.map(|version| {
+                Llvm::version_for(&version)
+                    .trim_start_matches("esp-")
+                    .to_string()
+            }),
+        ),
+    ];
+    // The ESP-IDF is only listed when the installation selected one
+    let esp_idf_version = Environment::from_exports(&toolchain_state.exports)
+        .variables
+        .into_iter()
+        .find(|(name, _)| name == "ESP_IDF_VERSION")
+        .map(|(_, version)| version);
+    if let Some(esp_idf_version) = esp_idf_version {
+        versions.push(ComponentVersions::new(
+            "ESP-IDF",
+            vec![esp_idf_version],
+            query_latest("ESP-IDF", || {
+                Ok(get_latest_release(ESP_IDF_REPOSITORY)?.tag_name)
+            })
+            .await,
+        ));
+    }
+    for arch in [XTENSA_GCC, RISCV_GCC] {
+        versions.push(ComponentVersions::new(
+            &format!("GCC ({arch})"),
+            installed_versions(&toolchain_dir.join(arch)),
+            Some(DEFAULT_GCC_RELEASE.to_string()),
+        ));
+    }
+
+    let installed_crates = read_to_string(get_cargo_home().join(".crates.toml"))
+        .map(|contents| parse_installed_crates(&contents))
+        .unwrap_or_default();
+    for name in &toolchain_state.crates {
+        let installed = installed_crates
+            .iter()
+            .filter(|(installed_name, _)| installed_name == name)
+            .map(|(_, version)| version.clone())
+            .collect();
+        versions.push(ComponentVersions::new(
+            name,
+            installed,
+            get_latest_crate_version(name).await,
+        ));
+    }
+    versions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        state::State,
+        versions::{
+            installed_toolchains, installed_versions, parse_installed_crates, ComponentVersions,
+            RemoteVersion,
+        },
+    };
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_installed_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        create_dir_all(temp_dir.path().join("esp-16.0.0-20230516")).unwrap();
+        create_dir_all(temp_dir.path().join("esp-15.0.0-20221201")).unwrap();
+        write(temp_dir.path().join("esp-17.0.0"), "").unwrap();
+        assert_eq!(
+            installed_versions(temp_dir.path()),
+            vec!["15.0.0-20221201", "16.0.0-20230516"]
+        );
+        assert!(installed_versions(&temp_dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn test_describe_remote_version() {
+        let mut state = State::default();
+        state.toolchain("esp").xtensa_rust_version = Some("1.82.0.3".to_string());
+        state.toolchain("esp-1.82").xtensa_rust_version = Some("1.82.0.3".to_string());
+        state.toolchain("esp-1.73").xtensa_rust_version = Some("1.73.0.1".to_string());
+        let remote = RemoteVersion {
+            version: "1.82.0.3".to_string(),
+            prerelease: false,
+        };
+        let installed_as = installed_toolchains(&state, &remote.version);
+        assert_eq!(installed_as, vec!["esp", "esp-1.82"]);
+        assert_eq!(
+            remote.describe(&installed_as),
+            "1.82.0.3 (installed as 'esp', 'esp-1.82')"
+        );
+        assert_eq!(remote.describe(&[]), "1.82.0.3");
+        let remote = RemoteVersion {
+            version: "1.83.0.0".to_string(),
+            prerelease: true,
+        };
+        assert!(installed_toolchains(&state, &remote.version).is_empty());
+        assert_eq!(
+            remote.describe(&["esp"]),
+            "1.83.0.0 (prerelease, installed as 'esp')"
+        );
+    }
+
+    #[test]
+    fn test_component_status() {
+        let installed = |installed: &[&str], latest: Option<&str>| {
+            ComponentVersions::new(
+                "Xtensa Rust",
+                installed
+                    .iter()
+                    .map(|version| version.to_string())
+                    .collect(),
+                latest.map(str::to_string),
+            )
+        };
+        assert_eq!(installed(&["1.82.0.3"], Some("1.82.0.3")).status(), "ok");
+        assert_eq!(
+            installed(&["1.74.0.0"], Some("1.82.0.3")).status(),
+            "outdated"
+        );
+        assert_eq!(installed(&["1.74.0.0"], None).status(), "unknown");
+        // Components that are not installed are not outdated
+        assert!(!installed(&[], Some("1.82.0.3")).is_outdated());
+    }
+
+    #[test]
+    fn test_porcelain() {
+        let versions = ComponentVersions::new(
+            "LLVM",
+            vec!["15.0.0-20221201".to_string(), "16.0.0-20230516".to_string()],
+            Some("16.0.0-20230516".to_string()),
+        );
+        assert_eq!(
+            versions.porcelain(),
+            "LLVM\t15.0.0-20221201,16.0.0-20230516\t16.0.0-20230516"
+        );
+        let versions = ComponentVersions::new("espflash", Vec::new(), None);
+        assert_eq!(versions.porcelain(), "espflash\t\t");
+    }
+
+    #[test]
+    fn test_parse_installed_crates() {
+        let contents = r#"
+[v1]
+"espflash 2.1.0 (registry+https://github.com/rust-lang/crates.io-index)" = ["espflash", "cargo-espflash"]
+"ldproxy 0.3.2 (registry+https://github.com/rust-lang/crates.io-index)" = ["ldproxy"]
+"#;
+        assert_eq!(
+            parse_installed_crates(contents),
+            vec![
+                ("espflash".to_string(), "2.1.0".to_string()),
+                ("ldproxy".to_string(), "0.3.2".to_string())
+            ]
+        );
+        assert!(parse_installed_crates("invalid").is_empty());
+    }
+}
@@ -6,6 +6,17 @@ fn fails_with_no_arguments() {
         .failure();
 }
 
+#[test]
+fn fails_with_unsupported_json_output() {
+    let output = assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["host-info", "--output", "json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not support '--output json'"));
+}
+
 #[test]
 fn verify_help() {
     assert_cmd::Command::cargo_bin("espup")
@@ -15,6 +26,153 @@ fn verify_help() {
         .success();
 }
 
+#[test]
+fn verify_activate_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["activate", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_apply_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["apply", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_bundle_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["bundle", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg(feature = "cargo-espup")]
+fn verify_cargo_espup_help() {
+    assert_cmd::Command::cargo_bin("cargo-espup")
+        .unwrap()
+        .args(["espup", "install", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_check_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["check", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_clean_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["clean", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_component_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["component", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_diff_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["diff", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_doctor_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["doctor", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_env_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["env", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_explain_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["explain", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_freeze_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["freeze", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_gc_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["gc", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_generate_tasks_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["generate", "tasks", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_host_info_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["host-info", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_aliases_help() {
+    for alias in ["i", "rm", "up"] {
+        assert_cmd::Command::cargo_bin("espup")
+            .unwrap()
+            .args([alias, "--help"])
+            .assert()
+            .success();
+    }
+}
+
 #[test]
 fn verify_install_help() {
     assert_cmd::Command::cargo_bin("espup")
@@ -24,6 +182,60 @@ fn verify_install_help() {
         .success();
 }
 
+#[test]
+fn verify_keys_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["keys", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_list_remote_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["list-remote", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_restore_backup_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["restore-backup", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_self_update_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["self-update", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_start_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["start", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_sync_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["sync", "--help"])
+        .assert()
+        .success();
+}
+
 #[test]
 fn verify_update_help() {
     assert_cmd::Command::cargo_bin("espup")
@@ -41,3 +253,21 @@ fn verify_uninstall_help() {
         .assert()
         .success();
 }
+
+#[test]
+fn verify_versions_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["versions", "--help"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_which_help() {
+    assert_cmd::Command::cargo_bin("espup")
+        .unwrap()
+        .args(["which", "--help"])
+        .assert()
+        .success();
+}